@@ -0,0 +1,67 @@
+//! Timings for the FFT and constraint-processing hot paths: `cargo bench`.
+//!
+//! criterion is not a dependency, so this is a plain `harness = false` binary: each case
+//! runs for about a second after one warm-up call and reports the mean time per call.
+
+use r1cs::{Bn128, Element};
+use snark_rs::fft::FftEngine;
+use snark_rs::file;
+use snark_rs::r1cs::process_constraints;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const TARGET: Duration = Duration::from_secs(1);
+
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    black_box(f());
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while iterations == 0 || start.elapsed() < TARGET {
+        black_box(f());
+        iterations += 1;
+    }
+    let mean = start.elapsed() / iterations;
+    println!(
+        "{:<32} {:>12.3?}/iter ({} iterations)",
+        name, mean, iterations
+    );
+}
+
+fn bench_fft() {
+    let engine = FftEngine::<Bn128>::new(16).unwrap();
+    for bits in 10..=16 {
+        let input: Vec<Element<Bn128>> = (0..1u64 << bits)
+            .map(|i| Element::from(i * i + 7))
+            .collect();
+        let evals = engine.fft(&input);
+        assert_ne!(evals, input);
+
+        let mut out = Vec::new();
+        bench(&format!("fft 2^{}", bits), || {
+            engine.fft_into(black_box(&input), &mut out);
+            out.len()
+        });
+    }
+}
+
+fn bench_process_constraints() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let r1cs = runtime.block_on(async {
+        let (mut fd, sections) = file::read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        file::read_r1cs_fd(&mut fd, &sections).await
+    });
+    let r1cs = r1cs.unwrap();
+
+    let (constraints, _, stats) = process_constraints::<Bn128>(&r1cs, None).unwrap();
+    assert!(!constraints.is_empty());
+    assert!(stats.n_mul + stats.n_sum > 0);
+
+    bench("process_constraints small.r1cs", || {
+        process_constraints::<Bn128>(black_box(&r1cs), None).unwrap()
+    });
+}
+
+fn main() {
+    bench_fft();
+    bench_process_constraints();
+}
@@ -0,0 +1,288 @@
+#!/usr/bin/env python3
+"""Generates the small deterministic test artifacts under src/artifacts/.
+
+* pot8.ptau  - a prepared BN128 powers-of-tau file (power 8) built from a fixed
+               tau/alpha/beta, laid out like snarkjs' `powersoftau prepare phase2`.
+* small.r1cs - a tiny circuit mixing multiplication, linear and constant
+               constraints, including long linear combinations.
+
+Run from the repository root: python3 scripts/gen_artifacts.py
+"""
+
+import struct
+
+Q = 21888242871839275222246405745257275088696311157297823662689037894645226208583
+R = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+MONT = pow(2, 256, Q)
+
+POWER = 8
+TAU = 0x1234567890ABCDEF
+ALPHA = 0xA1FA
+BETA = 0xBE7A
+
+# ----------------------------------------------------------------------------
+# Fq2 = Fq[u] / (u^2 + 1)
+
+
+def f2_add(a, b):
+    return ((a[0] + b[0]) % Q, (a[1] + b[1]) % Q)
+
+
+def f2_sub(a, b):
+    return ((a[0] - b[0]) % Q, (a[1] - b[1]) % Q)
+
+
+def f2_mul(a, b):
+    return ((a[0] * b[0] - a[1] * b[1]) % Q, (a[0] * b[1] + a[1] * b[0]) % Q)
+
+
+def f2_inv(a):
+    d = pow((a[0] * a[0] + a[1] * a[1]) % Q, Q - 2, Q)
+    return (a[0] * d % Q, (-a[1]) * d % Q)
+
+
+class F1:
+    zero = 0
+    one = 1
+    add = staticmethod(lambda a, b: (a + b) % Q)
+    sub = staticmethod(lambda a, b: (a - b) % Q)
+    mul = staticmethod(lambda a, b: a * b % Q)
+    inv = staticmethod(lambda a: pow(a, Q - 2, Q))
+
+
+class F2:
+    zero = (0, 0)
+    one = (1, 0)
+    add = staticmethod(f2_add)
+    sub = staticmethod(f2_sub)
+    mul = staticmethod(f2_mul)
+    inv = staticmethod(f2_inv)
+
+
+# ----------------------------------------------------------------------------
+# Affine short Weierstrass arithmetic (a = 0); None is the point at infinity.
+
+
+def ec_add(F, p1, p2):
+    if p1 is None:
+        return p2
+    if p2 is None:
+        return p1
+    if p1[0] == p2[0]:
+        if p1[1] != p2[1] or p1[1] == F.zero:
+            return None
+        x2 = F.mul(p1[0], p1[0])
+        lam = F.mul(F.add(F.add(x2, x2), x2), F.inv(F.add(p1[1], p1[1])))
+    else:
+        lam = F.mul(F.sub(p2[1], p1[1]), F.inv(F.sub(p2[0], p1[0])))
+    x3 = F.sub(F.sub(F.mul(lam, lam), p1[0]), p2[0])
+    y3 = F.sub(F.mul(lam, F.sub(p1[0], x3)), p1[1])
+    return (x3, y3)
+
+
+def ec_mul(F, p, k):
+    k %= R
+    acc = None
+    while k:
+        if k & 1:
+            acc = ec_add(F, acc, p)
+        p = ec_add(F, p, p)
+        k >>= 1
+    return acc
+
+
+G1 = (1, 2)
+G2 = (
+    (
+        10857046999023057135944570762232829481370756359578518086990519993285655852781,
+        11559732032986387107991004021392285783925812861821192530917403151452391805634,
+    ),
+    (
+        8495653923123431417604973247489272438418190587263600148770280649306958101930,
+        4082367875863433681332203403145435568316851327593401208105741076214120093531,
+    ),
+)
+
+
+def fq_lem(x):
+    return (x * MONT % Q).to_bytes(32, "little")
+
+
+def g1_lem(p):
+    if p is None:
+        return bytes(64)
+    return fq_lem(p[0]) + fq_lem(p[1])
+
+
+def g2_lem(p):
+    if p is None:
+        return bytes(128)
+    return fq_lem(p[0][0]) + fq_lem(p[0][1]) + fq_lem(p[1][0]) + fq_lem(p[1][1])
+
+
+def g1_mul(k):
+    return ec_mul(F1, G1, k)
+
+
+def g2_mul(k):
+    return ec_mul(F2, G2, k)
+
+
+# ----------------------------------------------------------------------------
+# Binary container shared by ptau/r1cs/zkey.
+
+
+def bin_file(magic, version, sections):
+    out = bytearray(magic.encode())
+    out += struct.pack("<II", version, len(sections))
+    for sid, data in sections:
+        out += struct.pack("<IQ", sid, len(data))
+        out += data
+    return bytes(out)
+
+
+def root_of_unity(bits):
+    # Same derivation as ffjavascript: nqr^((r - 1) >> bits) with nqr = 5.
+    return pow(5, (R - 1) >> bits, R)
+
+
+def lagrange_scalars(bits):
+    n = 1 << bits
+    w_inv = pow(root_of_unity(bits), R - 2, R)
+    n_inv = pow(n, R - 2, R)
+    tau_pows = [pow(TAU, i, R) for i in range(n)]
+    out = []
+    for j in range(n):
+        wj = pow(w_inv, j, R)
+        acc, wij = 0, 1
+        for i in range(n):
+            acc = (acc + tau_pows[i] * wij) % R
+            wij = wij * wj % R
+        out.append(acc * n_inv % R)
+    return out
+
+
+def gen_ptau():
+    n = 1 << POWER
+    tau_pows = [pow(TAU, i, R) for i in range(2 * n - 1)]
+
+    header = struct.pack("<I", 32) + Q.to_bytes(32, "little") + struct.pack("<II", POWER, POWER)
+    tau_g1 = b"".join(g1_lem(g1_mul(t)) for t in tau_pows)
+    tau_g2 = b"".join(g2_lem(g2_mul(t)) for t in tau_pows[:n])
+    alpha_g1 = b"".join(g1_lem(g1_mul(ALPHA * t)) for t in tau_pows[:n])
+    beta_g1 = b"".join(g1_lem(g1_mul(BETA * t)) for t in tau_pows[:n])
+    beta_g2 = g2_lem(g2_mul(BETA))
+
+    contribution = bytearray()
+    contribution += g1_lem(g1_mul(TAU)) + g2_lem(g2_mul(TAU))
+    contribution += g1_lem(g1_mul(ALPHA)) + g1_lem(g1_mul(BETA)) + beta_g2
+    for s in (7, 11, 13):  # (g1_s, g1_sx, g2_spx) for tau, alpha, beta
+        contribution += g1_lem(g1_mul(s)) + g1_lem(g1_mul(s * 3))
+    for s in (7, 11, 13):
+        contribution += g2_lem(g2_mul(s * 5))
+    contribution += bytes(i % 256 for i in range(216))  # partial hash
+    contribution += bytes((0xC0 + i) % 256 for i in range(64))  # next challenge
+    contribution += struct.pack("<I", 0)  # type
+    name = b"test"
+    params = bytes([1, len(name)]) + name
+    contribution += struct.pack("<I", len(params)) + params
+    contributions = struct.pack("<I", 1) + bytes(contribution)
+
+    lagrange = {12: bytearray(), 13: bytearray(), 14: bytearray(), 15: bytearray()}
+    for p in range(POWER + 1):
+        for l in lagrange_scalars(p):
+            lagrange[12] += g1_lem(g1_mul(l))
+            lagrange[13] += g2_lem(g2_mul(l))
+            lagrange[14] += g1_lem(g1_mul(ALPHA * l))
+            lagrange[15] += g1_lem(g1_mul(BETA * l))
+
+    sections = [
+        (1, header),
+        (2, tau_g1),
+        (3, tau_g2),
+        (4, alpha_g1),
+        (5, beta_g1),
+        (6, beta_g2),
+        (7, contributions),
+        (12, bytes(lagrange[12])),
+        (13, bytes(lagrange[13])),
+        (14, bytes(lagrange[14])),
+        (15, bytes(lagrange[15])),
+    ]
+    return bin_file("ptau", 1, sections)
+
+
+# ----------------------------------------------------------------------------
+# R1CS: signal 0 is the constant one, then outputs, public and private inputs.
+
+N_OUTPUTS = 1
+N_PUB = 2
+N_PRV = 3
+
+
+def gen_r1cs():
+    constraints = []
+    n_vars = 1 + N_OUTPUTS + N_PUB + N_PRV
+
+    def new_var():
+        nonlocal n_vars
+        n_vars += 1
+        return n_vars - 1
+
+    inputs = list(range(2, 2 + N_PUB + N_PRV))
+
+    # x_{i+1} = x_i * x_{i+1}
+    prev = inputs[0]
+    for s in inputs[1:]:
+        v = new_var()
+        constraints.append(({prev: 1}, {s: 1}, {v: 1}))
+        prev = v
+
+    # Long sums in C: (a + 2b) * c = d + e + f + g + h
+    terms = [new_var() for _ in range(5)]
+    constraints.append(({inputs[0]: 1, inputs[1]: 2}, {inputs[2]: 1}, {t: 1 for t in terms}))
+    for i, t in enumerate(terms):
+        constraints.append(({inputs[i % len(inputs)]: 1}, {inputs[(i + 1) % len(inputs)]: 1}, {t: 1}))
+
+    # Constant A: 3 * (a + b + c) = d
+    v = new_var()
+    constraints.append(({0: 3}, {inputs[0]: 1, inputs[1]: 1, inputs[2]: 1}, {v: 1}))
+
+    # Linear: 0 * x = a - b - c - d (empty A)
+    w = new_var()
+    constraints.append(({}, {inputs[0]: 1}, {w: 1, inputs[1]: R - 1, inputs[2]: R - 1, prev: R - 1}))
+
+    # Constants in both A and B: (a + 5) * (b + 7) = c + 1
+    u = new_var()
+    constraints.append(({inputs[3]: 1, 0: 5}, {inputs[4]: 1, 0: 7}, {u: 1, 0: 1}))
+
+    # Output: out = prev * v
+    constraints.append(({prev: 1}, {v: 1}, {1: 1}))
+
+    # A chain of squarings to give the circuit some depth.
+    acc = u
+    for _ in range(24):
+        nxt = new_var()
+        constraints.append(({acc: 1}, {acc: 1}, {nxt: 1, w: R - 2}))
+        acc = nxt
+
+    header = struct.pack("<I", 32) + R.to_bytes(32, "little")
+    header += struct.pack("<IIII", n_vars, N_OUTPUTS, N_PUB, N_PRV)
+    header += struct.pack("<QI", n_vars, len(constraints))
+
+    body = bytearray()
+    for triple in constraints:
+        for lc in triple:
+            body += struct.pack("<I", len(lc))
+            for idx in sorted(lc):
+                body += struct.pack("<I", idx) + (lc[idx] % R).to_bytes(32, "little")
+
+    wire2label = b"".join(struct.pack("<Q", i) for i in range(n_vars))
+    return bin_file("r1cs", 1, [(1, header), (2, bytes(body)), (3, wire2label)])
+
+
+if __name__ == "__main__":
+    with open("src/artifacts/small.r1cs", "wb") as f:
+        f.write(gen_r1cs())
+    with open("src/artifacts/pot8.ptau", "wb") as f:
+        f.write(gen_ptau())
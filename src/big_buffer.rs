@@ -1,42 +1,97 @@
+use crate::error::{Result, SnarkError};
+use std::borrow::Cow;
 use std::cmp::min;
-use std::io::{Result, Write};
-use std::ops::{Deref, DerefMut};
+use std::io::{self, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
 const PAGE_SIZE: usize = 1 << 30; // 1 GB per page
 
+/// A byte buffer split into pages, for sections too large for a single allocation.
+///
+/// It deliberately does not dereference to `[u8]`; access goes through [`set`](Self::set),
+/// [`slice`](Self::slice) or whole pages:
+///
+/// ```compile_fail
+/// let buffer = snark_rs::big_buffer::BigBuffer::new(16);
+/// let bytes: &[u8] = &buffer;
+/// ```
 pub struct BigBuffer {
-    pub byte_length: usize,
-    pub buffers: Vec<Vec<u8>>, // Each Vec<u8> is a page
+    byte_length: usize,
+    page_size: usize,
+    buffers: Vec<Vec<u8>>, // Each Vec<u8> is a page
 }
 
 impl BigBuffer {
     pub fn new(size: usize) -> Self {
+        Self::with_page_size(size, PAGE_SIZE)
+    }
+
+    /// A buffer of `size` bytes split into pages of `page_size` bytes.
+    pub fn with_page_size(size: usize, page_size: usize) -> Self {
+        assert!(page_size > 0);
         let mut buffers = Vec::new();
         let mut remaining = size;
 
         while remaining > 0 {
-            let page_len = min(remaining, PAGE_SIZE);
+            let page_len = min(remaining, page_size);
             buffers.push(vec![0u8; page_len]);
             remaining -= page_len;
         }
 
         Self {
             byte_length: size,
+            page_size,
             buffers,
         }
     }
 
+    pub fn byte_length(&self) -> usize {
+        self.byte_length
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// The `idx`-th page. All pages but the last are full.
+    pub fn as_page(&self, idx: usize) -> &[u8] {
+        &self.buffers[idx]
+    }
+
+    /// Copies `input` to `offset`. Panics if it does not fit; see
+    /// [`checked_set`](Self::checked_set).
     pub fn set(&mut self, input: &[u8], offset: usize) {
-        assert!(offset + input.len() <= self.byte_length);
+        if let Err(err) = self.checked_set(input, offset) {
+            panic!("{}", err);
+        }
+    }
+
+    /// Copies `input` to `offset`, or fails with [`SnarkError::BufferOutOfBounds`] if
+    /// it would run past the end. An empty input fits at any offset up to the length.
+    pub fn checked_set(&mut self, input: &[u8], offset: usize) -> Result<()> {
+        match offset.checked_add(input.len()) {
+            Some(end) if end <= self.byte_length => {}
+            _ => {
+                return Err(SnarkError::BufferOutOfBounds {
+                    offset,
+                    length: input.len(),
+                    size: self.byte_length,
+                });
+            }
+        }
 
         let mut remaining = input.len();
         let mut input_offset = 0;
-        let mut page_idx = offset / PAGE_SIZE;
-        let mut page_offset = offset % PAGE_SIZE;
+        let mut page_idx = offset / self.page_size;
+        let mut page_offset = offset % self.page_size;
 
         while remaining > 0 {
             let page = &mut self.buffers[page_idx];
-            let len = min(PAGE_SIZE - page_offset, remaining);
+            let len = min(self.page_size - page_offset, remaining);
             page[page_offset..page_offset + len]
                 .copy_from_slice(&input[input_offset..input_offset + len]);
 
@@ -45,6 +100,7 @@ impl BigBuffer {
             page_idx += 1;
             page_offset = 0;
         }
+        Ok(())
     }
 
     pub fn slice(&self, from: usize, to: usize) -> Vec<u8> {
@@ -53,12 +109,12 @@ impl BigBuffer {
         let mut result = vec![0u8; to - from];
         let mut remaining = to - from;
         let mut result_offset = 0;
-        let mut page_idx = from / PAGE_SIZE;
-        let mut page_offset = from % PAGE_SIZE;
+        let mut page_idx = from / self.page_size;
+        let mut page_offset = from % self.page_size;
 
         while remaining > 0 {
             let page = &self.buffers[page_idx];
-            let len = min(PAGE_SIZE - page_offset, remaining);
+            let len = min(self.page_size - page_offset, remaining);
             result[result_offset..result_offset + len]
                 .copy_from_slice(&page[page_offset..page_offset + len]);
 
@@ -70,18 +126,343 @@ impl BigBuffer {
 
         result
     }
+
+    /// Grows the buffer to `size` bytes, zero-filling the new tail. Does nothing if it is
+    /// already that long.
+    pub fn extend_to(&mut self, size: usize) {
+        let mut remaining = size.saturating_sub(self.byte_length);
+        self.byte_length = self.byte_length.max(size);
+
+        if let Some(last) = self.buffers.last_mut() {
+            let len = min(self.page_size - last.len(), remaining);
+            last.resize(last.len() + len, 0);
+            remaining -= len;
+        }
+        while remaining > 0 {
+            let page_len = min(remaining, self.page_size);
+            self.buffers.push(vec![0u8; page_len]);
+            remaining -= page_len;
+        }
+    }
+
+    /// The pages in order.
+    pub fn iter_pages(&self) -> impl Iterator<Item = &[u8]> {
+        self.buffers.iter().map(Vec::as_slice)
+    }
+
+    /// Consecutive `chunk`-byte pieces of the buffer, as if it were one slice. Chunks
+    /// within a page are borrowed; only those straddling a page boundary are copied.
+    ///
+    /// If `chunk` does not divide the length, the last chunk is short.
+    pub fn iter_chunks(&self, chunk: usize) -> impl Iterator<Item = Cow<'_, [u8]>> {
+        assert!(chunk > 0);
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            if offset >= self.byte_length {
+                return None;
+            }
+            let end = min(offset + chunk, self.byte_length);
+            let (page_idx, page_offset) = (offset / self.page_size, offset % self.page_size);
+            let item = if page_offset + (end - offset) <= self.page_size {
+                Cow::Borrowed(&self.buffers[page_idx][page_offset..page_offset + end - offset])
+            } else {
+                Cow::Owned(self.slice(offset, end))
+            };
+            offset = end;
+            Some(item)
+        })
+    }
+}
+
+/// A growable [`BigBuffer`] with a cursor, so a [`BinFile`](crate::file::BinFile) can
+/// write a whole zkey into memory. Writes past the end grow the buffer, and seeking back
+/// to patch section sizes goes through [`BigBuffer::set`].
+pub struct BigBufferStorage {
+    buffer: BigBuffer,
+    pos: u64,
+}
+
+impl BigBufferStorage {
+    pub fn new() -> Self {
+        Self::with_page_size(PAGE_SIZE)
+    }
+
+    pub fn with_page_size(page_size: usize) -> Self {
+        Self {
+            buffer: BigBuffer::with_page_size(0, page_size),
+            pos: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> BigBuffer {
+        self.buffer
+    }
 }
 
-impl Deref for BigBuffer {
-    type Target = [u8];
+impl Default for BigBufferStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    fn deref(&self) -> &Self::Target {
-        panic!("Direct deref not supported. Use `slice` instead.");
+impl AsyncRead for BigBufferStorage {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = min(this.pos as usize, this.buffer.byte_length);
+        let end = min(start + buf.remaining(), this.buffer.byte_length);
+        buf.put_slice(&this.buffer.slice(start, end));
+        this.pos = end as u64;
+        Poll::Ready(Ok(()))
     }
 }
 
-impl DerefMut for BigBuffer {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        panic!("Direct deref_mut not supported. Use `set` instead.");
+impl AsyncWrite for BigBufferStorage {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let start = this.pos as usize;
+        this.buffer.extend_to(start + data.len());
+        this.buffer.set(data, start);
+        this.pos += data.len() as u64;
+        Poll::Ready(Ok(data.len()))
     }
-}
\ No newline at end of file
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for BigBufferStorage {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        let (base, offset) = match position {
+            SeekFrom::Start(offset) => {
+                this.pos = offset;
+                return Ok(());
+            }
+            SeekFrom::End(offset) => (this.buffer.byte_length as u64, offset),
+            SeekFrom::Current(offset) => (this.pos, offset),
+        };
+        this.pos = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek to a negative position")
+        })?;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
+
+/// Overwrites every page with zeros, keeping the buffer's length.
+#[cfg(feature = "zeroize")]
+impl Zeroize for BigBuffer {
+    fn zeroize(&mut self) {
+        self.buffers.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+/// A [`BigBuffer`] whose pages are zeroed when it is dropped, for secret material such
+/// as toxic waste or intermediate SRS values.
+#[cfg(feature = "zeroize")]
+pub struct SecureBigBuffer(BigBuffer);
+
+#[cfg(feature = "zeroize")]
+impl SecureBigBuffer {
+    pub fn new(size: usize) -> Self {
+        Self(BigBuffer::new(size))
+    }
+
+    pub fn with_page_size(size: usize, page_size: usize) -> Self {
+        Self(BigBuffer::with_page_size(size, page_size))
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl From<BigBuffer> for SecureBigBuffer {
+    fn from(buffer: BigBuffer) -> Self {
+        Self(buffer)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl std::ops::Deref for SecureBigBuffer {
+    type Target = BigBuffer;
+
+    fn deref(&self) -> &BigBuffer {
+        &self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl std::ops::DerefMut for SecureBigBuffer {
+    fn deref_mut(&mut self) -> &mut BigBuffer {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for SecureBigBuffer {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for SecureBigBuffer {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for SecureBigBuffer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_slice_across_pages() {
+        let mut buffer = BigBuffer::with_page_size(40, 16);
+        assert_eq!(buffer.page_count(), 3);
+        assert_eq!(buffer.as_page(2).len(), 8);
+
+        // Spans the end of page 0, all of page 1 and the start of page 2.
+        let data: Vec<u8> = (1..=24).collect();
+        buffer.set(&data, 10);
+        assert_eq!(buffer.slice(10, 34), data);
+        assert_eq!(buffer.as_page(0)[10..], data[..6]);
+        assert_eq!(buffer.as_page(1), &data[6..22]);
+        assert_eq!(buffer.as_page(2)[..2], data[22..]);
+
+        // Boundaries on both ends, and empty slices.
+        assert_eq!(buffer.slice(16, 32), data[6..22]);
+        assert_eq!(buffer.slice(15, 17), [data[5], data[6]]);
+        assert!(buffer.slice(16, 16).is_empty());
+        assert!(buffer.slice(40, 40).is_empty());
+        assert_eq!(buffer.slice(0, 10), [0; 10]);
+        assert_eq!(buffer.slice(34, 40), [0; 6]);
+
+        assert_eq!(BigBuffer::new(40).page_count(), 1);
+    }
+
+    #[test]
+    fn test_checked_set_bounds() {
+        let mut buffer = BigBuffer::with_page_size(40, 16);
+        buffer.checked_set(&[], 40).unwrap();
+        buffer.checked_set(&[7; 8], 32).unwrap();
+        assert_eq!(buffer.as_page(2), [7; 8]);
+        assert!(matches!(
+            buffer.checked_set(&[1], 40),
+            Err(SnarkError::BufferOutOfBounds {
+                offset: 40,
+                length: 1,
+                size: 40
+            })
+        ));
+        assert!(matches!(
+            buffer.checked_set(&[1], usize::MAX),
+            Err(SnarkError::BufferOutOfBounds { .. })
+        ));
+        assert!(buffer.checked_set(&[], 41).is_err());
+
+        // No pages at all: only the empty write at 0 fits.
+        let mut empty = BigBuffer::new(0);
+        assert_eq!(empty.page_count(), 0);
+        empty.checked_set(&[], 0).unwrap();
+        assert!(matches!(
+            empty.checked_set(&[1, 2], 0),
+            Err(SnarkError::BufferOutOfBounds {
+                offset: 0,
+                length: 2,
+                size: 0
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_storage_matches_cursor() -> crate::Result<()> {
+        use crate::file::BinFile;
+        use std::io::Cursor;
+
+        // Page size 5 puts every section header and size patch across page boundaries.
+        let mut from_buffer =
+            BinFile::create_in(BigBufferStorage::with_page_size(5), "zkey", 1, 2).await?;
+        let mut from_cursor = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 2).await?;
+        for fd in [&mut from_buffer, &mut from_cursor] {
+            for (id, len) in [(1, 7u8), (2, 23)] {
+                fd.start_write_section(id).await?;
+                fd.write_bytes(&(0..len).collect::<Vec<_>>()).await?;
+                fd.end_write_section().await?;
+            }
+            fd.flush().await?;
+        }
+
+        let buffer = from_buffer
+            .into_storage::<BigBufferStorage>()
+            .unwrap()
+            .into_inner();
+        let expected = from_cursor
+            .into_storage::<Cursor<Vec<u8>>>()
+            .unwrap()
+            .into_inner();
+        assert_eq!(buffer.page_count(), expected.len().div_ceil(5));
+        assert_eq!(buffer.slice(0, buffer.byte_length()), expected);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Out-of-bounds write")]
+    fn test_set_past_end() {
+        BigBuffer::with_page_size(20, 16).set(&[1, 2, 3], 18);
+    }
+
+    #[test]
+    fn test_iter_chunks() {
+        let data: Vec<u8> = (0..100u32).map(|i| (i * 7) as u8).collect();
+        let mut buffer = BigBuffer::with_page_size(data.len(), 16);
+        buffer.set(&data, 0);
+        assert_eq!(buffer.page_count(), 7);
+        assert_eq!(buffer.iter_pages().map(<[u8]>::len).sum::<usize>(), 100);
+
+        let expected: u64 = data.iter().map(|&b| b as u64).sum();
+        for chunk in [1, 6, 16, 30, 100, 128] {
+            let chunks: Vec<_> = buffer.iter_chunks(chunk).collect();
+            assert_eq!(chunks.len(), data.len().div_ceil(chunk));
+            let sum: u64 = chunks.iter().flat_map(|c| c.iter()).map(|&b| b as u64).sum();
+            assert_eq!(sum, expected, "chunk size {}", chunk);
+            assert_eq!(chunks.concat(), data);
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_secure_big_buffer_zeroizes() {
+        fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<SecureBigBuffer>();
+
+        let data: Vec<u8> = (1..=40).collect();
+        let mut buffer = SecureBigBuffer::with_page_size(data.len(), 16);
+        buffer.set(&data, 0);
+        assert_eq!(buffer.slice(0, 40), data);
+
+        // Drop runs exactly this; the pages are freed right after, so wipe in place
+        // to observe them.
+        buffer.zeroize();
+        assert_eq!(buffer.byte_length(), 40);
+        assert_eq!(buffer.page_count(), 3);
+        assert!(buffer.iter_pages().all(|page| page.iter().all(|&b| b == 0)));
+    }
+}
@@ -0,0 +1,88 @@
+//! Synchronous wrappers over the async API, for callers without a tokio runtime.
+//!
+//! Each call drives the async implementation to completion on a private
+//! current-thread runtime, so it must not be used from within an async context.
+
+use crate::error::Result;
+use crate::file::{self, BinFile, Section};
+use crate::plonk;
+use crate::utils::ProgressCallback;
+use std::collections::HashMap;
+use std::future::Future;
+
+fn block_on<F: Future>(future: F) -> Result<F::Output> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(runtime.block_on(future))
+}
+
+/// Blocking version of [`file::read_bin_file`].
+///
+/// The file is read into memory and the returned [`BinFile`] is backed by those
+/// bytes, so it stays usable after the private runtime is gone: read from it with
+/// [`read_section_blocking`].
+pub fn read_bin_file_blocking(
+    file_name: &str,
+    expected_type: &str,
+    max_version: u32,
+) -> Result<(BinFile, HashMap<u32, Vec<Section>>)> {
+    let fd = BinFile::from_bytes(std::fs::read(file_name)?);
+    block_on(file::read_bin_file_fd(fd, expected_type, max_version))?
+}
+
+/// Blocking version of [`file::read_section`].
+pub fn read_section_blocking(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    section_id: u32,
+    offset: Option<u64>,
+    length: Option<u64>,
+) -> Result<Vec<u8>> {
+    block_on(file::read_section(fd, sections, section_id, offset, length))?
+}
+
+/// Blocking version of [`plonk::plonk_setup`].
+pub fn plonk_setup_blocking(
+    ptau_path: &str,
+    r1cs_path: &str,
+    out_path: &str,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    block_on(plonk::plonk_setup(ptau_path, r1cs_path, out_path, progress))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_blocking_api() -> Result<()> {
+        let (mut fd, sections) = read_bin_file_blocking("src/artifacts/pot8.ptau", "ptau", 1)?;
+        for id in [1, 2, 3, 12] {
+            assert!(sections.contains_key(&id), "missing section {}", id);
+        }
+
+        // The handle outlives the runtime that parsed it.
+        let contents = std::fs::read("src/artifacts/pot8.ptau")?;
+        let start = sections[&1][0].offset as usize;
+        let header = read_section_blocking(&mut fd, &sections, 1, None, None)?;
+        assert_eq!(header, contents[start..start + header.len()]);
+        let tau_g1 = read_section_blocking(&mut fd, &sections, 2, Some(64), Some(64))?;
+        let start = sections[&2][0].offset as usize + 64;
+        assert_eq!(tau_g1, contents[start..start + 64]);
+
+        let dir = tempdir()?;
+        let out_path = dir.path().join("output.zkey");
+        plonk_setup_blocking(
+            "src/artifacts/pot8.ptau",
+            "src/artifacts/small.r1cs",
+            out_path.to_str().unwrap(),
+            None,
+        )?;
+        assert_eq!(&std::fs::read(&out_path)?[0..4], b"zkey");
+
+        Ok(())
+    }
+}
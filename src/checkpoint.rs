@@ -0,0 +1,215 @@
+use crate::error::{Result, SnarkError};
+use crate::file::{self, BinFile, Section, SectionCursor};
+use crate::plonk::write_additions;
+use crate::r1cs::{PlonkAddition, PlonkConstraint, PlonkStats};
+use ::r1cs::num::BigUint;
+use ::r1cs::{Bn128, Element};
+use std::collections::HashMap;
+
+/// Format version written by [`write_checkpoint_fd`].
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+pub const CHECKPOINT_HEADER_SECTION: u32 = 1;
+pub const CHECKPOINT_CONSTRAINTS_SECTION: u32 = 2;
+pub const CHECKPOINT_ADDITIONS_SECTION: u32 = 3;
+
+const CHECKPOINT_N_SECTIONS: u32 = 3;
+const FR_N8: usize = 32;
+
+/// Setup state after the r1cs has been converted to PLONK gates, before any FFT or
+/// commitment. Saving it lets a failed setup resume without re-reading the circuit,
+/// see [`plonk_setup_with_checkpoint`](crate::plonk::plonk_setup_with_checkpoint).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetupCheckpoint {
+    /// Prime of the r1cs the gates were built from.
+    pub prime: BigUint,
+    /// Log2 of the evaluation domain size.
+    pub domain_power: u32,
+    pub constraints: Vec<PlonkConstraint>,
+    pub additions: Vec<PlonkAddition>,
+    pub stats: PlonkStats,
+}
+
+/// Writes `checkpoint` as a "pchk" binary file: a header section with the prime,
+/// domain power and gate statistics, then the gates and the additions.
+pub async fn write_checkpoint_fd(fd: &mut BinFile, checkpoint: &SetupCheckpoint) -> Result<()> {
+    fd.start_write_section(CHECKPOINT_HEADER_SECTION).await?;
+    fd.write_u32(FR_N8 as u32).await?;
+    let mut prime = checkpoint.prime.to_bytes_le();
+    prime.resize(FR_N8, 0);
+    fd.write_bytes(&prime).await?;
+    fd.write_u32(checkpoint.domain_power).await?;
+    let stats = &checkpoint.stats;
+    for count in [stats.n_mul, stats.n_sum, stats.n_public, stats.n_additions] {
+        fd.write_u32(count as u32).await?;
+    }
+    fd.write_u32(stats.final_n_vars).await?;
+    fd.write_u32(checkpoint.constraints.len() as u32).await?;
+    fd.write_u32(checkpoint.additions.len() as u32).await?;
+    fd.end_write_section().await?;
+
+    fd.start_write_section(CHECKPOINT_CONSTRAINTS_SECTION).await?;
+    for gate in &checkpoint.constraints {
+        write_gate(fd, gate).await?;
+    }
+    fd.end_write_section().await?;
+
+    write_additions(
+        fd,
+        CHECKPOINT_ADDITIONS_SECTION,
+        "Additions",
+        FR_N8,
+        &checkpoint.additions,
+        None,
+    )
+    .await?;
+
+    fd.flush().await
+}
+
+/// Reads a checkpoint written by [`write_checkpoint_fd`].
+pub async fn read_checkpoint_fd(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+) -> Result<SetupCheckpoint> {
+    let buf = file::read_section(fd, sections, CHECKPOINT_HEADER_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(CHECKPOINT_HEADER_SECTION, &buf);
+    let n8r = cursor.read_u32()? as usize;
+    if n8r != FR_N8 {
+        return Err(SnarkError::InvalidFieldSize {
+            expected: FR_N8,
+            got: n8r,
+        });
+    }
+    let prime = cursor.read_biguint(n8r)?;
+    let domain_power = cursor.read_u32()?;
+    let stats = PlonkStats {
+        n_mul: cursor.read_u32()? as usize,
+        n_sum: cursor.read_u32()? as usize,
+        n_public: cursor.read_u32()? as usize,
+        n_additions: cursor.read_u32()? as usize,
+        final_n_vars: cursor.read_u32()?,
+    };
+    let n_constraints = cursor.read_u32()? as usize;
+    let n_additions = cursor.read_u32()? as usize;
+    cursor.finish()?;
+
+    let buf = file::read_section(fd, sections, CHECKPOINT_CONSTRAINTS_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(CHECKPOINT_CONSTRAINTS_SECTION, &buf);
+    let mut constraints = Vec::with_capacity(n_constraints);
+    for _ in 0..n_constraints {
+        constraints.push(read_gate(&mut cursor)?);
+    }
+    cursor.finish()?;
+
+    let buf = file::read_section(fd, sections, CHECKPOINT_ADDITIONS_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(CHECKPOINT_ADDITIONS_SECTION, &buf);
+    let mut additions = Vec::with_capacity(n_additions);
+    for _ in 0..n_additions {
+        additions.push(read_addition(&mut cursor)?);
+    }
+    cursor.finish()?;
+
+    Ok(SetupCheckpoint {
+        prime,
+        domain_power,
+        constraints,
+        additions,
+        stats,
+    })
+}
+
+/// Writes a gate as its three signals followed by its five selectors.
+pub(crate) async fn write_gate(fd: &mut BinFile, gate: &PlonkConstraint) -> Result<()> {
+    let (a, b, c, qm, ql, qr, qo, qc) = gate;
+    for signal in [a, b, c] {
+        fd.write_u32(*signal).await?;
+    }
+    for coef in [qm, ql, qr, qo, qc] {
+        fd.write_field(coef, FR_N8).await?;
+    }
+    Ok(())
+}
+
+/// Writes an addition in the layout of the zkey additions section.
+pub(crate) async fn write_addition(fd: &mut BinFile, addition: &PlonkAddition) -> Result<()> {
+    let (sl, sr, cl, cr) = addition;
+    fd.write_u32(*sl).await?;
+    fd.write_u32(*sr).await?;
+    fd.write_field(cl, FR_N8).await?;
+    fd.write_field(cr, FR_N8).await
+}
+
+pub(crate) fn read_gate(cursor: &mut SectionCursor<'_>) -> Result<PlonkConstraint> {
+    Ok((
+        cursor.read_u32()?,
+        cursor.read_u32()?,
+        cursor.read_u32()?,
+        read_fr(cursor)?,
+        read_fr(cursor)?,
+        read_fr(cursor)?,
+        read_fr(cursor)?,
+        read_fr(cursor)?,
+    ))
+}
+
+pub(crate) fn read_addition(cursor: &mut SectionCursor<'_>) -> Result<PlonkAddition> {
+    Ok((
+        cursor.read_u32()?,
+        cursor.read_u32()?,
+        read_fr(cursor)?,
+        read_fr(cursor)?,
+    ))
+}
+
+fn read_fr(cursor: &mut SectionCursor<'_>) -> Result<Element<Bn128>> {
+    cursor.read_field(FR_N8)
+}
+
+/// Saves `checkpoint` to `path`, replacing any previous file.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn write_checkpoint(path: &str, checkpoint: &SetupCheckpoint) -> Result<()> {
+    let mut fd =
+        BinFile::create(path, "pchk", CHECKPOINT_VERSION, CHECKPOINT_N_SECTIONS).await?;
+    write_checkpoint_fd(&mut fd, checkpoint).await
+}
+
+/// Loads a checkpoint saved by [`write_checkpoint`].
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn read_checkpoint(path: &str) -> Result<SetupCheckpoint> {
+    let (mut fd, sections) = file::read_bin_file(path, "pchk", CHECKPOINT_VERSION).await?;
+    read_checkpoint_fd(&mut fd, &sections).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r1cs;
+    use tempfile::tempdir;
+
+    const R1CS_PATH: &str = "src/artifacts/small.r1cs";
+
+    #[tokio::test]
+    async fn test_checkpoint_round_trip() -> Result<()> {
+        let (mut fd, sections) = file::read_bin_file(R1CS_PATH, "r1cs", 1).await?;
+        let r1cs = file::read_r1cs_fd(&mut fd, &sections).await?;
+        let (constraints, additions, stats) = r1cs::process_constraints(&r1cs, None)?;
+        let checkpoint = SetupCheckpoint {
+            prime: r1cs.header.prime.clone(),
+            domain_power: 7,
+            constraints,
+            additions,
+            stats,
+        };
+
+        let dir = tempdir()?;
+        let path = dir.path().join("setup.pchk");
+        let path = path.to_str().unwrap();
+        write_checkpoint(path, &checkpoint).await?;
+
+        let loaded = read_checkpoint(path).await?;
+        assert_eq!(loaded.constraints, checkpoint.constraints);
+        assert_eq!(loaded, checkpoint);
+        Ok(())
+    }
+}
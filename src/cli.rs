@@ -0,0 +1,141 @@
+use crate::config::{SetupConfig, load_config};
+use crate::error::{Result, SnarkError};
+use crate::ptau_file::verify_ptau;
+use crate::zkey::{ZkeyProtocol, setup_with_config};
+use std::path::Path;
+
+pub const USAGE: &str = "Usage: snark-rs plonk setup <ptau> <r1cs> <out.zkey> [--verbose]
+       snark-rs plonk setup --config <setup.json> [<ptau> <r1cs> <out.zkey>] [--verbose]
+       snark-rs ptau verify <ptau>";
+
+/// A parsed command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `<protocol> setup <ptau> <r1cs> <out.zkey> [--verbose]`, with the protocol and
+    /// `--verbose` folded into the config.
+    Setup(SetupConfig),
+    /// `ptau verify <ptau>`
+    PtauVerify {
+        ptau: String,
+    },
+    Help,
+}
+
+/// Parses the arguments following the program name and checks that input files exist.
+///
+/// `plonk setup` can take its inputs from a [`SetupConfig`] file given with `--config`.
+/// The protocol, paths and `--verbose` on the command line take precedence over the
+/// file.
+pub fn parse_args<I, S>(args: I) -> Result<Command>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut verbose = false;
+    let mut config_path = None;
+    let mut positional = Vec::new();
+    let mut args = args.into_iter().map(Into::into);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-v" | "--verbose" => verbose = true,
+            "-c" | "--config" => {
+                config_path = Some(args.next().ok_or_else(|| {
+                    SnarkError::InvalidArguments(format!("{} needs a file", arg))
+                })?);
+            }
+            flag if flag.starts_with('-') => {
+                return Err(SnarkError::InvalidArguments(format!(
+                    "unknown flag {}",
+                    flag
+                )));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let config = config_path.as_deref().map(load_config).transpose()?;
+    match (positional.as_slice(), config) {
+        ([group, cmd, ptau, r1cs, out], config) if is_setup(group, cmd) => {
+            let mut setup = config.unwrap_or_else(|| SetupConfig::new(ptau, r1cs, out));
+            (setup.ptau, setup.r1cs, setup.out) = (ptau.clone(), r1cs.clone(), out.clone());
+            setup_command(setup, group, verbose)
+        }
+        ([group, cmd], Some(config)) if is_setup(group, cmd) => {
+            setup_command(config, group, verbose)
+        }
+        ([group, cmd, ptau], None) if group == "ptau" && cmd == "verify" => {
+            if !Path::new(ptau).is_file() {
+                return Err(SnarkError::InvalidArguments(format!(
+                    "input file not found: {}",
+                    ptau
+                )));
+            }
+            Ok(Command::PtauVerify { ptau: ptau.clone() })
+        }
+        ([], _) => Ok(Command::Help),
+        _ => Err(SnarkError::InvalidArguments(format!(
+            "unrecognized command: {}",
+            positional.join(" ")
+        ))),
+    }
+}
+
+/// Only PLONK has a complete setup; see [`setup_with_config`].
+fn is_setup(group: &str, cmd: &str) -> bool {
+    cmd == "setup" && group == ZkeyProtocol::Plonk.name()
+}
+
+fn setup_command(mut setup: SetupConfig, protocol: &str, verbose: bool) -> Result<Command> {
+    setup.curve()?;
+    setup.protocol = ZkeyProtocol::from_name(protocol)?;
+    for input in [&setup.ptau, &setup.r1cs] {
+        if !Path::new(input).is_file() {
+            return Err(SnarkError::InvalidArguments(format!(
+                "input file not found: {}",
+                input
+            )));
+        }
+    }
+    setup.verbose |= verbose;
+    Ok(Command::Setup(setup))
+}
+
+/// Executes a parsed command.
+pub async fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Setup(config) => {
+            let progress = |stage: &str, done: usize, total: usize| {
+                println!("ℹ️  {}: {}/{}", stage, done, total);
+            };
+            setup_with_config(&config, config.verbose.then_some(&progress as _)).await
+        }
+        Command::PtauVerify { ptau } => {
+            let report = verify_ptau(&ptau).await?;
+            println!(
+                "ℹ️  version {}, power {} (ceremony power {})",
+                report.version, report.power, report.ceremony_power
+            );
+            println!(
+                "ℹ️  tau G1 points: {} (expected {})",
+                report.tau_g1_points,
+                report.expected_points()
+            );
+            match report.lagrange_points {
+                Some(points) => println!(
+                    "ℹ️  lagrange points: {} (expected {})",
+                    points,
+                    report.expected_points()
+                ),
+                None => println!("ℹ️  lagrange points: section 12 missing"),
+            }
+            report.check()?;
+            println!("✅ {} is ready for a PLONK setup", ptau);
+            Ok(())
+        }
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    }
+}
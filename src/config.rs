@@ -0,0 +1,164 @@
+use crate::curves::Curve;
+use crate::error::{Result, SnarkError};
+use crate::json::{Json, JsonValue};
+use crate::zkey::ZkeyProtocol;
+
+/// Inputs of a setup, as given on the command line or in a config file for
+/// [`setup_with_config`](crate::zkey::setup_with_config).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupConfig {
+    pub ptau: String,
+    pub r1cs: String,
+    pub out: String,
+    /// Curve name, in any spelling [`Curve::from_name`] accepts.
+    pub curve: String,
+    /// Layout of the zkey to produce.
+    pub protocol: ZkeyProtocol,
+    pub verbose: bool,
+}
+
+impl SetupConfig {
+    /// PLONK setup over BN128, without progress output.
+    pub fn new(ptau: &str, r1cs: &str, out: &str) -> Self {
+        Self {
+            ptau: ptau.to_string(),
+            r1cs: r1cs.to_string(),
+            out: out.to_string(),
+            curve: Curve::new().name().to_string(),
+            protocol: ZkeyProtocol::default(),
+            verbose: false,
+        }
+    }
+
+    /// The configured curve. Fails for names [`Curve::from_name`] rejects.
+    pub fn curve(&self) -> Result<Curve> {
+        Curve::from_name(&self.curve)
+    }
+
+    /// Fails with [`SnarkError::CurveMismatch`] unless the configured curve is `ptau`, the
+    /// curve the ptau was generated over.
+    pub fn check_ptau_curve(&self, ptau: &Curve) -> Result<()> {
+        let curve = self.curve()?;
+        if curve.q != ptau.q {
+            return Err(SnarkError::CurveMismatch {
+                config: curve.q,
+                ptau: ptau.q.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// `curve`, `protocol` and `verbose` are optional when reading and default as in [`SetupConfig::new`].
+impl Json for SetupConfig {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("ptau".to_string(), JsonValue::from(self.ptau.as_str())),
+            ("r1cs".to_string(), JsonValue::from(self.r1cs.as_str())),
+            ("out".to_string(), JsonValue::from(self.out.as_str())),
+            ("curve".to_string(), JsonValue::from(self.curve.as_str())),
+            (
+                "protocol".to_string(),
+                JsonValue::from(self.protocol.name()),
+            ),
+            ("verbose".to_string(), JsonValue::Bool(self.verbose)),
+        ])
+    }
+
+    fn from_json(value: &JsonValue) -> Result<Self> {
+        let mut config = Self::new(
+            value.str_field("ptau")?,
+            value.str_field("r1cs")?,
+            value.str_field("out")?,
+        );
+        if value.get("curve").is_some() {
+            config.curve = value.str_field("curve")?.to_string();
+        }
+        if value.get("protocol").is_some() {
+            config.protocol = ZkeyProtocol::from_name(value.str_field("protocol")?)?;
+        }
+        if value.get("verbose").is_some() {
+            config.verbose = value.bool_field("verbose")?;
+        }
+        Ok(config)
+    }
+}
+
+/// Reads a JSON setup config. Relative paths in it are kept as written, i.e. relative
+/// to the working directory rather than to the config file.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_config(path: &str) -> Result<SetupConfig> {
+    let text = std::fs::read_to_string(path)?;
+    SetupConfig::from_json(&JsonValue::parse(&text)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_config() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("setup.json");
+        let path = path.to_str().unwrap();
+
+        std::fs::write(
+            path,
+            r#"{"ptau": "pot8.ptau", "r1cs": "small.r1cs", "out": "small.zkey",
+                "curve": "BN254", "protocol": "groth16", "verbose": true}"#,
+        )?;
+        let config = load_config(path)?;
+        assert_eq!(
+            config,
+            SetupConfig {
+                ptau: "pot8.ptau".to_string(),
+                r1cs: "small.r1cs".to_string(),
+                out: "small.zkey".to_string(),
+                curve: "BN254".to_string(),
+                protocol: ZkeyProtocol::Groth16,
+                verbose: true,
+            }
+        );
+        assert_eq!(config.curve()?.name(), "bn128");
+        assert_eq!(SetupConfig::from_json(&config.to_json())?, config);
+
+        // Optional fields take their defaults.
+        std::fs::write(path, r#"{"ptau": "a", "r1cs": "b", "out": "c"}"#)?;
+        assert_eq!(load_config(path)?, SetupConfig::new("a", "b", "c"));
+
+        std::fs::write(
+            path,
+            r#"{"ptau": "a", "r1cs": "b", "out": "c", "curve": "foo"}"#,
+        )?;
+        assert!(matches!(
+            load_config(path)?.curve(),
+            Err(SnarkError::UnknownCurveName(_))
+        ));
+        std::fs::write(
+            path,
+            r#"{"ptau": "a", "r1cs": "b", "out": "c", "protocol": "fflonk"}"#,
+        )?;
+        assert!(matches!(
+            load_config(path),
+            Err(SnarkError::UnknownProtocolName(name)) if name == "fflonk"
+        ));
+        std::fs::write(path, r#"{"ptau": "a", "out": "c"}"#)?;
+        assert!(matches!(load_config(path), Err(SnarkError::InvalidJson(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_ptau_curve() {
+        let config = SetupConfig::new("a", "b", "c");
+        let curve = Curve::new();
+        assert!(config.check_ptau_curve(&curve).is_ok());
+
+        let mut other = Curve::new();
+        other.q += 2u32;
+        assert!(matches!(
+            config.check_ptau_curve(&other),
+            Err(SnarkError::CurveMismatch { config, ptau }) if config == curve.q && ptau == other.q
+        ));
+    }
+}
@@ -1,10 +1,16 @@
-use anyhow::{Result, bail};
+use crate::error::{Result, SnarkError};
+use crate::fft::FftField;
 use r1cs::Bn128;
 use r1cs::num::BigUint;
 
 // TODO: add compatibility with BLS12-381.
 // use r1cs::Bls12_381;
 
+/// BLS12-381 base field modulus, recognized by name but not supported yet.
+fn bls12_381_q() -> BigUint {
+    BigUint::parse_bytes(b"1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaab", 16).unwrap()
+}
+
 #[derive(Debug)]
 pub struct CustomField {
     pub n64: usize, // Number of 64-bit words
@@ -18,6 +24,8 @@ pub struct Curve {
     pub n8q: usize, // bytes for q field (Fq, G1/G2 coords)
     pub n8r: usize, // bytes for r field (Fr, scalar field)
     pub fr: Bn128,
+    /// Largest `s` with a `2^s`-th root of unity in Fr, bounding the FFT domain.
+    pub two_adicity: u32,
 }
 
 impl Curve {
@@ -46,6 +54,7 @@ impl Curve {
             n8q: 32,
             n8r: 32,
             fr: Bn128 {},
+            two_adicity: Bn128::two_adicity(),
         }
     }
 
@@ -59,11 +68,38 @@ impl Curve {
         if q == &Self::q() {
             Ok(Self::new())
         } else {
-            bail!("Curve not supported: {}", q);
+            Err(SnarkError::CurveNotSupported(q.clone()))
+        }
+    }
+
+    /// The curve's name as snarkjs writes it, e.g. in `verification_key.json`.
+    pub fn name(&self) -> &'static str {
+        "bn128"
+    }
+
+    /// Looks a curve up by name, ignoring case and punctuation as snarkjs does, so
+    /// "BN254" and "alt_bn128" both name BN128. BLS12-381 is recognized but fails with
+    /// [`SnarkError::CurveNotSupported`] like its modulus does in [`from_q`](Self::from_q).
+    pub fn from_name(name: &str) -> Result<Self> {
+        let normalized: String = name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match normalized.as_str() {
+            "bn128" | "bn254" | "altbn128" => Ok(Self::new()),
+            "bls12381" => Err(SnarkError::CurveNotSupported(bls12_381_q())),
+            _ => Err(SnarkError::UnknownCurveName(name.to_string())),
         }
     }
 }
 
+impl Default for Curve {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,9 +120,25 @@ mod tests {
         assert_eq!(curve.f1.n64, 6);
     }
     #[test]
+    fn test_curve_from_name() {
+        for name in ["bn128", "BN254", "alt_bn128"] {
+            let curve = Curve::from_name(name).unwrap();
+            assert_eq!(curve.q, Curve::q());
+            assert_eq!(curve.name(), "bn128");
+        }
+        assert!(matches!(
+            Curve::from_name("bls12381"),
+            Err(SnarkError::CurveNotSupported(ref q)) if q == &bls12_381_q()
+        ));
+        assert!(matches!(
+            Curve::from_name("secp256k1"),
+            Err(SnarkError::UnknownCurveName(ref name)) if name == "secp256k1"
+        ));
+    }
+    #[test]
     fn test_get_curve_from_q_not_supported() {
         let q = BigUint::parse_bytes(b"1234567890123456789012345678901234567890", 16).unwrap();
         let curve = Curve::from_q(&q);
-        assert!(curve.is_err());
+        assert!(matches!(curve, Err(SnarkError::CurveNotSupported(ref got)) if got == &q));
     }
 }
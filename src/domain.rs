@@ -0,0 +1,228 @@
+use crate::curves::Curve;
+use crate::error::{Result, SnarkError};
+use crate::fft::{FftField, pow_u64, two_adicity_of};
+use crate::utils::compute_domain_power;
+use r1cs::{Bn128, Element, Field};
+
+/// The evaluation domain of a PLONK circuit: the multiplicative subgroup of size
+/// `2^log_size` together with the coset shifts used by the copy constraints.
+#[derive(Debug, Clone)]
+pub struct Domain {
+    pub log_size: u32,
+    pub size: usize,
+    /// Primitive `size`-th root of unity, derived from the field's nqr like [`FftEngine`](crate::fft::FftEngine).
+    pub generator: Element<Bn128>,
+    pub generator_inv: Element<Bn128>,
+    pub size_inv: Element<Bn128>,
+    pub k1: Element<Bn128>,
+    pub k2: Element<Bn128>,
+}
+
+impl Domain {
+    /// Smallest domain fitting `n_constraints` gates, see [`compute_domain_power`].
+    pub fn new(curve: &Curve, n_constraints: usize) -> Result<Self> {
+        let (log_size, _) = compute_domain_power(n_constraints);
+        Self::with_power(curve, log_size)
+    }
+
+    /// Domain of size `2^log_size`. Fails with [`SnarkError::FftTooLarge`] past
+    /// [`max_domain_power`], where the field has no root of unity of that order.
+    pub fn with_power(curve: &Curve, log_size: u32) -> Result<Self> {
+        let max = max_domain_power(curve);
+        if log_size > max {
+            return Err(SnarkError::FftTooLarge {
+                bits: log_size as usize,
+                two_adicity: max,
+            });
+        }
+        let size = 1usize << log_size;
+        let generator = compute_domain_generator(curve, log_size);
+        let (k1, k2) = get_k1_k2(log_size);
+
+        Ok(Self {
+            log_size,
+            size,
+            generator_inv: generator.multiplicative_inverse(),
+            size_inv: Element::<Bn128>::from(size as u64).multiplicative_inverse(),
+            generator,
+            k1,
+            k2,
+        })
+    }
+
+    /// The `i`-th domain point, `generator^i`.
+    pub fn element(&self, i: usize) -> Element<Bn128> {
+        pow_u64(&self.generator, i as u64)
+    }
+}
+
+/// Largest power of two dividing `r - 1`: the field has roots of unity of order `2^k`
+/// exactly for `k` up to this, so no domain or FFT can be larger. 28 for BN128.
+pub fn max_domain_power(curve: &Curve) -> u32 {
+    two_adicity_of(&curve.r)
+}
+
+/// Primitive `2^pow`-th root of unity, `nqr^((r - 1) >> pow)`. This is the root snarkjs
+/// and [`FftEngine`](crate::fft::FftEngine) use, so zkeys agree on the domain.
+pub fn compute_domain_generator(curve: &Curve, pow: u32) -> Element<Bn128> {
+    let exponent = Element::<Bn128>::from((&curve.r - 1u32) >> pow as usize);
+    Bn128::nqr().exponentiation(&exponent)
+}
+
+/// Whether `k` lies in the subgroup of order `2^pow`. The multiplicative group is cyclic,
+/// so that subgroup is exactly the `2^pow`-th roots of unity and membership costs `pow`
+/// squarings.
+fn in_subgroup<F: Field>(k: &Element<F>, pow: u32) -> bool {
+    let mut x = k.clone();
+    for _ in 0..pow {
+        x = &x * &x;
+    }
+    x.is_one()
+}
+
+/// The coset shifts `(k1, k2)` for the domain `H` of size `2^pow` over `F`, chosen so
+/// that `H`, `k1·H` and `k2·H` are pairwise disjoint: the smallest `k1 >= 2` outside
+/// `H`, then the smallest `k2 > k1` outside both `H` and `k1·H`, as snarkjs does.
+pub fn get_k1_k2<F: Field>(pow: u32) -> (Element<F>, Element<F>) {
+    let one = Element::<F>::one();
+
+    let mut k1 = &one + &one;
+    while in_subgroup(&k1, pow) {
+        k1 = &k1 + &one;
+    }
+
+    // k2 is in k1·H exactly when k2 / k1 is in H.
+    let k1_inv = k1.multiplicative_inverse();
+    let mut k2 = &k1 + &one;
+    while in_subgroup(&k2, pow) || in_subgroup(&(&k2 * &k1_inv), pow) {
+        k2 = &k2 + &one;
+    }
+
+    (k1, k2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft::FftEngine;
+    use r1cs::Bls12_381;
+
+    #[test]
+    fn test_domain() {
+        let curve = Curve::new();
+        let domain = Domain::with_power(&curve, 4).unwrap();
+
+        assert_eq!(domain.size, 16);
+        assert!(domain.element(0).is_one());
+        assert!(domain.element(domain.size).is_one());
+        assert!(!domain.element(domain.size / 2).is_one());
+        assert!((&domain.size_inv * Element::from(domain.size as u64)).is_one());
+        assert!((&domain.generator_inv * &domain.generator).is_one());
+        assert_eq!(domain.generator, FftEngine::<Bn128>::new(4).unwrap().w[4]);
+        assert_eq!(domain.k1, Element::from(2u64));
+        assert_eq!(domain.k2, Element::from(3u64));
+    }
+
+    #[test]
+    fn test_domain_generator_order() {
+        let curve = Curve::new();
+        for pow in [1, 3, 8] {
+            let w = compute_domain_generator(&curve, pow);
+            // w^(2^pow) = 1 and w^(2^(pow-1)) = -1, so the order is exactly 2^pow.
+            let half = pow_u64(&w, 1 << (pow - 1));
+            assert_eq!(half, -Element::<Bn128>::one());
+            assert!((&half * &half).is_one());
+        }
+        assert_eq!(compute_domain_generator(&curve, 0), Element::one());
+    }
+
+    #[test]
+    fn test_max_domain_power() {
+        let curve = Curve::new();
+        assert_eq!(max_domain_power(&curve), 28);
+        assert_eq!(max_domain_power(&curve), Bn128::two_adicity());
+
+        // w of order 2^28 exists, and the odd part of r - 1 leaves no room for 2^29.
+        let w = compute_domain_generator(&curve, 28);
+        assert_eq!(pow_u64(&w, 1 << 27), -Element::<Bn128>::one());
+        assert_eq!(((&curve.r - 1u32) >> 28usize) % 2u32, 1u32.into());
+    }
+
+    #[test]
+    fn test_domain_past_two_adicity() {
+        let curve = Curve::new();
+        assert_eq!(Domain::with_power(&curve, 28).unwrap().log_size, 28);
+        assert!(matches!(
+            Domain::with_power(&curve, 29),
+            Err(SnarkError::FftTooLarge {
+                bits: 29,
+                two_adicity: 28
+            })
+        ));
+    }
+
+    #[test]
+    fn test_domain_sizing() {
+        let curve = Curve::new();
+        assert_eq!(Domain::new(&curve, 0).unwrap().log_size, 3);
+        assert_eq!(Domain::new(&curve, 8).unwrap().log_size, 3);
+        assert_eq!(Domain::new(&curve, 9).unwrap().log_size, 4);
+        assert_eq!(Domain::new(&curve, 72).unwrap().log_size, 7);
+    }
+
+    /// The original search: walks the whole subgroup for every candidate.
+    fn is_included(
+        k: &Element<Bn128>,
+        existing: &[Element<Bn128>],
+        pow: u32,
+        step: &Element<Bn128>,
+    ) -> bool {
+        let mut w = Element::<Bn128>::one();
+        for _ in 0..1u64 << pow {
+            if k == &w || existing.iter().any(|e| k == &(e.clone() * &w)) {
+                return true;
+            }
+            w = &w * step;
+        }
+        false
+    }
+
+    #[test]
+    fn test_k1_k2_match_subgroup_walk() {
+        let curve = Curve::new();
+        let one = Element::<Bn128>::one();
+        for pow in 0..=6 {
+            let step = &compute_domain_generator(&curve, pow);
+            let mut k1 = &one + &one;
+            while is_included(&k1, &[], pow, step) {
+                k1 = &k1 + &one;
+            }
+            let mut k2 = &k1 + &one;
+            while is_included(&k2, &[k1.clone()], pow, step) {
+                k2 = &k2 + &one;
+            }
+            assert_eq!(get_k1_k2(pow), (k1, k2), "pow {}", pow);
+        }
+
+        // -1 is in every subgroup of even order, and w itself in its own.
+        assert!(in_subgroup(&-one.clone(), 1));
+        assert!(in_subgroup(&compute_domain_generator(&curve, 5), 5));
+        assert!(!in_subgroup(&compute_domain_generator(&curve, 5), 4));
+    }
+
+    #[test]
+    fn test_k1_k2_snarkjs_values() {
+        // snarkjs picks k1 = 2 and k2 = 3 for bn128 at these powers.
+        for pow in [3, 4] {
+            let (k1, k2) = get_k1_k2::<Bn128>(pow);
+            assert_eq!(k1, Element::from(2u64));
+            assert_eq!(k2, Element::from(3u64));
+        }
+
+        // Any field works; the shifts stay outside the subgroup.
+        let (k1, k2) = get_k1_k2::<Bls12_381>(5);
+        assert!(!in_subgroup(&k1, 5));
+        assert!(!in_subgroup(&k2, 5));
+        assert!(!in_subgroup(&(&k2 * &k1.multiplicative_inverse()), 5));
+    }
+}
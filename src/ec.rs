@@ -0,0 +1,451 @@
+use crate::curves::Curve;
+use crate::error::{Result, SnarkError};
+use r1cs::num::BigUint;
+use r1cs::{Bn128, Element, Field};
+use std::sync::OnceLock;
+
+/// The BN128 base field, over which the G1 coordinates live.
+#[derive(Debug)]
+pub struct Bn128Fq {}
+
+impl Field for Bn128Fq {
+    // Called on every field operation, so parse the modulus once.
+    fn order() -> BigUint {
+        static ORDER: OnceLock<BigUint> = OnceLock::new();
+        ORDER
+            .get_or_init(|| {
+                BigUint::parse_bytes(
+                    b"21888242871839275222246405745257275088696311157297823662689037894645226208583",
+                    10,
+                )
+                .unwrap()
+            })
+            .clone()
+    }
+}
+
+pub type Fq = Element<Bn128Fq>;
+
+/// Decodes a little-endian Montgomery-form base field element (`x * 2^(8 * len) mod q`).
+fn fq_from_montgomery_bytes(bytes: &[u8]) -> Result<Fq> {
+    let raw = BigUint::from_bytes_le(bytes);
+    if raw >= Bn128Fq::order() {
+        return Err(SnarkError::InvalidPoint(
+            "coordinate is not reduced modulo q",
+        ));
+    }
+    let r = Fq::from((BigUint::from(1u32) << (8 * bytes.len())) % Bn128Fq::order());
+    Ok(Fq::from(raw) * r.multiplicative_inverse())
+}
+
+/// Inverse of [`fq_from_montgomery_bytes`], zero-padded to `n8` bytes.
+fn fq_to_montgomery_bytes(x: &Fq, n8: usize) -> Vec<u8> {
+    let r = Fq::from((BigUint::from(1u32) << (8 * n8)) % Bn128Fq::order());
+    let mut bytes = (x * r).to_biguint().to_bytes_le();
+    bytes.resize(n8, 0);
+    bytes
+}
+
+/// Checks that `bytes` holds exactly `expected` bytes.
+fn check_len(bytes: &[u8], expected: usize) -> Result<()> {
+    if bytes.len() != expected {
+        return Err(SnarkError::InvalidFieldSize {
+            expected,
+            got: bytes.len(),
+        });
+    }
+    Ok(())
+}
+
+/// The quadratic extension `Fq[u] / (u^2 + 1)`, over which the G2 coordinates live.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fq2 {
+    pub c0: Fq,
+    pub c1: Fq,
+}
+
+impl Fq2 {
+    pub fn new(c0: Fq, c1: Fq) -> Self {
+        Self { c0, c1 }
+    }
+
+    pub fn zero() -> Self {
+        Self::new(Fq::zero(), Fq::zero())
+    }
+
+    pub fn one() -> Self {
+        Self::new(Fq::one(), Fq::zero())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.c0.is_zero() && self.c1.is_zero()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self::new(&self.c0 + &other.c0, &self.c1 + &other.c1)
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        Self::new(&self.c0 - &other.c0, &self.c1 - &other.c1)
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let aa = &self.c0 * &other.c0;
+        let bb = &self.c1 * &other.c1;
+        let c1 = (&self.c0 + &self.c1) * (&other.c0 + &other.c1) - &aa - &bb;
+        Self::new(aa - bb, c1)
+    }
+
+    pub fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    pub fn inverse(&self) -> Self {
+        let norm = &self.c0 * &self.c0 + &self.c1 * &self.c1;
+        let norm_inv = norm.multiplicative_inverse();
+        Self::new(&self.c0 * &norm_inv, -(&self.c1 * norm_inv))
+    }
+}
+
+/// A G1 point in affine coordinates, as stored in ptau and zkey files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct G1Affine {
+    pub x: Fq,
+    pub y: Fq,
+    pub infinity: bool,
+}
+
+impl G1Affine {
+    pub fn new(x: Fq, y: Fq) -> Self {
+        Self {
+            x,
+            y,
+            infinity: false,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self {
+            x: Fq::zero(),
+            y: Fq::zero(),
+            infinity: true,
+        }
+    }
+
+    pub fn generator() -> Self {
+        Self::new(Fq::from(1u64), Fq::from(2u64))
+    }
+
+    /// Checks `y^2 = x^3 + 3`. The point at infinity is on the curve.
+    pub fn is_on_curve(&self) -> bool {
+        if self.infinity {
+            return true;
+        }
+        &self.y * &self.y == &self.x * &self.x * &self.x + Fq::from(3u64)
+    }
+
+    /// Decodes an `(x, y)` pair of Montgomery-form coordinates, `2 * n8q` bytes in total,
+    /// rejecting points off the curve. All-zero bytes encode the point at infinity.
+    pub fn from_montgomery_bytes(curve: &Curve, bytes: &[u8]) -> Result<Self> {
+        check_len(bytes, 2 * curve.n8q)?;
+        if bytes.iter().all(|&b| b == 0) {
+            return Ok(Self::identity());
+        }
+
+        let (x, y) = bytes.split_at(curve.n8q);
+        let point = Self::new(fq_from_montgomery_bytes(x)?, fq_from_montgomery_bytes(y)?);
+        if !point.is_on_curve() {
+            return Err(SnarkError::InvalidPoint("G1 point is not on the curve"));
+        }
+        Ok(point)
+    }
+
+    /// Encodes the point as [`Self::from_montgomery_bytes`] reads it, the layout of the
+    /// ptau and zkey points. The point at infinity is all zeros.
+    pub fn to_montgomery_bytes(&self, curve: &Curve) -> Vec<u8> {
+        if self.infinity {
+            return vec![0; 2 * curve.n8q];
+        }
+        let mut bytes = fq_to_montgomery_bytes(&self.x, curve.n8q);
+        bytes.extend(fq_to_montgomery_bytes(&self.y, curve.n8q));
+        bytes
+    }
+}
+
+/// A G2 point in affine coordinates over [`Fq2`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct G2Affine {
+    pub x: Fq2,
+    pub y: Fq2,
+    pub infinity: bool,
+}
+
+impl G2Affine {
+    pub fn new(x: Fq2, y: Fq2) -> Self {
+        Self {
+            x,
+            y,
+            infinity: false,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self {
+            x: Fq2::zero(),
+            y: Fq2::zero(),
+            infinity: true,
+        }
+    }
+
+    /// The twist coefficient `b' = 3 / (9 + u)`.
+    pub fn twist_b() -> Fq2 {
+        let xi = Fq2::new(Fq::from(9u64), Fq::one());
+        Fq2::new(Fq::from(3u64), Fq::zero()).mul(&xi.inverse())
+    }
+
+    /// Checks `y^2 = x^3 + b'` on the twist. The point at infinity is on the curve.
+    pub fn is_on_curve(&self) -> bool {
+        if self.infinity {
+            return true;
+        }
+        self.y.square() == self.x.square().mul(&self.x).add(&Self::twist_b())
+    }
+
+    /// Decodes Montgomery-form coordinates laid out as `x.c0, x.c1, y.c0, y.c1`,
+    /// `4 * n8q` bytes in total, rejecting points off the twist. All-zero bytes encode
+    /// the point at infinity.
+    pub fn from_montgomery_bytes(curve: &Curve, bytes: &[u8]) -> Result<Self> {
+        check_len(bytes, 4 * curve.n8q)?;
+        if bytes.iter().all(|&b| b == 0) {
+            return Ok(Self::identity());
+        }
+
+        let mut coords = bytes.chunks_exact(curve.n8q).map(fq_from_montgomery_bytes);
+        let mut next = || coords.next().unwrap();
+        let x = Fq2::new(next()?, next()?);
+        let y = Fq2::new(next()?, next()?);
+        let point = Self::new(x, y);
+        if !point.is_on_curve() {
+            return Err(SnarkError::InvalidPoint("G2 point is not on the twist"));
+        }
+        Ok(point)
+    }
+}
+
+/// A G1 point in Jacobian coordinates `(X, Y, Z)`, standing for `(X / Z^2, Y / Z^3)`.
+#[derive(Debug, Clone)]
+pub struct G1 {
+    pub x: Fq,
+    pub y: Fq,
+    pub z: Fq,
+}
+
+impl G1 {
+    pub fn identity() -> Self {
+        Self {
+            x: Fq::one(),
+            y: Fq::one(),
+            z: Fq::zero(),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    pub fn double(&self) -> Self {
+        if self.is_identity() || self.y.is_zero() {
+            return Self::identity();
+        }
+        // dbl-2009-l
+        let a = &self.x * &self.x;
+        let b = &self.y * &self.y;
+        let c = &b * &b;
+        let xb = &self.x + &b;
+        let d = (&xb * &xb - &a - &c) * 2u128;
+        let e = a * 3u128;
+        let f = &e * &e;
+        let x = &f - &d * 2u128;
+        let y = e * (d - &x) - c * 8u128;
+        let z = &self.y * &self.z * 2u128;
+        Self { x, y, z }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.is_identity() {
+            return other.clone();
+        }
+        if other.is_identity() {
+            return self.clone();
+        }
+        // add-2007-bl
+        let z1z1 = &self.z * &self.z;
+        let z2z2 = &other.z * &other.z;
+        let u1 = &self.x * &z2z2;
+        let u2 = &other.x * &z1z1;
+        let s1 = &self.y * &other.z * &z2z2;
+        let s2 = &other.y * &self.z * &z1z1;
+        if u1 == u2 {
+            return if s1 == s2 {
+                self.double()
+            } else {
+                Self::identity()
+            };
+        }
+        let h = u2 - &u1;
+        let i = {
+            let h2 = &h * 2u128;
+            &h2 * &h2
+        };
+        let j = &h * &i;
+        let r = (s2 - &s1) * 2u128;
+        let v = u1 * &i;
+        let x = &r * &r - &j - &v * 2u128;
+        let y = r * (v - &x) - s1 * j * 2u128;
+        let zz = &self.z + &other.z;
+        let z = (&zz * &zz - z1z1 - z2z2) * h;
+        Self { x, y, z }
+    }
+
+    pub fn add_affine(&self, other: &G1Affine) -> Self {
+        if other.infinity {
+            return self.clone();
+        }
+        if self.is_identity() {
+            return Self::from(other);
+        }
+        // madd-2007-bl
+        let z1z1 = &self.z * &self.z;
+        let u2 = &other.x * &z1z1;
+        let s2 = &other.y * &self.z * &z1z1;
+        if u2 == self.x {
+            return if s2 == self.y {
+                self.double()
+            } else {
+                Self::identity()
+            };
+        }
+        let h = u2 - &self.x;
+        let hh = &h * &h;
+        let i = &hh * 4u128;
+        let j = &h * &i;
+        let r = (s2 - &self.y) * 2u128;
+        let v = &self.x * &i;
+        let x = &r * &r - &j - &v * 2u128;
+        let y = r * (v - &x) - &self.y * j * 2u128;
+        let zh = &self.z + &h;
+        let z = &zh * &zh - z1z1 - hh;
+        Self { x, y, z }
+    }
+
+    /// Scalar multiplication by double-and-add.
+    pub fn mul(&self, scalar: &Element<Bn128>) -> Self {
+        let mut acc = Self::identity();
+        for i in (0..scalar.bits()).rev() {
+            acc = acc.double();
+            if scalar.bit(i) {
+                acc = acc.add(self);
+            }
+        }
+        acc
+    }
+
+    pub fn to_affine(&self) -> G1Affine {
+        if self.is_identity() {
+            return G1Affine::identity();
+        }
+        let z_inv = self.z.multiplicative_inverse();
+        let z_inv2 = &z_inv * &z_inv;
+        G1Affine::new(&self.x * &z_inv2, &self.y * z_inv2 * z_inv)
+    }
+}
+
+impl From<&G1Affine> for G1 {
+    fn from(p: &G1Affine) -> Self {
+        if p.infinity {
+            return Self::identity();
+        }
+        Self {
+            x: p.x.clone(),
+            y: p.y.clone(),
+            z: Fq::one(),
+        }
+    }
+}
+
+impl PartialEq for G1 {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_affine() == other.to_affine()
+    }
+}
+
+impl Eq for G1 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{read_bin_file, read_section};
+
+    #[test]
+    fn test_group_law() {
+        let g = G1::from(&G1Affine::generator());
+        let g2 = g.double();
+        let g3 = g2.add(&g);
+
+        assert_eq!(g.add(&g), g2);
+        assert_eq!(g2.add_affine(&G1Affine::generator()), g3);
+        assert_eq!(g.mul(&Element::from(3u64)), g3);
+        assert!(g3.add(&g3.mul(&-Element::<Bn128>::one())).is_identity());
+        // The generator has order r.
+        assert!(g.mul(&Element::largest_element()).add(&g).is_identity());
+    }
+
+    #[tokio::test]
+    async fn test_decode_ptau_generators() -> Result<()> {
+        let curve = Curve::new();
+        let (mut fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+
+        // tau^0 in sections 2 and 3 are the group generators.
+        let g1_bytes = read_section(&mut fd, &sections, 2, Some(0), Some(64)).await?;
+        let g1 = G1Affine::from_montgomery_bytes(&curve, &g1_bytes)?;
+        assert!(!g1.infinity);
+        assert!(g1.is_on_curve());
+        assert_eq!(g1, G1Affine::generator());
+
+        let g2_bytes = read_section(&mut fd, &sections, 3, Some(0), Some(128)).await?;
+        let g2 = G2Affine::from_montgomery_bytes(&curve, &g2_bytes)?;
+        assert!(!g2.infinity);
+        assert!(g2.is_on_curve());
+
+        // Flipping a bit of y moves the point off the curve.
+        let mut bad = g1_bytes.clone();
+        bad[32] ^= 1;
+        assert!(matches!(
+            G1Affine::from_montgomery_bytes(&curve, &bad),
+            Err(SnarkError::InvalidPoint(_))
+        ));
+        let mut bad = g2_bytes.clone();
+        bad[64] ^= 1;
+        assert!(matches!(
+            G2Affine::from_montgomery_bytes(&curve, &bad),
+            Err(SnarkError::InvalidPoint(_))
+        ));
+
+        assert_eq!(g1.to_montgomery_bytes(&curve), g1_bytes);
+        let g3 = G1::from(&g1).mul(&Element::from(3u64)).to_affine();
+        let g3_bytes = g3.to_montgomery_bytes(&curve);
+        assert_eq!(G1Affine::from_montgomery_bytes(&curve, &g3_bytes)?, g3);
+        assert_eq!(G1Affine::identity().to_montgomery_bytes(&curve), [0; 64]);
+
+        assert!(G1Affine::from_montgomery_bytes(&curve, &[0; 64])?.infinity);
+        assert!(matches!(
+            G1Affine::from_montgomery_bytes(&curve, &g1_bytes[..63]),
+            Err(SnarkError::InvalidFieldSize {
+                expected: 64,
+                got: 63
+            })
+        ));
+
+        Ok(())
+    }
+}
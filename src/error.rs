@@ -0,0 +1,249 @@
+use r1cs::num::BigUint;
+use std::fmt;
+
+/// Errors raised while reading or writing snarkjs-compatible binary files.
+#[derive(Debug)]
+pub enum SnarkError {
+    Io(std::io::Error),
+    InvalidMagic { expected: String, got: String },
+    UnsupportedVersion { version: u32, max: u32 },
+    SectionMissing(u32),
+    SectionDuplicated(u32),
+    SectionSizeMismatch { section: u32, expected: u64, got: u64 },
+    SectionOutOfBounds { section: u32, offset: u64, length: u64, size: u64 },
+    SectionExceedsFile { section: u32, offset: u64, size: u64, file_size: u64 },
+    BufferOutOfBounds { offset: usize, length: usize, size: usize },
+    ZeroSize(&'static str),
+    SectionRecordMisaligned { section: u32, size: u64, record_len: usize },
+    TrailingBytes { pos: u64, file_size: u64 },
+    UnexpectedEof { pos: u64, requested: u64, available: u64 },
+    CurveNotSupported(BigUint),
+    UnknownCurveName(String),
+    InvalidFieldSize { expected: usize, got: usize },
+    FieldNotReduced,
+    InvalidWriteState(&'static str),
+    InvalidColumn(usize),
+    PrimeMismatch { r1cs: BigUint, ptau: BigUint },
+    CurveMismatch { config: BigUint, ptau: BigUint },
+    CircuitTooBig { cir_power: u32, power: u32, n_constraints: usize },
+    PtauNotPrepared,
+    LagrangeSectionTooShort { power: u32, required: u64, available: u64 },
+    PowerTooLarge { power: u32, max: u32 },
+    PtauPowerMismatch { power: u32, points: u64 },
+    CeremonyPowerTooSmall { power: u32, ceremony_power: u32 },
+    FftTooLarge { bits: usize, two_adicity: u32 },
+    InvalidDomainSize { size: u64, max_bits: usize },
+    CustomGatesNotSupported(String),
+    CustomGatesDeclared,
+    TooManyVariables { max: u32 },
+    MsmLengthMismatch { points: usize, scalars: usize },
+    InvalidPoint(&'static str),
+    IdentityCommitment(&'static str),
+    PairingCheckFailed { index: u64 },
+    InvalidContribution(&'static str),
+    InvalidArguments(String),
+    InvalidHex(String),
+    InvalidJson(String),
+    UnsupportedProtocol(u32),
+    UnknownProtocolName(String),
+    ZkeyMismatch { field: &'static str, expected: String, got: String },
+    SignalCountMismatch { n_vars: u32, n_signals: u64 },
+    Cancelled,
+}
+
+pub type Result<T> = std::result::Result<T, SnarkError>;
+
+impl fmt::Display for SnarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {}", err),
+            Self::InvalidMagic { expected, got } => {
+                write!(f, "Invalid file format (expected {}, got {})", expected, got)
+            }
+            Self::UnsupportedVersion { version, max } => {
+                write!(f, "Version {} not supported (max {})", version, max)
+            }
+            Self::SectionMissing(id) => write!(f, "Section {} not found", id),
+            Self::SectionDuplicated(id) => write!(f, "Section {} duplicated", id),
+            Self::SectionSizeMismatch {
+                section,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Invalid size for section {}: read {}, expected {}",
+                section, got, expected
+            ),
+            Self::SectionOutOfBounds {
+                section,
+                offset,
+                length,
+                size,
+            } => write!(
+                f,
+                "Out-of-bounds read in section {}: offset {} + length {} > size {}",
+                section, offset, length, size
+            ),
+            Self::ZeroSize(name) => write!(f, "{} must be greater than zero", name),
+            Self::BufferOutOfBounds {
+                offset,
+                length,
+                size,
+            } => write!(
+                f,
+                "Out-of-bounds write to buffer: offset {} + length {} > size {}",
+                offset, length, size
+            ),
+            Self::SectionExceedsFile {
+                section,
+                offset,
+                size,
+                file_size,
+            } => write!(
+                f,
+                "Section {} at offset {} with size {} extends past the end of the file ({} bytes)",
+                section, offset, size, file_size
+            ),
+            Self::SectionRecordMisaligned {
+                section,
+                size,
+                record_len,
+            } => write!(
+                f,
+                "Section {} has size {}, not a multiple of its {}-byte records",
+                section, size, record_len
+            ),
+            Self::TrailingBytes { pos, file_size } => write!(
+                f,
+                "Unexpected data after the last section: sections end at {}, file size is {}",
+                pos, file_size
+            ),
+            Self::UnexpectedEof {
+                pos,
+                requested,
+                available,
+            } => write!(
+                f,
+                "Unexpected end of file at byte {}: needed {} bytes, only {} available",
+                pos, requested, available
+            ),
+            Self::CurveNotSupported(q) => write!(f, "Curve not supported: {}", q),
+            Self::UnknownCurveName(name) => write!(f, "Unknown curve name: {}", name),
+            Self::InvalidFieldSize { expected, got } => {
+                write!(f, "Invalid field size: expected {} bytes, got {}", expected, got)
+            }
+            Self::InvalidWriteState(msg) => write!(f, "{}", msg),
+            Self::InvalidColumn(pos) => write!(f, "Invalid constraint column index {}", pos),
+            Self::PrimeMismatch { r1cs, ptau } => write!(
+                f,
+                "R1CS curve does not match PTAU curve (r1cs prime {}, ptau prime {})",
+                r1cs, ptau
+            ),
+            Self::CurveMismatch { config, ptau } => write!(
+                f,
+                "Configured curve does not match PTAU curve (config q {}, ptau q {})",
+                config, ptau
+            ),
+            Self::CircuitTooBig {
+                cir_power,
+                power,
+                n_constraints,
+            } => write!(
+                f,
+                "Circuit too big for this PTAU. 2**{} > 2**{} ({} constraints)",
+                cir_power, power, n_constraints
+            ),
+            Self::PtauNotPrepared => write!(f, "PTAU file is not prepared (section 12 missing)"),
+            Self::LagrangeSectionTooShort { power, required, available } => write!(
+                f,
+                "PTAU section 12 holds {} Lagrange points, but a domain of power {} needs {}",
+                available, power, required
+            ),
+            Self::PowerTooLarge { power, max } => {
+                write!(f, "Power {} is larger than the ptau power {}", power, max)
+            }
+            Self::CeremonyPowerTooSmall {
+                power,
+                ceremony_power,
+            } => write!(
+                f,
+                "PTAU power {} is larger than its ceremony power {}",
+                power, ceremony_power
+            ),
+            Self::PtauPowerMismatch { power, points } => write!(
+                f,
+                "PTAU header declares power {} but section 2 holds {} G1 points",
+                power, points
+            ),
+            Self::FftTooLarge { bits, two_adicity } => write!(
+                f,
+                "FFT of size 2^{} exceeds the field's two-adicity ({})",
+                bits, two_adicity
+            ),
+            Self::InvalidDomainSize { size, max_bits } => write!(
+                f,
+                "Domain size {} is not a power of two up to 2^{}",
+                size, max_bits
+            ),
+            Self::TooManyVariables { max } => {
+                write!(f, "Circuit needs more than {} PLONK variables", max)
+            }
+            Self::CustomGatesNotSupported(name) => {
+                write!(f, "Circuit uses custom gate '{}', which PLONK setup does not support", name)
+            }
+            Self::CustomGatesDeclared => {
+                write!(f, "Circuit declares custom gates, which PLONK setup does not support")
+            }
+            Self::MsmLengthMismatch { points, scalars } => {
+                write!(f, "MSM needs one scalar per point ({} points, {} scalars)", points, scalars)
+            }
+            Self::FieldNotReduced => write!(f, "Field element is not reduced modulo r"),
+            Self::InvalidPoint(reason) => write!(f, "Invalid curve point: {}", reason),
+            Self::IdentityCommitment(name) => {
+                write!(f, "Commitment {} is the point at infinity", name)
+            }
+            Self::PairingCheckFailed { index } => write!(
+                f,
+                "Pairing check failed: tau power {} is not tau times power {}",
+                index + 1,
+                index
+            ),
+            Self::InvalidContribution(msg) => write!(f, "Invalid ptau contribution: {}", msg),
+            Self::InvalidArguments(msg) => write!(f, "Invalid arguments: {}", msg),
+            Self::InvalidHex(s) => write!(f, "Invalid hex field element: {}", s),
+            Self::InvalidJson(msg) => write!(f, "Invalid JSON: {}", msg),
+            Self::UnsupportedProtocol(id) => write!(f, "Unsupported zkey protocol id: {}", id),
+            Self::UnknownProtocolName(name) => write!(f, "Unknown proving system: {}", name),
+            Self::ZkeyMismatch {
+                field,
+                expected,
+                got,
+            } => write!(
+                f,
+                "zkey does not match the circuit: {} is {}, expected {}",
+                field, got, expected
+            ),
+            Self::SignalCountMismatch { n_vars, n_signals } => write!(
+                f,
+                "R1CS declares {} variables, fewer than its {} input/output signals and the one wire",
+                n_vars, n_signals
+            ),
+            Self::Cancelled => write!(f, "Setup cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for SnarkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SnarkError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
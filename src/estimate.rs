@@ -0,0 +1,131 @@
+use crate::utils::compute_domain_power;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{
+    error::Result,
+    file,
+    ptau_file::{PTAU_MAX_VERSION, PTauFile, PtauHeader},
+    r1cs,
+};
+
+/// Size of a zkey and its setup, worked out from the inputs without running the setup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupEstimate {
+    /// Power of the ptau file; the setup fails if `domain_power` exceeds it.
+    pub ptau_power: u32,
+    /// Bytes per scalar field element, shared by the r1cs and the zkey.
+    pub n8r: usize,
+    pub domain_power: u32,
+    pub domain_size: usize,
+    /// PLONK gates, including the public input gates.
+    pub n_constraints: usize,
+    pub n_additions: usize,
+    pub n_public: usize,
+    /// Exact size of the zkey that would be written, in bytes.
+    pub zkey_size: u64,
+    /// Rough peak of field data held in memory, in bytes: the selectors, the sigma
+    /// evaluations and one extended 4x FFT at `n8r` bytes per element. The in-memory
+    /// representation is larger, so treat this as a lower bound.
+    pub peak_memory: u64,
+}
+
+impl SetupEstimate {
+    fn new(
+        n8q: usize,
+        n8r: usize,
+        ptau_power: u32,
+        n_constraints: usize,
+        n_additions: usize,
+        n_public: usize,
+    ) -> Self {
+        let (domain_power, domain_size) = compute_domain_power(n_constraints);
+        let (n8q, n8r, n) = (n8q as u64, n8r as u64, domain_size as u64);
+        // A polynomial written by write_p4: coefficients plus evaluations over 4n points.
+        let p4 = 5 * n * n8r;
+
+        let sections = [
+            4,
+            4 + n8q + 4 + n8r + 5 * 4 + 2 * n8r + 8 * 2 * n8q + 4 * n8q,
+            n_additions as u64 * (2 * 4 + 2 * n8r),
+            n_constraints as u64 * 4,
+            n_constraints as u64 * 4,
+            n_constraints as u64 * 4,
+            p4,
+            p4,
+            p4,
+            p4,
+            p4,
+            3 * p4,
+            n_public.max(1) as u64 * p4,
+            (n + 6) * 2 * n8q,
+        ];
+        let zkey_size = 12 + sections.iter().map(|size| 12 + size).sum::<u64>();
+        let peak_memory = (5 + 3 + 4) * n * n8r;
+
+        Self {
+            ptau_power,
+            n8r: n8r as usize,
+            domain_power,
+            domain_size,
+            n_constraints,
+            n_additions,
+            n_public,
+            zkey_size,
+            peak_memory,
+        }
+    }
+}
+
+/// Reads the ptau header and converts the r1cs into PLONK gates to report the domain,
+/// the number of additions and the expected output size. Nothing is written and no
+/// FFT is computed.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn estimate_setup(ptau_path: &str, r1cs_path: &str) -> Result<SetupEstimate> {
+    let (fd_ptau, sections_ptau) = file::read_bin_file(ptau_path, "ptau", PTAU_MAX_VERSION).await?;
+    let PtauHeader { curve, power, .. } =
+        PTauFile::from(fd_ptau).read_header(&sections_ptau).await?;
+
+    let (mut fd_r1cs, sections_r1cs) = file::read_bin_file(r1cs_path, "r1cs", 1).await?;
+    let r1cs = file::read_r1cs_fd(&mut fd_r1cs, &sections_r1cs).await?;
+    r1cs.header.check_field(&curve)?;
+    let (constraints, additions, stats) = r1cs::process_constraints::<::r1cs::Bn128>(&r1cs, None)?;
+
+    Ok(SetupEstimate::new(
+        curve.n8q,
+        curve.n8r,
+        power,
+        constraints.len(),
+        additions.len(),
+        stats.n_public,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plonk::plonk_setup;
+    use crate::zkey::read_zkey;
+    use tempfile::tempdir;
+
+    const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+    const R1CS_PATH: &str = "src/artifacts/small.r1cs";
+
+    #[tokio::test]
+    async fn test_estimate_matches_setup() -> Result<()> {
+        let estimate = estimate_setup(PTAU_PATH, R1CS_PATH).await?;
+        assert_eq!(estimate.ptau_power, 8);
+        assert_eq!(estimate.n8r, 32);
+        assert_eq!(estimate.domain_power, 7);
+        assert_eq!(estimate.domain_size, 128);
+
+        let dir = tempdir()?;
+        let out_path = dir.path().join("circuit.zkey");
+        let out = out_path.to_str().unwrap();
+        plonk_setup(PTAU_PATH, R1CS_PATH, out, None).await?;
+
+        let zkey = read_zkey(out).await?;
+        assert_eq!(estimate.n_additions, zkey.additions.len());
+        assert_eq!(estimate.n_constraints, zkey.header.n_constraints as usize);
+        assert_eq!(estimate.zkey_size, std::fs::metadata(&out_path)?.len());
+        Ok(())
+    }
+}
@@ -1,29 +1,104 @@
-use r1cs::{Bn128, Element, Field};
-use num_bigint::BigUint;
-use std::ops::{Add, Sub, Mul};
-
-pub struct FftEngine {
-    pub w: Vec<Element<Bn128>>,      // roots of unity
-    pub wi: Vec<Element<Bn128>>,     // inverse roots
-    pub one: Element<Bn128>,
-    pub twoinv: Element<Bn128>,
+use crate::error::{Result, SnarkError};
+use r1cs::num::BigUint;
+use r1cs::{Bls12_381, Bn128, Element, Field};
+use std::cell::OnceCell;
+
+/// A prime field with large power-of-two roots of unity.
+pub trait FftField: Field + Sized {
+    /// Largest `s` such that `2^s` divides `order - 1`.
+    fn two_adicity() -> u32 {
+        two_adicity_of(&Self::order())
+    }
+
+    /// Precomputed quadratic non-residue, if known for this field.
+    fn known_nqr() -> Option<Element<Self>> {
+        None
+    }
+
+    /// A quadratic non-residue, whose powers generate the roots of unity. Uses
+    /// [`known_nqr`](Self::known_nqr) and only falls back to searching for one.
+    fn nqr() -> Element<Self> {
+        Self::known_nqr().unwrap_or_else(find_nqr)
+    }
+}
+
+impl FftField for Bn128 {
+    fn known_nqr() -> Option<Element<Self>> {
+        Some(Element::from(5u64))
+    }
+}
+
+impl FftField for Bls12_381 {
+    fn known_nqr() -> Option<Element<Self>> {
+        Some(Element::from(5u64))
+    }
+}
+
+/// Largest `s` such that `2^s` divides `modulus - 1`: 28 for BN128's scalar field, 32
+/// for BLS12-381's.
+pub fn two_adicity_of(modulus: &BigUint) -> u32 {
+    let minus_one = (modulus - 1u32).to_bytes_le();
+    let zero_bytes = minus_one.iter().take_while(|&&b| b == 0).count();
+    let last = minus_one.get(zero_bytes).map_or(0, |b| b.trailing_zeros());
+    8 * zero_bytes as u32 + last
+}
+
+fn is_nqr<F: Field>(x: &Element<F>) -> bool {
+    let exponent = Element::<F>::from((F::order() - 1u32) >> 1);
+    x.exponentiation(&exponent) == -Element::<F>::one()
+}
+
+/// Smallest quadratic non-residue, found by Euler's criterion. Each candidate costs a
+/// full exponentiation, hence [`FftField::known_nqr`].
+pub fn find_nqr<F: Field>() -> Element<F> {
+    let one = Element::<F>::one();
+    let mut candidate = &one + &one;
+    while !is_nqr(&candidate) {
+        candidate = &candidate + &one;
+    }
+    candidate
+}
+
+/// `base^exp` by square-and-multiply over the bits of `exp`, for exponents that fit in
+/// a `u64` and so need no `Element` exponent.
+pub fn pow_u64<F: Field>(base: &Element<F>, exp: u64) -> Element<F> {
+    let mut result = Element::<F>::one();
+    for i in (0..64 - exp.leading_zeros()).rev() {
+        result = &result * &result;
+        if exp >> i & 1 == 1 {
+            result *= base;
+        }
+    }
+    result
 }
 
-impl FftEngine {
-    pub fn new(max_bits: usize) -> Self {
-        let mut nqr = Element::<Bn128>::one();
-        let half = (Bn128::order() - 1u32) >> 1;
-        let half = Element::<Bn128>::from(half);
+pub struct FftEngine<F: FftField> {
+    pub w: Vec<Element<F>>,      // roots of unity
+    pub wi: Vec<Element<F>>,     // inverse roots
+    pub one: Element<F>,
+    pub twoinv: Element<F>,
+    nqr: Element<F>,
+    /// Bit-reversal permutation per transform size, built on first use.
+    bit_reversal: Vec<OnceCell<Vec<usize>>>,
+}
 
-        while nqr.clone().exponentiation(&half) == Element::<Bn128>::one() {
-            nqr = &nqr + &Element::<Bn128>::one();
+impl<F: FftField> FftEngine<F> {
+    /// Engine for transforms of up to `2^max_bits` points. Fails if the field has no
+    /// root of unity of that order.
+    pub fn new(max_bits: usize) -> Result<Self> {
+        if max_bits > F::two_adicity() as usize {
+            return Err(SnarkError::FftTooLarge {
+                bits: max_bits,
+                two_adicity: F::two_adicity(),
+            });
         }
+        let nqr = F::nqr();
 
-        let mut w = vec![Element::<Bn128>::zero(); max_bits + 1];
-        let mut wi = vec![Element::<Bn128>::zero(); max_bits + 1];
+        let mut w = vec![Element::<F>::zero(); max_bits + 1];
+        let mut wi = vec![Element::<F>::zero(); max_bits + 1];
 
-        let pow = Element::<Bn128>::from(Bn128::order() - 1u32 >> max_bits);
-        w[max_bits] = nqr.clone().exponentiation(&pow);
+        let pow = Element::<F>::from((F::order() - 1u32) >> max_bits);
+        w[max_bits] = nqr.exponentiation(&pow);
         wi[max_bits] = w[max_bits].multiplicative_inverse_or_zero();
 
         for i in (0..max_bits).rev() {
@@ -31,32 +106,100 @@ impl FftEngine {
             wi[i] = wi[i + 1].clone() * &wi[i + 1];
         }
 
-        let one = Element::<Bn128>::one();
+        let one = Element::<F>::one();
         let twoinv = (&one + &one).multiplicative_inverse_or_zero();
 
-        Self { w, wi, one, twoinv }
+        Ok(Self {
+            w,
+            wi,
+            one,
+            twoinv,
+            nqr,
+            bit_reversal: vec![OnceCell::new(); max_bits + 1],
+        })
+    }
+
+    /// Grows the engine to transforms of up to `2^bits` points, reusing its non-residue.
+    /// Existing roots are kept; smaller `bits` leave the engine unchanged.
+    pub fn ensure_bits(&mut self, bits: usize) -> Result<()> {
+        let max_bits = self.w.len() - 1;
+        if bits <= max_bits {
+            return Ok(());
+        }
+        if bits > F::two_adicity() as usize {
+            return Err(SnarkError::FftTooLarge {
+                bits,
+                two_adicity: F::two_adicity(),
+            });
+        }
+
+        // The new top root, squared down until it meets the old one.
+        let pow = Element::<F>::from((F::order() - 1u32) >> bits);
+        let mut w = vec![self.nqr.exponentiation(&pow)];
+        for _ in max_bits + 1..bits {
+            let last = w.last().unwrap();
+            w.push(last.clone() * last);
+        }
+        w.reverse();
+        let wi: Vec<_> = w.iter().map(Element::multiplicative_inverse_or_zero).collect();
+
+        self.w.extend(w);
+        self.wi.extend(wi);
+        self.bit_reversal.resize(bits + 1, OnceCell::new());
+        Ok(())
+    }
+
+    /// The permutation sending index `i` to `bit_reverse(i, bits)`, cached per size.
+    pub fn bit_reversal_table(&self, bits: usize) -> &[usize] {
+        self.bit_reversal[bits]
+            .get_or_init(|| (0..1 << bits).map(|i| bit_reverse(i, bits)).collect())
+    }
+
+    /// Primitive `size`-th root of unity. `size` must be a power of two no larger than
+    /// the engine's `2^max_bits`.
+    pub fn root_for_size(&self, size: u64) -> Result<Element<F>> {
+        let max_bits = self.w.len() - 1;
+        let bits = size.trailing_zeros() as usize;
+        if !size.is_power_of_two() || bits > max_bits {
+            return Err(SnarkError::InvalidDomainSize { size, max_bits });
+        }
+        Ok(self.w[bits].clone())
+    }
+
+    /// The quadratic non-residue the roots of unity were derived from.
+    pub fn nqr(&self) -> &Element<F> {
+        &self.nqr
+    }
+
+    pub fn fft(&self, input: &[Element<F>]) -> Vec<Element<F>> {
+        let mut out = Vec::new();
+        self.fft_into(input, &mut out);
+        out
     }
 
-    pub fn fft(&self, input: &[Element<Bn128>]) -> Vec<Element<Bn128>> {
-        self.fft_internal(input, false)
+    /// Same as [`fft`](Self::fft), writing into `out` so its allocation can be reused
+    /// across transforms. `out` is resized to `input.len()`.
+    pub fn fft_into(&self, input: &[Element<F>], out: &mut Vec<Element<F>>) {
+        self.fft_internal(input, false, out);
     }
 
-    pub fn ifft(&self, input: &[Element<Bn128>]) -> Vec<Element<Bn128>> {
-        let mut out = self.fft_internal(input, true);
-        let inv_n = Element::<Bn128>::from(input.len() as u64).multiplicative_inverse_or_zero();
+    pub fn ifft(&self, input: &[Element<F>]) -> Vec<Element<F>> {
+        let mut out = Vec::new();
+        self.fft_internal(input, true, &mut out);
+        let inv_n = Element::<F>::from(input.len() as u64).multiplicative_inverse_or_zero();
         out.iter_mut().for_each(|x| *x = x.clone() * &inv_n);
         out
     }
 
-    fn fft_internal(&self, input: &[Element<Bn128>], inverse: bool) -> Vec<Element<Bn128>> {
+    fn fft_internal(&self, input: &[Element<F>], inverse: bool, output: &mut Vec<Element<F>>) {
         let n = input.len();
         let bits = (n as f64).log2() as usize;
         assert_eq!(n, 1 << bits, "Input length must be power of 2");
 
-        let mut output = vec![Element::<Bn128>::zero(); n];
-        for i in 0..n {
-            let rev = bit_reverse(i, bits);
-            output[rev] = input[i].clone();
+        // Every slot is overwritten by the permutation below.
+        output.resize(n, Element::<F>::zero());
+        for (x, &j) in input.iter().zip(self.bit_reversal_table(bits)) {
+            output[j] = x.clone();
         }
 
         for s in 1..=bits {
@@ -64,19 +207,41 @@ impl FftEngine {
             let m_half = m / 2;
             let root = if inverse { &self.wi[s] } else { &self.w[s] };
             for k in (0..n).step_by(m) {
-                let mut w = Element::<Bn128>::one();
+                let mut w = Element::<F>::one();
                 for j in 0..m_half {
                     let t = w.clone() * &output[k + j + m_half];
                     let u = output[k + j].clone();
                     output[k + j] = &u + &t;
                     output[k + j + m_half] = &u - &t;
-                    w = w * root;
+                    w *= root;
                 }
             }
         }
+    }
+}
+
+/// Inverts every element with a single field inversion (Montgomery's trick). Zeros have
+/// no inverse and map to zero.
+pub fn batch_inverse<F: Field>(elems: &[Element<F>]) -> Vec<Element<F>> {
+    // prefix[i] is the product of the non-zero elements before i.
+    let mut prefix = Vec::with_capacity(elems.len());
+    let mut acc = Element::<F>::one();
+    for e in elems {
+        prefix.push(acc.clone());
+        if !e.is_zero() {
+            acc *= e;
+        }
+    }
 
-        output
+    let mut inv = acc.multiplicative_inverse();
+    let mut out = vec![Element::<F>::zero(); elems.len()];
+    for (i, e) in elems.iter().enumerate().rev() {
+        if !e.is_zero() {
+            out[i] = &inv * &prefix[i];
+            inv *= e;
+        }
     }
+    out
 }
 
 fn bit_reverse(mut x: usize, bits: usize) -> usize {
@@ -87,3 +252,153 @@ fn bit_reverse(mut x: usize, bits: usize) -> usize {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curves::Curve;
+
+    fn round_trip<F: FftField + std::fmt::Debug>(bits: usize) {
+        let engine = FftEngine::<F>::new(bits).unwrap();
+        let input: Vec<Element<F>> = (0..1u64 << bits)
+            .map(|i| Element::from(i * i + 7))
+            .collect();
+
+        let evals = engine.fft(&input);
+        assert_ne!(evals, input);
+        assert_eq!(engine.ifft(&evals), input);
+    }
+
+    #[test]
+    fn test_fft_round_trip() {
+        round_trip::<Bn128>(4);
+        round_trip::<Bls12_381>(4);
+    }
+
+    #[test]
+    fn test_fft_into_matches_fft() {
+        let engine = FftEngine::<Bn128>::new(4).unwrap();
+        let input: Vec<Element<Bn128>> = (0..16u64).map(|i| Element::from(3 * i + 1)).collect();
+
+        let mut out = vec![Element::<Bn128>::one(); 16];
+        let ptr = out.as_ptr();
+        engine.fft_into(&input, &mut out);
+        assert_eq!(out, engine.fft(&input));
+        assert_eq!(out.as_ptr(), ptr);
+
+        // Shrinks to a smaller transform, and grows from empty.
+        engine.fft_into(&input[..8], &mut out);
+        assert_eq!(out, engine.fft(&input[..8]));
+        let mut empty = Vec::new();
+        engine.fft_into(&input, &mut empty);
+        assert_eq!(empty, engine.fft(&input));
+    }
+
+    #[test]
+    fn test_ensure_bits() {
+        let mut engine = FftEngine::<Bn128>::new(4).unwrap();
+        engine.ensure_bits(3).unwrap();
+        assert_eq!(engine.w.len(), 5);
+
+        engine.ensure_bits(10).unwrap();
+        let fresh = FftEngine::<Bn128>::new(10).unwrap();
+        assert_eq!(engine.w, fresh.w);
+        assert_eq!(engine.wi, fresh.wi);
+
+        let input: Vec<Element<Bn128>> = (0..1u64 << 10).map(|i| Element::from(i * 5 + 2)).collect();
+        assert_eq!(engine.ifft(&engine.fft(&input)), input);
+
+        assert!(matches!(
+            engine.ensure_bits(29),
+            Err(SnarkError::FftTooLarge { bits: 29, two_adicity: 28 })
+        ));
+        assert_eq!(engine.w.len(), 11);
+    }
+
+    #[test]
+    fn test_fft_too_large() {
+        assert!(FftEngine::<Bn128>::new(28).is_ok());
+        assert!(matches!(
+            FftEngine::<Bn128>::new(29),
+            Err(SnarkError::FftTooLarge { bits: 29, two_adicity: 28 })
+        ));
+        assert_eq!(Curve::new().two_adicity, 28);
+        assert_eq!(Bls12_381::two_adicity(), 32);
+    }
+
+    #[test]
+    fn test_bit_reversal_table() {
+        let engine = FftEngine::<Bn128>::new(10).unwrap();
+        let table = engine.bit_reversal_table(10);
+        assert_eq!(table.len(), 1024);
+        for (i, &j) in table.iter().enumerate() {
+            assert_eq!(j, bit_reverse(i, 10));
+        }
+        // Cached: the second call hands out the same table.
+        assert!(std::ptr::eq(table, engine.bit_reversal_table(10)));
+    }
+
+    #[test]
+    fn test_nqr() {
+        assert!(is_nqr(FftEngine::<Bn128>::new(4).unwrap().nqr()));
+        assert!(is_nqr(&Bls12_381::nqr()));
+        assert_eq!(find_nqr::<Bn128>(), Bn128::nqr());
+        assert!(!is_nqr(&Element::<Bn128>::from(4u64)));
+    }
+
+    #[test]
+    fn test_batch_inverse() {
+        let elems: Vec<Element<Bn128>> = [3u64, 0, 7, 1, 0, 12345]
+            .iter()
+            .map(|&x| Element::from(x))
+            .collect();
+        let expected: Vec<_> = elems.iter().map(|e| e.multiplicative_inverse_or_zero()).collect();
+
+        assert_eq!(batch_inverse(&elems), expected);
+        assert!(batch_inverse::<Bn128>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_pow_u64() {
+        let two = Element::<Bn128>::from(2u64);
+        assert_eq!(pow_u64(&two, 10), Element::from(1024u64));
+        assert!(pow_u64(&two, 0).is_one());
+        for exp in [1u64, 7, 255, 1 << 40, u64::MAX] {
+            let base = Element::<Bn128>::from(123456789u64);
+            assert_eq!(pow_u64(&base, exp), base.exponentiation(&Element::from(exp)));
+        }
+    }
+
+    #[test]
+    fn test_root_for_size() {
+        let engine = FftEngine::<Bn128>::new(5).unwrap();
+        let w8 = engine.root_for_size(8).unwrap();
+        assert!(pow_u64(&w8, 8).is_one());
+        assert!(!pow_u64(&w8, 4).is_one());
+        assert!(engine.root_for_size(1).unwrap().is_one());
+        assert_eq!(engine.root_for_size(32).unwrap(), engine.w[5]);
+
+        for size in [0, 6, 64] {
+            assert!(matches!(
+                engine.root_for_size(size),
+                Err(SnarkError::InvalidDomainSize { size: s, max_bits: 5 }) if s == size
+            ));
+        }
+    }
+
+    #[test]
+    fn test_roots_of_unity() {
+        let engine = FftEngine::<Bn128>::new(5).unwrap();
+        // w[s] has order exactly 2^s.
+        assert!(engine.w[0].is_one());
+        assert_eq!(engine.w[1], -Element::<Bn128>::one());
+        for (w, wi) in engine.w.iter().zip(&engine.wi) {
+            assert!((w * wi).is_one());
+        }
+        // Same generator as the previous nqr search produced.
+        assert_eq!(
+            engine.w[5],
+            Element::from(5u64).exponentiation(&Element::from((Bn128::order() - 1u32) >> 5))
+        );
+    }
+}
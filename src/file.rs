@@ -1,21 +1,54 @@
-use anyhow::{Result, anyhow, bail};
+use crate::big_buffer::BigBuffer;
+use crate::curves::Curve;
+use crate::error::{Result, SnarkError};
+use crate::json::{Json, JsonValue};
+use crate::plonk::{FromMontgomeryBytes, ToMontgomeryBytes, to_n8r_bytes};
 use r1cs::num::BigUint;
+use r1cs::{Bn128, Element, Field};
+use std::any::Any;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::SeekFrom;
+use std::io::{Cursor, SeekFrom};
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
 
 const R1CS_FILE_HEADER_SECTION: u32 = 1;
+const R1CS_FILE_CONSTRAINTS_SECTION: u32 = 2;
+const R1CS_FILE_WIRE2LABELID_SECTION: u32 = 3;
 const R1CS_FILE_CUSTOM_GATES_LIST_SECTION: u32 = 4;
 const R1CS_FILE_CUSTOM_GATES_USES_SECTION: u32 = 5;
 
+/// The (A, B, C) linear combinations of an R1CS constraint, keyed by signal id.
+pub type Constraint = [HashMap<u32, BigUint>; 3];
+
+/// A [`Constraint`] with its coefficients already taken as elements of `F`.
+pub type FieldConstraint<F = Bn128> = [HashMap<u32, Element<F>>; 3];
+
 pub struct R1cs {
     pub header: R1csHeader,
-    pub constraints: Vec<[HashMap<u32, BigUint>; 3]>,
+    pub constraints: Vec<Constraint>,
+    pub custom_gates: Vec<CustomGate>,
+    pub custom_gates_uses: Vec<CustomGateUse>,
+}
+
+/// A custom gate template declared in the custom gates list section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomGate {
+    pub template_name: String,
+    pub parameters: Vec<BigUint>,
 }
 
-#[derive(Debug)]
+/// An instance of a custom gate: the index into the gates list and the signals it binds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomGateUse {
+    pub id: u32,
+    pub signals: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct R1csHeader {
     pub n8: u32,
     pub prime: BigUint,
@@ -28,85 +61,469 @@ pub struct R1csHeader {
     pub use_custom_gates: bool,
 }
 
-#[derive(Debug, Clone)]
+impl R1csHeader {
+    /// Lower bound on the PLONK gates of this circuit: one per public signal and at least
+    /// one per constraint, before any addition gates.
+    ///
+    /// Constraints that reduce to `0 = 0` produce no gate, so a circuit made of them can
+    /// fall below this bound; circom does not emit such constraints.
+    pub fn min_plonk_gates(&self) -> usize {
+        self.n_constraints as usize + self.n_outputs as usize + self.n_pub_inputs as usize
+    }
+
+    /// Checks that the circuit is defined over `curve`'s scalar field, with elements of the
+    /// same byte width: the prime must equal `r` and `n8` must equal `n8r`.
+    pub fn check_field(&self, curve: &Curve) -> Result<()> {
+        if self.prime != curve.r {
+            return Err(SnarkError::PrimeMismatch {
+                r1cs: self.prime.clone(),
+                ptau: curve.r.clone(),
+            });
+        }
+        if self.n8 as usize != curve.n8r {
+            return Err(SnarkError::InvalidFieldSize {
+                expected: curve.n8r,
+                got: self.n8 as usize,
+            });
+        }
+        Ok(())
+    }
+
+    /// Number of public signals (outputs then public inputs), after checking that the
+    /// variables cover the one wire and every input and output signal.
+    ///
+    /// The remaining variables are intermediate signals, so `n_vars` may exceed that count
+    /// but never fall short of it.
+    pub fn validate_public_count(&self) -> Result<u32> {
+        let n_signals = self.n_outputs as u64 + self.n_pub_inputs as u64 + self.n_prv_inputs as u64;
+        if (self.n_vars as u64) < n_signals + 1 {
+            return Err(SnarkError::SignalCountMismatch {
+                n_vars: self.n_vars,
+                n_signals,
+            });
+        }
+        Ok(self.n_outputs + self.n_pub_inputs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Section {
     pub offset: u64,
     pub size: u64,
 }
 
+/// The prime is written as a decimal string, since JSON numbers cannot hold it.
+impl Json for R1csHeader {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("n8".to_string(), JsonValue::from(self.n8 as u64)),
+            ("prime".to_string(), JsonValue::from(self.prime.to_string())),
+            ("nVars".to_string(), JsonValue::from(self.n_vars as u64)),
+            ("nOutputs".to_string(), JsonValue::from(self.n_outputs as u64)),
+            ("nPubInputs".to_string(), JsonValue::from(self.n_pub_inputs as u64)),
+            ("nPrvInputs".to_string(), JsonValue::from(self.n_prv_inputs as u64)),
+            ("nLabels".to_string(), JsonValue::from(self.n_labels)),
+            ("nConstraints".to_string(), JsonValue::from(self.n_constraints as u64)),
+            ("useCustomGates".to_string(), JsonValue::Bool(self.use_custom_gates)),
+        ])
+    }
+
+    fn from_json(value: &JsonValue) -> Result<Self> {
+        let prime = value.str_field("prime")?;
+        Ok(Self {
+            n8: value.number_field("n8")?,
+            prime: prime
+                .parse()
+                .map_err(|_| SnarkError::InvalidJson(format!("invalid prime '{}'", prime)))?,
+            n_vars: value.number_field("nVars")?,
+            n_outputs: value.number_field("nOutputs")?,
+            n_pub_inputs: value.number_field("nPubInputs")?,
+            n_prv_inputs: value.number_field("nPrvInputs")?,
+            n_labels: value.number_field("nLabels")?,
+            n_constraints: value.number_field("nConstraints")?,
+            use_custom_gates: value.bool_field("useCustomGates")?,
+        })
+    }
+}
+
+impl Json for Section {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("offset".to_string(), JsonValue::from(self.offset)),
+            ("size".to_string(), JsonValue::from(self.size)),
+        ])
+    }
+
+    fn from_json(value: &JsonValue) -> Result<Self> {
+        Ok(Self {
+            offset: value.number_field("offset")?,
+            size: value.number_field("size")?,
+        })
+    }
+}
+
+/// Every section of a section table with its id, ordered by offset, i.e. in file order.
+pub fn sections_sorted(sections: &HashMap<u32, Vec<Section>>) -> Vec<(u32, &Section)> {
+    let mut sorted: Vec<_> = sections
+        .iter()
+        .flat_map(|(&id, v)| v.iter().map(move |s| (id, s)))
+        .collect();
+    sorted.sort_by_key(|(_, s)| s.offset);
+    sorted
+}
+
+/// One entry of [`describe_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionInfo {
+    pub id: u32,
+    pub offset: u64,
+    pub size: u64,
+    /// What the section holds in this file type, or "unknown".
+    pub name: &'static str,
+}
+
+/// Format versions of the snarkjs file types, the newest each reader accepts.
+pub const FILE_VERSIONS: &[(&str, u32)] = &[
+    ("ptau", crate::ptau_file::PTAU_MAX_VERSION),
+    ("r1cs", 1),
+    ("zkey", 1),
+    ("wtns", 2),
+];
+
+/// Current version of `file_type` from [`FILE_VERSIONS`], or `None` for other types.
+pub fn current_version(file_type: &str) -> Option<u32> {
+    FILE_VERSIONS
+        .iter()
+        .find(|(t, _)| *t == file_type)
+        .map(|&(_, version)| version)
+}
+
+/// Name of section `id` in a file of type `file_type` ("ptau", "r1cs", "zkey" for
+/// PLONK keys, or "pchk"), as snarkjs calls it.
+pub fn section_name(file_type: &str, id: u32) -> Option<&'static str> {
+    let name = match (file_type, id) {
+        (_, 1) => "header",
+        ("ptau", 2) => "tauG1",
+        ("ptau", 3) => "tauG2",
+        ("ptau", 4) => "alphaTauG1",
+        ("ptau", 5) => "betaTauG1",
+        ("ptau", 6) => "betaG2",
+        ("ptau", 7) => "contributions",
+        ("ptau", 12) => "lagrangeTauG1",
+        ("ptau", 13) => "lagrangeTauG2",
+        ("ptau", 14) => "lagrangeAlphaTauG1",
+        ("ptau", 15) => "lagrangeBetaTauG1",
+        ("r1cs", 2) => "constraints",
+        ("r1cs", 3) => "wire2LabelId",
+        ("r1cs", 4) => "customGatesList",
+        ("r1cs", 5) => "customGatesUses",
+        ("zkey", 2) => "plonkHeader",
+        ("zkey" | "pchk", 3) => "additions",
+        ("zkey", 4) => "aMap",
+        ("zkey", 5) => "bMap",
+        ("zkey", 6) => "cMap",
+        ("zkey", 7) => "qm",
+        ("zkey", 8) => "ql",
+        ("zkey", 9) => "qr",
+        ("zkey", 10) => "qo",
+        ("zkey", 11) => "qc",
+        ("zkey", 12) => "sigma",
+        ("zkey", 13) => "lagrange",
+        ("zkey", 14) => "ptau",
+        ("pchk", 2) => "constraints",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Lists the sections of the `expected_type` file at `path` in file order, naming the
+/// ones [`section_name`] knows. Any format version is accepted.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn describe_file(path: &str, expected_type: &str) -> Result<Vec<SectionInfo>> {
+    let (_, sections) = read_bin_file(path, expected_type, u32::MAX).await?;
+    Ok(sections_sorted(&sections)
+        .into_iter()
+        .map(|(id, section)| SectionInfo {
+            id,
+            offset: section.offset,
+            size: section.size,
+            name: section_name(expected_type, id).unwrap_or("unknown"),
+        })
+        .collect())
+}
+
+/// Byte order of the integers and field elements in a binary file.
+///
+/// Every format read here (r1cs, ptau, zkey, wtns) is little-endian, as snarkjs writes
+/// them. [`BinFile`] and the section decoders go through this type instead of calling
+/// `from_le_bytes` directly, so the assumption lives in one place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+impl ByteOrder {
+    /// Decodes the first 4 bytes of `bytes`.
+    pub fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes: [u8; 4] = bytes[..4].try_into().unwrap();
+        match self {
+            Self::LittleEndian => u32::from_le_bytes(bytes),
+            Self::BigEndian => u32::from_be_bytes(bytes),
+        }
+    }
+
+    /// Decodes the first 8 bytes of `bytes`.
+    pub fn u64(self, bytes: &[u8]) -> u64 {
+        let bytes: [u8; 8] = bytes[..8].try_into().unwrap();
+        match self {
+            Self::LittleEndian => u64::from_le_bytes(bytes),
+            Self::BigEndian => u64::from_be_bytes(bytes),
+        }
+    }
+
+    pub fn u32_bytes(self, val: u32) -> [u8; 4] {
+        match self {
+            Self::LittleEndian => val.to_le_bytes(),
+            Self::BigEndian => val.to_be_bytes(),
+        }
+    }
+
+    pub fn u64_bytes(self, val: u64) -> [u8; 8] {
+        match self {
+            Self::LittleEndian => val.to_le_bytes(),
+            Self::BigEndian => val.to_be_bytes(),
+        }
+    }
+
+    /// Decodes an integer spanning all of `bytes`, such as a modulus.
+    pub fn biguint(self, bytes: &[u8]) -> BigUint {
+        match self {
+            Self::LittleEndian => BigUint::from_bytes_le(bytes),
+            Self::BigEndian => BigUint::from_bytes_be(bytes),
+        }
+    }
+
+    /// Decodes a field element stored in Montgomery form, rejecting values at or above
+    /// the modulus.
+    pub fn field(self, bytes: &[u8]) -> Result<Element<Bn128>> {
+        Element::from_montgomery_bytes(&self.to_le(bytes))
+    }
+
+    /// Encodes `x` in Montgomery form, zero-padded to `n8` bytes.
+    pub fn field_bytes(self, x: &Element<Bn128>, n8: usize) -> Vec<u8> {
+        let bytes = to_n8r_bytes(&x.as_montgomery_bytes(), n8);
+        self.to_le(&bytes).into_owned()
+    }
+
+    /// `bytes` reordered from this order to little-endian, or back: the conversion is
+    /// its own inverse.
+    fn to_le(self, bytes: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            Self::LittleEndian => Cow::Borrowed(bytes),
+            Self::BigEndian => Cow::Owned(bytes.iter().rev().copied().collect()),
+        }
+    }
+}
+
+/// Anything a [`BinFile`] can read from and write to.
+///
+/// Native builds use `tokio::fs::File`. Targets without a filesystem, such as wasm32, can
+/// pass an in-memory buffer or their own fetch-backed reader to [`BinFile::from_storage`].
+pub trait Storage: AsyncRead + AsyncWrite + AsyncSeek + Unpin {
+    /// Erases the type, so [`BinFile::into_storage`] can hand the storage back.
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: AsyncRead + AsyncWrite + AsyncSeek + Unpin + 'static> Storage for T {
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Space set aside by [`BinFile::reserve_section`], to be written later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReservedSection {
+    pub id: u32,
+    /// Offset of the section body.
+    pub offset: u64,
+    pub size: u64,
+}
+
 pub struct BinFile {
-    pub file: File,
+    pub(crate) file: Box<dyn Storage>,
     pub pos: u64,
     section_start: Option<u64>,
+    /// Reservations not filled yet.
+    reserved: Vec<ReservedSection>,
+    /// The reservation being filled, and the end of the file to return to.
+    filling: Option<(ReservedSection, u64)>,
+    byte_order: ByteOrder,
 }
 
 impl BinFile {
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path).await?;
-        Ok(Self {
-            file,
+        Ok(Self::from_storage(file))
+    }
+
+    /// A file held entirely in memory, positioned at its start. Writes grow the buffer.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self::from_storage(Cursor::new(bytes))
+    }
+
+    /// Reads from `storage`, positioned at its start.
+    pub fn from_storage(storage: impl Storage + 'static) -> Self {
+        Self {
+            file: Box::new(storage),
             pos: 0,
             section_start: None,
-        })
+            reserved: Vec::new(),
+            filling: None,
+            byte_order: ByteOrder::default(),
+        }
+    }
+
+    /// Uses `order` for the integers and field elements read or written from now on.
+    /// Files start little-endian, which is what every snarkjs format uses.
+    pub fn with_byte_order(mut self, order: ByteOrder) -> Self {
+        self.byte_order = order;
+        self
+    }
+
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// The storage this file was opened over, if it is a `T`. Flush first: buffered
+    /// writes are not carried over.
+    pub fn into_storage<T: 'static>(self) -> Option<T> {
+        self.file.into_any().downcast().ok().map(|storage| *storage)
     }
 
     pub async fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
         let mut buf = vec![0u8; len];
-        self.file.read_exact(&mut buf).await?;
-        self.pos += len as u64;
+        self.read_exact(&mut buf).await?;
         Ok(buf)
     }
 
+    /// Reads an `n8`-byte integer, such as a field modulus.
+    pub async fn read_biguint(&mut self, n8: usize) -> Result<BigUint> {
+        Ok(self.byte_order.biguint(&self.read_bytes(n8).await?))
+    }
+
+    /// Reads an `n8`-byte field element stored in Montgomery form, as zkeys hold them.
+    pub async fn read_field(&mut self, n8: usize) -> Result<Element<Bn128>> {
+        self.byte_order.field(&self.read_bytes(n8).await?)
+    }
+
     pub async fn read_u32(&mut self) -> Result<u32> {
         let mut buf = [0u8; 4];
-        self.file.read_exact(&mut buf).await?;
-        self.pos += 4;
-        Ok(u32::from_le_bytes(buf))
+        self.read_exact(&mut buf).await?;
+        Ok(self.byte_order.u32(&buf))
     }
 
     pub async fn read_u64(&mut self) -> Result<u64> {
         let mut buf = [0u8; 8];
-        self.file.read_exact(&mut buf).await?;
-        self.pos += 8;
-        Ok(u64::from_le_bytes(buf))
+        self.read_exact(&mut buf).await?;
+        Ok(self.byte_order.u64(&buf))
+    }
+
+    /// Fills `buf` from the current position and advances past it. Running out of data
+    /// fails with [`SnarkError::UnexpectedEof`], which records where the read started
+    /// and how many bytes were left.
+    pub(crate) async fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let n = self.file.read(&mut buf[filled..]).await?;
+            if n == 0 {
+                return Err(SnarkError::UnexpectedEof {
+                    pos: self.pos,
+                    requested: buf.len() as u64,
+                    available: filled as u64,
+                });
+            }
+            filled += n;
+        }
+        self.pos += buf.len() as u64;
+        Ok(())
     }
 
     pub async fn skip(&mut self, n: u64) -> Result<()> {
-        self.pos += n;
+        self.pos = self.pos.checked_add(n).ok_or(SnarkError::UnexpectedEof {
+            pos: self.pos,
+            requested: n,
+            available: 0,
+        })?;
         self.file.seek(SeekFrom::Start(self.pos)).await?;
         Ok(())
     }
 
+    /// Creates `path` and writes the file preamble. For the types in [`FILE_VERSIONS`],
+    /// `version` must be one their readers accept.
+    #[cfg(not(target_arch = "wasm32"))]
     pub async fn create<P: AsRef<Path>>(
         path: P,
         magic_type: &str,
         version: u32,
         n_sections: u32,
     ) -> Result<Self> {
-        if magic_type.len() != 4 {
-            bail!("Magic type must be exactly 4 characters");
-        }
-
-        let mut file = File::create(path).await?;
-        let mut pos = 0;
+        Self::check_magic(magic_type)?;
+        let file = File::create(path).await?;
+        Self::create_in(file, magic_type, version, n_sections).await
+    }
 
-        // Write magic type
-        file.write_all(magic_type.as_bytes()).await?;
-        pos += 4;
+    /// Same as [`BinFile::create`], writing to `storage` instead of a new file.
+    pub async fn create_in(
+        storage: impl Storage + 'static,
+        magic_type: &str,
+        version: u32,
+        n_sections: u32,
+    ) -> Result<Self> {
+        let order = ByteOrder::default();
+        Self::create_in_with_byte_order(storage, magic_type, version, n_sections, order).await
+    }
 
-        // Write version
-        file.write_all(&version.to_le_bytes()).await?;
-        pos += 4;
+    /// Same as [`BinFile::create_in`], writing the preamble, the section headers and
+    /// everything after them in `order`.
+    pub async fn create_in_with_byte_order(
+        storage: impl Storage + 'static,
+        magic_type: &str,
+        version: u32,
+        n_sections: u32,
+        order: ByteOrder,
+    ) -> Result<Self> {
+        Self::check_magic(magic_type)?;
+        Self::check_version(magic_type, version)?;
+
+        let mut fd = Self::from_storage(storage).with_byte_order(order);
+        fd.write_bytes(magic_type.as_bytes()).await?;
+        fd.write_u32(version).await?;
+        fd.write_u32(n_sections).await?;
+        Ok(fd)
+    }
 
-        // Write number of sections
-        file.write_all(&n_sections.to_le_bytes()).await?;
-        pos += 4;
+    /// Rejects versions the readers of a known file type would not accept: 0, or above
+    /// [`current_version`]. Other types are written with any version.
+    fn check_version(magic_type: &str, version: u32) -> Result<()> {
+        if let Some(max) = current_version(magic_type)
+            && (version == 0 || version > max)
+        {
+            return Err(SnarkError::UnsupportedVersion { version, max });
+        }
+        Ok(())
+    }
 
-        Ok(Self {
-            file,
-            pos,
-            section_start: None,
-        })
+    fn check_magic(magic_type: &str) -> Result<()> {
+        if magic_type.len() != 4 {
+            return Err(SnarkError::InvalidMagic {
+                expected: "4 characters".to_string(),
+                got: magic_type.to_string(),
+            });
+        }
+        Ok(())
     }
 
     pub async fn write_bytes(&mut self, data: &[u8]) -> Result<()> {
@@ -115,26 +532,40 @@ impl BinFile {
         Ok(())
     }
 
-    pub async fn write_u32(&mut self, val: u32) -> Result<()> {
-        self.file.write_u32_le(val).await?;
-        self.pos += 4;
+    /// Writes every page of `buffer` in order, without assembling it into one slice.
+    pub async fn write_big_buffer(&mut self, buffer: &BigBuffer) -> Result<()> {
+        for page in buffer.iter_pages() {
+            self.write_bytes(page).await?;
+        }
         Ok(())
     }
 
+    /// Writes `x` in Montgomery form, zero-padded to `n8` bytes.
+    pub async fn write_field(&mut self, x: &Element<Bn128>, n8: usize) -> Result<()> {
+        self.write_bytes(&self.byte_order.field_bytes(x, n8)).await
+    }
+
+    pub async fn write_u32(&mut self, val: u32) -> Result<()> {
+        self.write_bytes(&self.byte_order.u32_bytes(val)).await
+    }
+
     pub async fn write_u64(&mut self, val: u64) -> Result<()> {
-        self.file.write_u64_le(val).await?;
-        self.pos += 8;
-        Ok(())
+        self.write_bytes(&self.byte_order.u64_bytes(val)).await
     }
 
+    /// Flushes the underlying storage. Fails if a reserved section was never filled,
+    /// since the file would contain a zeroed placeholder.
     pub async fn flush(&mut self) -> Result<()> {
+        if !self.reserved.is_empty() || self.filling.is_some() {
+            return Err(SnarkError::InvalidWriteState("Reserved section not filled"));
+        }
         self.file.flush().await?;
         Ok(())
     }
 
     pub async fn start_write_section(&mut self, id_section: u32) -> Result<()> {
-        if self.section_start.is_some() {
-            bail!("Already writing a section");
+        if self.section_start.is_some() || self.filling.is_some() {
+            return Err(SnarkError::InvalidWriteState("Already writing a section"));
         }
 
         self.write_u32(id_section).await?; // Section ID
@@ -147,58 +578,144 @@ impl BinFile {
         let section_start = self
             .section_start
             .take()
-            .ok_or_else(|| anyhow!("Not writing a section"))?;
+            .ok_or(SnarkError::InvalidWriteState("Not writing a section"))?;
         let section_size = self.pos - section_start - 8;
         let current_pos = self.pos;
 
         // Seek back to write the section size
         self.file.seek(SeekFrom::Start(section_start)).await?;
-        self.file.write_all(&section_size.to_le_bytes()).await?;
+        self.file.write_all(&self.byte_order.u64_bytes(section_size)).await?;
         self.pos = current_pos; // Restore pos after writing
         self.file.seek(SeekFrom::Start(current_pos)).await?;
 
         Ok(())
     }
+
+    /// Writes the header of a section whose `size`-byte body is zero-filled for now, so
+    /// other sections can follow before it is written with
+    /// [`start_fill_section`](Self::start_fill_section).
+    pub async fn reserve_section(&mut self, id_section: u32, size: u64) -> Result<ReservedSection> {
+        if self.section_start.is_some() || self.filling.is_some() {
+            return Err(SnarkError::InvalidWriteState("Already writing a section"));
+        }
+
+        self.write_u32(id_section).await?;
+        self.write_u64(size).await?;
+        let reserved = ReservedSection {
+            id: id_section,
+            offset: self.pos,
+            size,
+        };
+
+        let zeros = vec![0u8; size.min(1 << 16) as usize];
+        let mut remaining = size;
+        while remaining > 0 {
+            let len = remaining.min(zeros.len() as u64);
+            self.write_bytes(&zeros[..len as usize]).await?;
+            remaining -= len;
+        }
+
+        self.reserved.push(reserved);
+        Ok(reserved)
+    }
+
+    /// Moves back into a reserved section; the following writes fill its body.
+    pub async fn start_fill_section(&mut self, section: ReservedSection) -> Result<()> {
+        if self.section_start.is_some() || self.filling.is_some() {
+            return Err(SnarkError::InvalidWriteState("Already writing a section"));
+        }
+        let idx = self
+            .reserved
+            .iter()
+            .position(|r| *r == section)
+            .ok_or(SnarkError::InvalidWriteState("Section is not reserved"))?;
+        self.reserved.swap_remove(idx);
+
+        self.filling = Some((section, self.pos));
+        self.file.seek(SeekFrom::Start(section.offset)).await?;
+        self.pos = section.offset;
+        Ok(())
+    }
+
+    /// Checks that the reserved section was filled exactly and returns to the end of the file.
+    pub async fn end_fill_section(&mut self) -> Result<()> {
+        let (section, end) = self
+            .filling
+            .take()
+            .ok_or(SnarkError::InvalidWriteState("Not filling a section"))?;
+        let written = self.pos - section.offset;
+        if written != section.size {
+            return Err(SnarkError::SectionSizeMismatch {
+                section: section.id,
+                expected: section.size,
+                got: written,
+            });
+        }
+
+        self.file.seek(SeekFrom::Start(end)).await?;
+        self.pos = end;
+        Ok(())
+    }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub async fn read_bin_file(
     file_name: &str,
     expected_type: &str,
     max_version: u32,
 ) -> Result<(BinFile, HashMap<u32, Vec<Section>>)> {
-    let mut bin_file = BinFile::open(file_name).await?;
+    let bin_file = BinFile::open(file_name).await?;
+    read_bin_file_fd(bin_file, expected_type, max_version).await
+}
+
+/// Same as [`read_bin_file`], over an already opened file such as [`BinFile::from_bytes`].
+pub async fn read_bin_file_fd(
+    mut bin_file: BinFile,
+    expected_type: &str,
+    max_version: u32,
+) -> Result<(BinFile, HashMap<u32, Vec<Section>>)> {
+    let file_size = bin_file.file.seek(SeekFrom::End(0)).await?;
+    bin_file.file.seek(SeekFrom::Start(0)).await?;
+    bin_file.pos = 0;
 
     let file_type_bytes = bin_file.read_bytes(4).await?;
-    let read_type = String::from_utf8(file_type_bytes.clone())
-        .map_err(|_| anyhow!("Invalid UTF-8 in file type"))?;
+    let read_type = String::from_utf8_lossy(&file_type_bytes).into_owned();
 
     if read_type != expected_type {
-        return Err(anyhow!(
-            "{}: Invalid file format (expected {}, got {})",
-            file_name,
-            expected_type,
-            read_type
-        ));
+        return Err(SnarkError::InvalidMagic {
+            expected: expected_type.to_string(),
+            got: read_type,
+        });
     }
 
     let version = bin_file.read_u32().await?;
     if version > max_version {
-        return Err(anyhow!(
-            "Version {} not supported (max {})",
+        return Err(SnarkError::UnsupportedVersion {
             version,
-            max_version
-        ));
+            max: max_version,
+        });
     }
 
     let n_sections = bin_file.read_u32().await?;
 
     let mut sections: HashMap<u32, Vec<Section>> = HashMap::new();
 
+    // Sections are laid out back to back, so checking each one against the file end
+    // also rules out overlaps.
     for _ in 0..n_sections {
         let ht = bin_file.read_u32().await?;
         let hl = bin_file.read_u64().await?;
         let offset = bin_file.pos;
 
+        if offset.checked_add(hl).is_none_or(|end| end > file_size) {
+            return Err(SnarkError::SectionExceedsFile {
+                section: ht,
+                offset,
+                size: hl,
+                file_size,
+            });
+        }
+
         sections
             .entry(ht)
             .or_default()
@@ -207,22 +724,35 @@ pub async fn read_bin_file(
         bin_file.skip(hl).await?;
     }
 
+    if bin_file.pos != file_size {
+        return Err(SnarkError::TrailingBytes {
+            pos: bin_file.pos,
+            file_size,
+        });
+    }
+
     Ok((bin_file, sections))
 }
 
+/// The one section with id `id`, for sections a file must not repeat. Fails with
+/// [`SnarkError::SectionDuplicated`] rather than silently using the first copy.
+pub fn get_unique_section(sections: &HashMap<u32, Vec<Section>>, id: u32) -> Result<&Section> {
+    match sections.get(&id).map(Vec::as_slice) {
+        None | Some([]) => Err(SnarkError::SectionMissing(id)),
+        Some([section]) => Ok(section),
+        Some(_) => Err(SnarkError::SectionDuplicated(id)),
+    }
+}
+
+/// Reads only the header section, leaving the constraints untouched. This is enough to
+/// size a setup (see [`R1csHeader::min_plonk_gates`]) before [`read_r1cs_fd`] loads
+/// everything.
 pub async fn read_r1cs_header(
     fd: &mut BinFile,
     sections: &HashMap<u32, Vec<Section>>,
 ) -> Result<R1csHeader> {
     // Locate header section
-    let section = sections
-        .get(&R1CS_FILE_HEADER_SECTION)
-        .and_then(|v| v.first())
-        .ok_or_else(|| anyhow!("R1CS header section missing"))?;
-
-    if sections[&R1CS_FILE_HEADER_SECTION].len() > 1 {
-        bail!("R1CS header section duplicated");
-    }
+    let section = get_unique_section(sections, R1CS_FILE_HEADER_SECTION)?;
 
     // Seek to header section start
     fd.file.seek(SeekFrom::Start(section.offset)).await?;
@@ -230,8 +760,7 @@ pub async fn read_r1cs_header(
 
     // Read header values
     let n8 = fd.read_u32().await?;
-    let prime_bytes = fd.read_bytes(n8 as usize).await?;
-    let prime = BigUint::from_bytes_le(&prime_bytes);
+    let prime = fd.read_biguint(n8 as usize).await?;
 
     let n_vars = fd.read_u32().await?;
     let n_outputs = fd.read_u32().await?;
@@ -247,14 +776,14 @@ pub async fn read_r1cs_header(
     // Validate we consumed the section fully
     let read_len = fd.pos - section.offset;
     if read_len != section.size {
-        bail!(
-            "Invalid R1CS header section size: read {}, expected {}",
-            read_len,
-            section.size
-        );
+        return Err(SnarkError::SectionSizeMismatch {
+            section: R1CS_FILE_HEADER_SECTION,
+            expected: section.size,
+            got: read_len,
+        });
     }
 
-    Ok(R1csHeader {
+    let header = R1csHeader {
         n8,
         prime,
         n_vars,
@@ -264,7 +793,9 @@ pub async fn read_r1cs_header(
         n_labels,
         n_constraints,
         use_custom_gates,
-    })
+    };
+    header.validate_public_count()?;
+    Ok(header)
 }
 
 pub async fn read_section(
@@ -274,71 +805,136 @@ pub async fn read_section(
     offset: Option<u64>,
     length: Option<u64>,
 ) -> Result<Vec<u8>> {
-    let section = sections
-        .get(&section_id)
-        .and_then(|v| v.first())
-        .ok_or_else(|| anyhow!("Section {} not found", section_id))?;
+    let section = get_unique_section(sections, section_id)?;
 
     let off = offset.unwrap_or(0);
-    let len = length.unwrap_or(section.size - off);
-
-    if off + len > section.size {
-        return Err(anyhow!(
-            "Out-of-bounds section read: offset {} + length {} > size {}",
-            off,
-            len,
-            section.size
-        ));
+    let len = length.unwrap_or(section.size.saturating_sub(off));
+
+    if off.checked_add(len).is_none_or(|end| end > section.size) {
+        return Err(SnarkError::SectionOutOfBounds {
+            section: section_id,
+            offset: off,
+            length: len,
+            size: section.size,
+        });
     }
 
     fd.file.seek(SeekFrom::Start(section.offset + off)).await?;
     fd.pos = section.offset + off;
 
     let mut buf = vec![0u8; len as usize];
-    fd.file.read_exact(&mut buf).await?;
-    fd.pos += len;
+    fd.read_exact(&mut buf).await?;
 
     Ok(buf)
 }
 
+/// Reads the sections `ids` concurrently, each through its own file handle, so they do
+/// not contend for the single cursor of a [`BinFile`]. `sections` is the table
+/// [`read_bin_file`] returned for the same `path`.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn read_sections_parallel(
+    path: &str,
+    sections: &HashMap<u32, Vec<Section>>,
+    ids: &[u32],
+) -> Result<HashMap<u32, Vec<u8>>> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for &id in ids {
+        let section = get_unique_section(sections, id)?;
+        let (path, offset, size) = (path.to_owned(), section.offset, section.size);
+        tasks.spawn(async move {
+            let mut file = File::open(path).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+            let mut buf = vec![0u8; size as usize];
+            file.read_exact(&mut buf).await?;
+            Ok::<_, SnarkError>((id, buf))
+        });
+    }
+
+    let mut out = HashMap::with_capacity(ids.len());
+    while let Some(task) = tasks.join_next().await {
+        let (id, buf) = task.map_err(std::io::Error::from)??;
+        out.insert(id, buf);
+    }
+    Ok(out)
+}
+
+/// Reads a section made of fixed-width records, decoding each `record_len`-byte slice
+/// with `decode`, which may reject a record. The section size must be a multiple of
+/// `record_len`, which must not be zero.
+pub async fn read_records<T>(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    section_id: u32,
+    record_len: usize,
+    decode: impl Fn(&[u8]) -> Result<T>,
+) -> Result<Vec<T>> {
+    if record_len == 0 {
+        return Err(SnarkError::ZeroSize("record length"));
+    }
+    let buf = read_section(fd, sections, section_id, None, None).await?;
+    if buf.len() % record_len != 0 {
+        return Err(SnarkError::SectionRecordMisaligned {
+            section: section_id,
+            size: buf.len() as u64,
+            record_len,
+        });
+    }
+    buf.chunks_exact(record_len).map(decode).collect()
+}
+
 pub async fn read_constraints(
     fd: &mut BinFile,
     sections: &HashMap<u32, Vec<Section>>,
     r1cs: &R1csHeader,
-) -> Result<Vec<[HashMap<u32, BigUint>; 3]>> {
-    const CONSTRAINTS_SECTION: u32 = 2;
+) -> Result<Vec<Constraint>> {
+    let order = fd.byte_order;
+    read_constraints_with(fd, sections, r1cs, |bytes| order.biguint(bytes)).await
+}
 
-    let section = sections
-        .get(&CONSTRAINTS_SECTION)
-        .and_then(|v| v.first())
-        .ok_or_else(|| anyhow::anyhow!("Missing constraints section"))?;
+/// Same as [`read_constraints`], but each coefficient is decoded straight into an
+/// element of `F` instead of going through an intermediate `BigUint` map.
+pub async fn read_constraints_as_field<F: Field>(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    r1cs: &R1csHeader,
+) -> Result<Vec<FieldConstraint<F>>> {
+    let order = fd.byte_order;
+    read_constraints_with(fd, sections, r1cs, |bytes| Element::from(order.biguint(bytes))).await
+}
+
+/// Reads the constraints section, turning each `n8`-byte coefficient into a `T`.
+async fn read_constraints_with<T>(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    r1cs: &R1csHeader,
+    decode: impl Fn(&[u8]) -> T,
+) -> Result<Vec<[HashMap<u32, T>; 3]>> {
+    let section = get_unique_section(sections, R1CS_FILE_CONSTRAINTS_SECTION)?;
 
     fd.file.seek(SeekFrom::Start(section.offset)).await?;
     fd.pos = section.offset;
 
     let mut buf = vec![0u8; section.size as usize];
-    fd.file.read_exact(&mut buf).await?;
-    fd.pos += section.size;
+    fd.read_exact(&mut buf).await?;
 
-    let mut constraints: Vec<[HashMap<u32, BigUint>; 3]> =
-        Vec::with_capacity(r1cs.n_constraints as usize);
+    let order = fd.byte_order;
+    let mut constraints = Vec::with_capacity(r1cs.n_constraints as usize);
     let mut cursor = 0;
 
     for _ in 0..r1cs.n_constraints {
-        let mut triple: [HashMap<u32, BigUint>; 3] = Default::default();
+        let mut triple: [HashMap<u32, T>; 3] = Default::default();
         for lc in &mut triple {
-            let n_idx = u32::from_le_bytes(buf[cursor..cursor + 4].try_into().unwrap());
+            let n_idx = order.u32(&buf[cursor..]);
             cursor += 4;
 
             for _ in 0..n_idx {
-                let idx = u32::from_le_bytes(buf[cursor..cursor + 4].try_into().unwrap());
+                let idx = order.u32(&buf[cursor..]);
                 cursor += 4;
 
                 let coeff_bytes = &buf[cursor..cursor + r1cs.n8 as usize];
                 cursor += r1cs.n8 as usize;
 
-                let coeff = BigUint::from_bytes_le(coeff_bytes);
-                lc.insert(idx, coeff);
+                lc.insert(idx, decode(coeff_bytes));
             }
         }
         constraints.push(triple);
@@ -346,22 +942,302 @@ pub async fn read_constraints(
 
     // Optional: sanity check we consumed entire section
     if (cursor as u64) != section.size {
-        bail!(
-            "Unexpected constraint section size: read {}, expected {}",
-            cursor,
-            section.size
-        );
+        return Err(SnarkError::SectionSizeMismatch {
+            section: R1CS_FILE_CONSTRAINTS_SECTION,
+            expected: section.size,
+            got: cursor as u64,
+        });
     }
 
     Ok(constraints)
 }
 
+/// Bytes buffered at a time by [`ConstraintStream`].
+const STREAM_CHUNK: usize = 1 << 20;
+
+/// Reads the constraints section one constraint at a time, holding at most a
+/// [`STREAM_CHUNK`]-sized window of the file in memory.
+pub struct ConstraintStream<'a> {
+    fd: &'a mut BinFile,
+    section: Section,
+    n8: usize,
+    remaining: u32,
+    buf: Vec<u8>,
+    cursor: usize,
+}
+
+impl ConstraintStream<'_> {
+    /// Returns the next constraint, or `None` once all `n_constraints` were read.
+    pub async fn next(&mut self) -> Result<Option<Constraint>> {
+        if self.remaining == 0 {
+            let section_end = self.section.offset + self.section.size;
+            let unread = (self.buf.len() - self.cursor) as u64 + (section_end - self.fd.pos);
+            if unread != 0 {
+                return Err(SnarkError::SectionSizeMismatch {
+                    section: R1CS_FILE_CONSTRAINTS_SECTION,
+                    expected: self.section.size,
+                    got: self.section.size - unread,
+                });
+            }
+            return Ok(None);
+        }
+
+        let mut triple: Constraint = Default::default();
+        for lc in &mut triple {
+            let n_idx = self.read_u32().await?;
+            for _ in 0..n_idx {
+                let idx = self.read_u32().await?;
+                let order = self.fd.byte_order;
+                let coeff = order.biguint(self.take(self.n8).await?);
+                lc.insert(idx, coeff);
+            }
+        }
+        self.remaining -= 1;
+        Ok(Some(triple))
+    }
+
+    async fn read_u32(&mut self) -> Result<u32> {
+        let order = self.fd.byte_order;
+        Ok(order.u32(self.take(4).await?))
+    }
+
+    /// Returns the next `n` bytes of the section, refilling the window from the file.
+    async fn take(&mut self, n: usize) -> Result<&[u8]> {
+        if self.buf.len() - self.cursor < n {
+            self.buf.drain(..self.cursor);
+            self.cursor = 0;
+
+            let section_end = self.section.offset + self.section.size;
+            let available = section_end - self.fd.pos;
+            let missing = (n - self.buf.len()) as u64;
+            if available < missing {
+                return Err(SnarkError::SectionOutOfBounds {
+                    section: R1CS_FILE_CONSTRAINTS_SECTION,
+                    offset: self.fd.pos - self.buf.len() as u64 - self.section.offset,
+                    length: n as u64,
+                    size: self.section.size,
+                });
+            }
+            let len = available.min(missing.max(STREAM_CHUNK as u64));
+            let bytes = self.fd.read_bytes(len as usize).await?;
+            self.buf.extend_from_slice(&bytes);
+        }
+
+        let start = self.cursor;
+        self.cursor += n;
+        Ok(&self.buf[start..self.cursor])
+    }
+}
+
+/// Streaming counterpart of [`read_constraints`]: constraints are decoded lazily as the
+/// returned [`ConstraintStream`] is advanced.
+pub async fn read_constraints_streaming<'a>(
+    fd: &'a mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    r1cs: &R1csHeader,
+) -> Result<ConstraintStream<'a>> {
+    let section = get_unique_section(sections, R1CS_FILE_CONSTRAINTS_SECTION)?.clone();
+
+    fd.file.seek(SeekFrom::Start(section.offset)).await?;
+    fd.pos = section.offset;
+
+    Ok(ConstraintStream {
+        fd,
+        section,
+        n8: r1cs.n8 as usize,
+        remaining: r1cs.n_constraints,
+        buf: Vec::new(),
+        cursor: 0,
+    })
+}
+
+/// Cursor over an in-memory section body that reports short reads as
+/// [`SnarkError::SectionOutOfBounds`].
+pub(crate) struct SectionCursor<'a> {
+    section: u32,
+    buf: &'a [u8],
+    pos: usize,
+    order: ByteOrder,
+}
+
+impl<'a> SectionCursor<'a> {
+    pub(crate) fn new(section: u32, buf: &'a [u8]) -> Self {
+        Self {
+            section,
+            buf,
+            pos: 0,
+            order: ByteOrder::default(),
+        }
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.buf.len() - self.pos < n {
+            return Err(SnarkError::SectionOutOfBounds {
+                section: self.section,
+                offset: self.pos as u64,
+                length: n as u64,
+                size: self.buf.len() as u64,
+            });
+        }
+        let start = self.pos;
+        self.pos += n;
+        Ok(&self.buf[start..self.pos])
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(self.order.u32(self.take(4)?))
+    }
+
+    pub(crate) fn read_biguint(&mut self, n8: usize) -> Result<BigUint> {
+        Ok(self.order.biguint(self.take(n8)?))
+    }
+
+    pub(crate) fn read_field(&mut self, n8: usize) -> Result<Element<Bn128>> {
+        self.order.field(self.take(n8)?)
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64> {
+        Ok(self.order.u64(self.take(8)?))
+    }
+
+    /// Reads a null-terminated string, consuming the terminator.
+    pub(crate) fn read_string(&mut self) -> Result<String> {
+        let rest = &self.buf[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(SnarkError::SectionOutOfBounds {
+                section: self.section,
+                offset: self.pos as u64,
+                length: rest.len() as u64 + 1,
+                size: self.buf.len() as u64,
+            })?;
+        let s = String::from_utf8_lossy(&rest[..len]).into_owned();
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    pub(crate) fn finish(self) -> Result<()> {
+        if self.pos != self.buf.len() {
+            return Err(SnarkError::SectionSizeMismatch {
+                section: self.section,
+                expected: self.buf.len() as u64,
+                got: self.pos as u64,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Reads the custom gates list (section 4): each gate's template name followed by its
+/// `n8`-byte little-endian parameters.
+pub async fn read_custom_gates_list(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    r1cs: &R1csHeader,
+) -> Result<Vec<CustomGate>> {
+    let buf = read_section(
+        fd,
+        sections,
+        R1CS_FILE_CUSTOM_GATES_LIST_SECTION,
+        None,
+        None,
+    )
+    .await?;
+    let mut cursor = SectionCursor::new(R1CS_FILE_CUSTOM_GATES_LIST_SECTION, &buf);
+
+    let n_gates = cursor.read_u32()?;
+    let mut gates = Vec::with_capacity(n_gates as usize);
+    for _ in 0..n_gates {
+        let template_name = cursor.read_string()?;
+        let n_parameters = cursor.read_u32()?;
+        let parameters = (0..n_parameters)
+            .map(|_| cursor.read_biguint(r1cs.n8 as usize))
+            .collect::<Result<Vec<_>>>()?;
+        gates.push(CustomGate {
+            template_name,
+            parameters,
+        });
+    }
+    cursor.finish()?;
+
+    Ok(gates)
+}
+
+/// Reads the custom gates uses (section 5): which gate each use instantiates and the
+/// signals wired into it.
+pub async fn read_custom_gates_uses(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+) -> Result<Vec<CustomGateUse>> {
+    let buf = read_section(
+        fd,
+        sections,
+        R1CS_FILE_CUSTOM_GATES_USES_SECTION,
+        None,
+        None,
+    )
+    .await?;
+    let mut cursor = SectionCursor::new(R1CS_FILE_CUSTOM_GATES_USES_SECTION, &buf);
+
+    let n_uses = cursor.read_u32()?;
+    let mut uses = Vec::with_capacity(n_uses as usize);
+    for _ in 0..n_uses {
+        let id = cursor.read_u32()?;
+        let n_signals = cursor.read_u32()?;
+        let signals = (0..n_signals)
+            .map(|_| cursor.read_u64())
+            .collect::<Result<Vec<_>>>()?;
+        uses.push(CustomGateUse { id, signals });
+    }
+    cursor.finish()?;
+
+    Ok(uses)
+}
+
+/// Reads the wire-to-label map (section 3): the label id of each wire, indexed by wire.
+///
+/// The r1cs format stores one `u64` per wire, so the section must be `n_vars * 8` bytes;
+/// `n_labels` only bounds the ids, since optimized-away signals keep their labels.
+pub async fn read_wire_to_label(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    r1cs: &R1csHeader,
+) -> Result<Vec<u64>> {
+    let buf = read_section(fd, sections, R1CS_FILE_WIRE2LABELID_SECTION, None, None).await?;
+    let expected = r1cs.n_vars as u64 * 8;
+    if buf.len() as u64 != expected {
+        return Err(SnarkError::SectionSizeMismatch {
+            section: R1CS_FILE_WIRE2LABELID_SECTION,
+            expected,
+            got: buf.len() as u64,
+        });
+    }
+
+    let mut cursor = SectionCursor::new(R1CS_FILE_WIRE2LABELID_SECTION, &buf);
+    (0..r1cs.n_vars).map(|_| cursor.read_u64()).collect()
+}
+
 pub async fn read_r1cs_fd(fd: &mut BinFile, sections: &HashMap<u32, Vec<Section>>) -> Result<R1cs> {
     let header = read_r1cs_header(fd, sections).await?;
     let constraints = read_constraints(fd, sections, &header).await?;
+    let (custom_gates, custom_gates_uses) = if header.use_custom_gates {
+        (
+            read_custom_gates_list(fd, sections, &header).await?,
+            read_custom_gates_uses(fd, sections).await?,
+        )
+    } else {
+        (Vec::new(), Vec::new())
+    };
     Ok(R1cs {
         header,
         constraints,
+        custom_gates,
+        custom_gates_uses,
     })
 }
 
@@ -369,17 +1245,11 @@ pub async fn read_r1cs_fd(fd: &mut BinFile, sections: &HashMap<u32, Vec<Section>
 mod tests {
     use super::*;
     use std::fs::OpenOptions;
-    use std::io::Seek;
-    use std::io::SeekFrom;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
     #[tokio::test]
     async fn test_read_bin_file_basic() -> Result<()> {
-        // Create temporary file
-        let tmp = NamedTempFile::new()?;
-        let mut file = OpenOptions::new().write(true).read(true).open(tmp.path())?;
-
         // Build binary structure
         let mut contents = vec![];
 
@@ -402,13 +1272,8 @@ mod tests {
         contents.extend(&4u64.to_le_bytes());
         contents.extend(&[0xbb; 4]); // Dummy section data
 
-        // Write to file
-        file.write_all(&contents)?;
-        file.sync_all()?;
-        file.seek(SeekFrom::Start(0))?;
-
-        // Now test read_bin_file
-        let (_bin_file, sections) = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 2).await?;
+        let (mut bin_file, sections) =
+            read_bin_file_fd(BinFile::from_bytes(contents), "ptau", 2).await?;
 
         assert_eq!(sections.len(), 2);
         assert!(sections.contains_key(&3));
@@ -419,7 +1284,270 @@ mod tests {
 
         let sec12 = &sections[&12][0];
         assert_eq!(sec12.size, 4);
+        assert_eq!(read_section(&mut bin_file, &sections, 12, None, None).await?, [0xbb; 4]);
+
+        Ok(())
+    }
+
+    // Only uses APIs that are also available on wasm32, where there is no filesystem.
+    #[tokio::test]
+    async fn test_in_memory_round_trip() -> Result<()> {
+        let mut fd = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 1).await?;
+        fd.start_write_section(3).await?;
+        fd.write_u32(0xdeadbeef).await?;
+        fd.write_bytes(b"snark").await?;
+        fd.end_write_section().await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = read_bin_file_fd(fd, "zkey", 1).await?;
+        assert_eq!(sections[&3][0].offset, 24);
+        let body = read_section(&mut fd, &sections, 3, None, None).await?;
+        assert_eq!(body[..4], 0xdeadbeefu32.to_le_bytes());
+        assert_eq!(&body[4..], b"snark");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_sections_parallel() -> Result<()> {
+        let path = "src/artifacts/pot8.ptau";
+        let (mut fd, sections) = read_bin_file(path, "ptau", 2).await?;
+        let ids: Vec<u32> = sections.keys().copied().collect();
+
+        let parallel = read_sections_parallel(path, &sections, &ids).await?;
+        assert_eq!(parallel.len(), ids.len());
+        for id in ids {
+            assert_eq!(parallel[&id], read_section(&mut fd, &sections, id, None, None).await?);
+        }
+
+        let result = read_sections_parallel(path, &sections, &[2, 99]).await;
+        assert!(matches!(result, Err(SnarkError::SectionMissing(99))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_big_buffer() -> Result<()> {
+        let bytes: Vec<u8> = (0..50).collect();
+        let mut buffer = BigBuffer::with_page_size(bytes.len(), 7);
+        buffer.set(&bytes, 0);
+
+        let mut fd = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 1).await?;
+        fd.start_write_section(1).await?;
+        fd.write_big_buffer(&buffer).await?;
+        fd.end_write_section().await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = read_bin_file_fd(fd, "zkey", 1).await?;
+        assert_eq!(read_section(&mut fd, &sections, 1, None, None).await?, bytes);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_field_round_trip() -> Result<()> {
+        let values = [Element::<Bn128>::zero(), Element::one(), -Element::<Bn128>::from(7u64)];
+        let mut fd = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 1).await?;
+        fd.start_write_section(1).await?;
+        for x in &values {
+            fd.write_field(x, 32).await?;
+        }
+        fd.end_write_section().await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = read_bin_file_fd(fd, "zkey", 1).await?;
+        assert_eq!(sections[&1][0].size, 3 * 32);
+        fd.file.seek(SeekFrom::Start(sections[&1][0].offset)).await?;
+        for x in &values {
+            assert_eq!(&fd.read_field(32).await?, x);
+        }
+        // One is stored as R mod r, not as the integer 1.
+        fd.file.seek(SeekFrom::Start(sections[&1][0].offset + 32)).await?;
+        assert_ne!(fd.read_biguint(32).await?, BigUint::from(1u32));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_records() -> Result<()> {
+        let mut fd = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 2).await?;
+        fd.start_write_section(1).await?;
+        for v in [3u32, 1, 4, 1, 5] {
+            fd.write_u32(v).await?;
+        }
+        fd.end_write_section().await?;
+        fd.start_write_section(2).await?;
+        fd.write_bytes(&[0; 6]).await?;
+        fd.end_write_section().await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = read_bin_file_fd(fd, "zkey", 1).await?;
+        let decode = |r: &[u8]| Ok(u32::from_le_bytes(r.try_into().unwrap()));
+        let records = read_records(&mut fd, &sections, 1, 4, decode).await?;
+        assert_eq!(records, [3, 1, 4, 1, 5]);
+
+        let result = read_records(&mut fd, &sections, 2, 4, decode).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionRecordMisaligned { section: 2, size: 6, record_len: 4 })
+        ));
+        let result = read_records(&mut fd, &sections, 1, 0, decode).await;
+        assert!(matches!(result, Err(SnarkError::ZeroSize("record length"))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_past_end_reports_position() -> Result<()> {
+        let mut fd = BinFile::from_bytes(b"zkey\x01\x00".to_vec());
+        fd.read_u32().await?;
+        let err = fd.read_u32().await.unwrap_err();
+        assert!(matches!(
+            err,
+            SnarkError::UnexpectedEof { pos: 4, requested: 4, available: 2 }
+        ));
+        assert!(err.to_string().contains("at byte 4"));
+        // `pos` still points at the start of the failed read.
+        assert_eq!(fd.pos, 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_sections() -> Result<()> {
+        let mut fd = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 3).await?;
+        let second = fd.reserve_section(2, 8).await?;
+        fd.start_write_section(3).await?;
+        fd.write_bytes(b"third").await?;
+        fd.end_write_section().await?;
+        let first = fd.reserve_section(1, 4).await?;
+        assert!(matches!(fd.flush().await, Err(SnarkError::InvalidWriteState(_))));
+
+        fd.start_fill_section(first).await?;
+        fd.write_u32(1).await?;
+        fd.end_fill_section().await?;
+        fd.start_fill_section(second).await?;
+        fd.write_u32(2).await?;
+        assert!(matches!(
+            fd.end_fill_section().await,
+            Err(SnarkError::SectionSizeMismatch { section: 2, expected: 8, got: 4 })
+        ));
+
+        let mut fd = BinFile::create_in(Cursor::new(Vec::new()), "zkey", 1, 3).await?;
+        let second = fd.reserve_section(2, 8).await?;
+        fd.start_write_section(3).await?;
+        fd.write_bytes(b"third").await?;
+        fd.end_write_section().await?;
+        let first = fd.reserve_section(1, 4).await?;
+        fd.start_fill_section(first).await?;
+        fd.write_u32(1).await?;
+        fd.end_fill_section().await?;
+        fd.start_fill_section(second).await?;
+        fd.write_u64(2).await?;
+        fd.end_fill_section().await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = read_bin_file_fd(fd, "zkey", 1).await?;
+        assert_eq!(read_section(&mut fd, &sections, 1, None, None).await?, 1u32.to_le_bytes());
+        assert_eq!(read_section(&mut fd, &sections, 2, None, None).await?, 2u64.to_le_bytes());
+        assert_eq!(read_section(&mut fd, &sections, 3, None, None).await?, b"third");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_section_exceeding_file() -> Result<()> {
+        let mut contents = b"ptau".to_vec();
+        contents.extend(1u32.to_le_bytes());
+        contents.extend(2u32.to_le_bytes());
+        contents.extend(1u32.to_le_bytes());
+        contents.extend(4u64.to_le_bytes());
+        contents.extend([0xaa; 4]);
+        // Declares 100 bytes but only 4 follow.
+        contents.extend(2u32.to_le_bytes());
+        contents.extend(100u64.to_le_bytes());
+        contents.extend([0xbb; 4]);
+
+        let tmp = NamedTempFile::new()?;
+        std::fs::write(tmp.path(), &contents)?;
+        let result = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 1).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionExceedsFile { section: 2, offset: 40, size: 100, file_size: 44 })
+        ));
+
+        // A length near u64::MAX must not wrap around past the offset.
+        for size in [u64::MAX, u64::MAX - 39] {
+            contents[32..40].copy_from_slice(&size.to_le_bytes());
+            std::fs::write(tmp.path(), &contents)?;
+            let result = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 1).await;
+            assert!(matches!(
+                result,
+                Err(SnarkError::SectionExceedsFile { section: 2, offset: 40, size: s, file_size: 44 })
+                    if s == size
+            ));
+        }
+
+        contents.truncate(28);
+        contents[8..12].copy_from_slice(&1u32.to_le_bytes());
+        contents.extend([0xcc; 2]);
+        std::fs::write(tmp.path(), &contents)?;
+        let result = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 1).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::TrailingBytes { pos: 28, file_size: 30 })
+        ));
+        Ok(())
+    }
 
+    #[tokio::test]
+    async fn test_read_r1cs_header_stops_before_constraints() -> Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+
+        let header_section = &sections[&R1CS_FILE_HEADER_SECTION][0];
+        assert_eq!(fd.pos, header_section.offset + header_section.size);
+        let constraints_section = &sections[&R1CS_FILE_CONSTRAINTS_SECTION][0];
+        assert!(fd.pos <= constraints_section.offset);
+
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+        assert_eq!(header.min_plonk_gates(), r1cs.constraints.len() + 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sections_sorted() -> Result<()> {
+        let (_, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let sorted = sections_sorted(&sections);
+        assert_eq!(sorted.len(), sections.values().map(Vec::len).sum::<usize>());
+        assert_eq!(sorted[0], (1, &sections[&1][0]));
+        // Offsets increase strictly and each section starts after the previous one ends.
+        for pair in sorted.windows(2) {
+            let ((_, a), (_, b)) = (pair[0], pair[1]);
+            assert!(a.offset + a.size < b.offset);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_describe_file() -> Result<()> {
+        let description = describe_file("src/artifacts/pot8.ptau", "ptau").await?;
+        assert_eq!(description[0].id, 1);
+        assert_eq!(description[0].name, "header");
+        assert!(description.windows(2).all(|w| w[0].offset < w[1].offset));
+
+        let (_, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        assert_eq!(description.len(), sections.len());
+        let lagrange = description.iter().find(|info| info.id == 12).unwrap();
+        assert_eq!(lagrange.name, "lagrangeTauG1");
+        assert_eq!(lagrange.size, sections[&12][0].size);
+
+        let description = describe_file("src/artifacts/small.r1cs", "r1cs").await?;
+        assert!(description.iter().any(|info| info.name == "constraints"));
+        assert_eq!(section_name("r1cs", 12), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_section_offset_overflow() -> Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let result = read_section(&mut fd, &sections, 1, Some(u64::MAX), Some(2)).await;
+        assert!(matches!(result, Err(SnarkError::SectionOutOfBounds { section: 1, .. })));
+        let result = read_section(&mut fd, &sections, 1, Some(u64::MAX), None).await;
+        assert!(matches!(result, Err(SnarkError::SectionOutOfBounds { section: 1, .. })));
         Ok(())
     }
 
@@ -438,7 +1566,7 @@ mod tests {
         file.sync_all().unwrap();
 
         let result = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 2).await;
-        assert!(result.is_err());
+        assert!(matches!(result, Err(SnarkError::InvalidMagic { .. })));
     }
 
     #[tokio::test]
@@ -456,7 +1584,322 @@ mod tests {
         file.sync_all().unwrap();
 
         let result = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 1).await;
-        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(SnarkError::UnsupportedVersion { version: 999, max: 1 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_missing_section() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        let mut file = OpenOptions::new().write(true).open(tmp.path())?;
+
+        file.write_all(b"r1cs")?;
+        file.write_all(&1u32.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?; // no sections
+        file.sync_all()?;
+
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let result = read_r1cs_header(&mut fd, &sections).await;
+        assert!(matches!(result, Err(SnarkError::SectionMissing(1))));
+
+        let result = read_section(&mut fd, &sections, 2, None, None).await;
+        assert!(matches!(result, Err(SnarkError::SectionMissing(2))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_r1cs_header_size_mismatch() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        let mut file = OpenOptions::new().write(true).open(tmp.path())?;
+
+        // A valid header is 4 + n8 + 4 * 4 + 8 + 4 bytes; declare 4 extra trailing bytes.
+        let n8 = 32u32;
+        let header_len = 4 + n8 as u64 + 16 + 8 + 4 + 4;
+
+        file.write_all(b"r1cs")?;
+        file.write_all(&1u32.to_le_bytes())?;
+        file.write_all(&1u32.to_le_bytes())?;
+        file.write_all(&1u32.to_le_bytes())?;
+        file.write_all(&header_len.to_le_bytes())?;
+        file.write_all(&n8.to_le_bytes())?;
+        file.write_all(&[0u8; 32])?;
+        file.write_all(&[0u8; 16])?;
+        file.write_all(&0u64.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(&[0u8; 4])?;
+        file.sync_all()?;
+
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let result = read_r1cs_header(&mut fd, &sections).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionSizeMismatch {
+                section: 1,
+                expected,
+                got,
+            }) if expected == header_len && got == header_len - 4
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncated_section_read() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        let mut file = OpenOptions::new().write(true).open(tmp.path())?;
+
+        file.write_all(b"ptau")?;
+        file.write_all(&1u32.to_le_bytes())?;
+        file.write_all(&1u32.to_le_bytes())?;
+        file.write_all(&2u32.to_le_bytes())?;
+        file.write_all(&8u64.to_le_bytes())?;
+        file.write_all(&[0xaa; 8])?;
+        file.sync_all()?;
+
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 1).await?;
+        let result = read_section(&mut fd, &sections, 2, Some(4), Some(8)).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionOutOfBounds {
+                section: 2,
+                offset: 4,
+                length: 8,
+                size: 8,
+            })
+        ));
+        Ok(())
+    }
+
+    /// Serializes an r1cs file with an empty circuit over `n_vars` signals plus `extra`.
+    fn r1cs_with_sections(n_vars: u32, extra: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut header = 32u32.to_le_bytes().to_vec();
+        header.extend(&[0u8; 32]);
+        header.extend(&n_vars.to_le_bytes());
+        header.extend(&[0u8; 12]);
+        header.extend(&(n_vars as u64).to_le_bytes());
+        header.extend(&0u32.to_le_bytes());
+
+        let mut sections = vec![(1, header), (2, Vec::new())];
+        sections.extend_from_slice(extra);
+
+        let mut contents = b"r1cs".to_vec();
+        contents.extend(&1u32.to_le_bytes());
+        contents.extend(&(sections.len() as u32).to_le_bytes());
+        for (id, data) in &sections {
+            contents.extend(&id.to_le_bytes());
+            contents.extend(&(data.len() as u64).to_le_bytes());
+            contents.extend(data);
+        }
+        contents
+    }
+
+    #[test]
+    fn test_r1cs_header_json_round_trip() -> Result<()> {
+        let header = R1csHeader {
+            n8: 32,
+            prime: Curve::r(),
+            n_vars: 10,
+            n_outputs: 1,
+            n_pub_inputs: 2,
+            n_prv_inputs: 3,
+            n_labels: 1 << 40,
+            n_constraints: 7,
+            use_custom_gates: true,
+        };
+        let text = header.to_json().to_string_pretty();
+        assert_eq!(R1csHeader::from_json(&JsonValue::parse(&text)?)?, header);
+
+        let section = Section { offset: 12, size: 1 << 33 };
+        assert_eq!(Section::from_json(&JsonValue::parse(&section.to_json().to_string())?)?, section);
+
+        let bad = JsonValue::parse(r#"{"n8": 32, "prime": "0x12"}"#)?;
+        assert!(matches!(R1csHeader::from_json(&bad), Err(SnarkError::InvalidJson(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_field() {
+        let curve = Curve::new();
+        let mut header = R1csHeader {
+            n8: 32,
+            prime: curve.r.clone(),
+            n_vars: 1,
+            n_outputs: 0,
+            n_pub_inputs: 0,
+            n_prv_inputs: 0,
+            n_labels: 1,
+            n_constraints: 0,
+            use_custom_gates: false,
+        };
+        assert!(header.check_field(&curve).is_ok());
+
+        // Same prime, padded to a wider encoding than the curve's scalars.
+        header.n8 = 48;
+        assert!(matches!(
+            header.check_field(&curve),
+            Err(SnarkError::InvalidFieldSize { expected: 32, got: 48 })
+        ));
+
+        header.prime = curve.q.clone();
+        assert!(matches!(header.check_field(&curve), Err(SnarkError::PrimeMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_validate_public_count() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        let mut contents = r1cs_with_sections(4, &[]);
+        // nOutputs, nPubInputs, nPrvInputs follow n8, the prime and nVars.
+        let counts = 24 + 4 + 32 + 4;
+        contents[counts..counts + 12].copy_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        std::fs::write(tmp.path(), &contents)?;
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        assert_eq!(header.validate_public_count()?, 2);
+
+        // Three signals plus the one wire no longer fit in three variables.
+        contents[24 + 4 + 32] = 3;
+        std::fs::write(tmp.path(), &contents)?;
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let result = read_r1cs_header(&mut fd, &sections).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SignalCountMismatch { n_vars: 3, n_signals: 3 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_duplicated_constraints_section() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        std::fs::write(tmp.path(), r1cs_with_sections(1, &[(2, Vec::new())]))?;
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        assert!(get_unique_section(&sections, 1).is_ok());
+        assert!(matches!(get_unique_section(&sections, 9), Err(SnarkError::SectionMissing(9))));
+
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        let result = read_constraints(&mut fd, &sections, &header).await;
+        assert!(matches!(result, Err(SnarkError::SectionDuplicated(2))));
+        let result = read_constraints_streaming(&mut fd, &sections, &header).await;
+        assert!(matches!(result, Err(SnarkError::SectionDuplicated(2))));
+        let result = read_section(&mut fd, &sections, 2, None, None).await;
+        assert!(matches!(result, Err(SnarkError::SectionDuplicated(2))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_custom_gates() -> Result<()> {
+        let mut list = 2u32.to_le_bytes().to_vec();
+        list.extend(b"CMul\0");
+        list.extend(&0u32.to_le_bytes());
+        list.extend(b"Pow\0");
+        list.extend(&1u32.to_le_bytes());
+        let mut param = [0u8; 32];
+        param[0] = 5;
+        list.extend(&param);
+
+        let mut uses = 2u32.to_le_bytes().to_vec();
+        for (id, signals) in [(1u32, &[1u64, 2][..]), (0, &[3, 1 << 40, 4])] {
+            uses.extend(&id.to_le_bytes());
+            uses.extend(&(signals.len() as u32).to_le_bytes());
+            for s in signals {
+                uses.extend(&s.to_le_bytes());
+            }
+        }
+
+        let tmp = NamedTempFile::new()?;
+        std::fs::write(tmp.path(), r1cs_with_sections(5, &[(4, list), (5, uses)]))?;
+
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+
+        assert!(r1cs.header.use_custom_gates);
+        assert_eq!(
+            r1cs.custom_gates,
+            vec![
+                CustomGate {
+                    template_name: "CMul".to_string(),
+                    parameters: vec![],
+                },
+                CustomGate {
+                    template_name: "Pow".to_string(),
+                    parameters: vec![BigUint::from(5u32)],
+                },
+            ]
+        );
+        assert_eq!(
+            r1cs.custom_gates_uses,
+            vec![
+                CustomGateUse {
+                    id: 1,
+                    signals: vec![1, 2],
+                },
+                CustomGateUse {
+                    id: 0,
+                    signals: vec![3, 1 << 40, 4],
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_wire_to_label() -> Result<()> {
+        let labels = [0u64, 1, 4, 7, 9];
+        let map: Vec<u8> = labels.iter().flat_map(|l| l.to_le_bytes()).collect();
+
+        let tmp = NamedTempFile::new()?;
+        std::fs::write(tmp.path(), r1cs_with_sections(5, &[(3, map.clone())]))?;
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        assert_eq!(read_wire_to_label(&mut fd, &sections, &header).await?, labels);
+
+        // One entry short.
+        std::fs::write(tmp.path(), r1cs_with_sections(5, &[(3, map[..32].to_vec())]))?;
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        let result = read_wire_to_label(&mut fd, &sections, &header).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionSizeMismatch { section: 3, expected: 40, got: 32 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_custom_gates_truncated() -> Result<()> {
+        // The template name is never terminated.
+        let mut list = 1u32.to_le_bytes().to_vec();
+        list.extend(b"CMul");
+        let uses = 0u32.to_le_bytes().to_vec();
+
+        let tmp = NamedTempFile::new()?;
+        std::fs::write(tmp.path(), r1cs_with_sections(5, &[(4, list), (5, uses)]))?;
+
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let result = read_r1cs_fd(&mut fd, &sections).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionOutOfBounds { section: 4, .. })
+        ));
+
+        // A complete gate followed by stray bytes: the section must be fully consumed.
+        let mut list = 1u32.to_le_bytes().to_vec();
+        list.extend(b"CMul\0");
+        list.extend(&0u32.to_le_bytes());
+        list.extend(&[0xff; 3]);
+        std::fs::write(
+            tmp.path(),
+            r1cs_with_sections(5, &[(4, list), (5, 0u32.to_le_bytes().to_vec())]),
+        )?;
+
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "r1cs", 1).await?;
+        let result = read_r1cs_fd(&mut fd, &sections).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionSizeMismatch { section: 4, expected: 16, got: 13 })
+        ));
+        Ok(())
     }
 
     #[tokio::test]
@@ -480,15 +1923,55 @@ mod tests {
         }
 
         // Optional: print some info to debug
-        for (id, sects) in &sections {
-            for s in sects {
-                println!("Section {} -> offset: {}, size: {}", id, s.offset, s.size);
-            }
+        for (id, s) in sections_sorted(&sections) {
+            println!("Section {} -> offset: {}, size: {}", id, s.offset, s.size);
         }
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_read_constraints_streaming_matches_eager() -> Result<()> {
+        let path = "src/artifacts/small.r1cs";
+
+        let (mut fd, sections) = read_bin_file(path, "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        let expected = read_constraints(&mut fd, &sections, &header).await?;
+
+        let mut stream = read_constraints_streaming(&mut fd, &sections, &header).await?;
+        let mut streamed = Vec::new();
+        while let Some(constraint) = stream.next().await? {
+            streamed.push(constraint);
+        }
+
+        assert_eq!(streamed.len(), header.n_constraints as usize);
+        assert_eq!(streamed, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_constraints_streaming_truncated() -> Result<()> {
+        let path = "src/artifacts/small.r1cs";
+
+        let (mut fd, mut sections) = read_bin_file(path, "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        sections.get_mut(&2).unwrap()[0].size -= 1;
+
+        let mut stream = read_constraints_streaming(&mut fd, &sections, &header).await?;
+        let mut result = Ok(None);
+        for _ in 0..=header.n_constraints {
+            result = stream.next().await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionOutOfBounds { section: 2, .. })
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore] // Heavy test, run only on demand.
     async fn test_read_constraints_basic() -> Result<()> {
@@ -511,4 +1994,98 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_read_constraints_as_field() -> Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        let expected = read_constraints(&mut fd, &sections, &header).await?;
+        let constraints = read_constraints_as_field::<Bn128>(&mut fd, &sections, &header).await?;
+
+        assert_eq!(constraints.len(), expected.len());
+        for (constraint, expected) in constraints.iter().zip(&expected) {
+            for (lc, expected) in constraint.iter().zip(expected) {
+                assert_eq!(lc.len(), expected.len());
+                for (idx, value) in expected {
+                    assert_eq!(lc[idx], Element::from(value.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_byte_order() -> Result<()> {
+        // Little-endian is the default, and spelling it out reads the same header.
+        let (fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        assert_eq!(fd.byte_order(), ByteOrder::LittleEndian);
+        let mut fd = fd.with_byte_order(ByteOrder::LittleEndian);
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        let (mut fd, _) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        assert_eq!(read_r1cs_header(&mut fd, &sections).await?, header);
+
+        let x = Element::<Bn128>::from(7u64);
+        let mut fd = BinFile::from_bytes(Vec::new()).with_byte_order(ByteOrder::BigEndian);
+        fd.write_u32(0x01020304).await?;
+        fd.write_u64(5).await?;
+        fd.write_field(&x, 32).await?;
+        fd.flush().await?;
+        let bytes = fd.into_storage::<Cursor<Vec<u8>>>().unwrap().into_inner();
+        assert_eq!(bytes[..4], [1, 2, 3, 4]);
+        assert_eq!(bytes[4..12], [0, 0, 0, 0, 0, 0, 0, 5]);
+        let mut le = ByteOrder::LittleEndian.field_bytes(&x, 32);
+        le.reverse();
+        assert_eq!(bytes[12..], le);
+
+        let mut fd = BinFile::from_bytes(bytes).with_byte_order(ByteOrder::BigEndian);
+        assert_eq!(fd.read_u32().await?, 0x01020304);
+        assert_eq!(fd.read_u64().await?, 5);
+        assert_eq!(fd.read_field(32).await?, x);
+
+        // The preamble and section headers follow the order too.
+        let storage = Cursor::new(Vec::new());
+        let mut fd =
+            BinFile::create_in_with_byte_order(storage, "zkey", 1, 1, ByteOrder::BigEndian).await?;
+        fd.start_write_section(1).await?;
+        fd.write_u32(0x01020304).await?;
+        fd.end_write_section().await?;
+        fd.flush().await?;
+        let bytes = fd.into_storage::<Cursor<Vec<u8>>>().unwrap().into_inner();
+        assert_eq!(bytes[4..8], [0, 0, 0, 1]);
+        assert_eq!(bytes[8..12], [0, 0, 0, 1]);
+        assert_eq!(bytes[16..24], [0, 0, 0, 0, 0, 0, 0, 4]);
+
+        let fd = BinFile::from_bytes(bytes).with_byte_order(ByteOrder::BigEndian);
+        let (mut fd, sections) = read_bin_file_fd(fd, "zkey", 1).await?;
+        assert_eq!(sections[&1], [Section { offset: 24, size: 4 }]);
+        assert_eq!(read_section(&mut fd, &sections, 1, None, None).await?, [1, 2, 3, 4]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_checks_version() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let version = current_version("zkey").unwrap();
+        assert_eq!(version, 1);
+        let mut fd = BinFile::create(path, "zkey", version, 1).await?;
+        fd.start_write_section(1).await?;
+        fd.write_u32(2).await?;
+        fd.end_write_section().await?;
+        fd.flush().await?;
+        let (_, sections) = read_bin_file(path, "zkey", version).await?;
+        assert_eq!(sections[&1][0].size, 4);
+
+        for (file_type, version, max) in [("zkey", 2, 1), ("zkey", 0, 1), ("r1cs", 2, 1), ("ptau", 2, 1)] {
+            let result = BinFile::create_in(Cursor::new(Vec::new()), file_type, version, 1).await;
+            assert!(matches!(
+                result,
+                Err(SnarkError::UnsupportedVersion { version: v, max: m }) if v == version && m == max
+            ));
+        }
+        BinFile::create_in(Cursor::new(Vec::new()), "wtns", 2, 1).await?;
+        // Types outside the table, like checkpoints, take any version.
+        BinFile::create_in(Cursor::new(Vec::new()), "pchk", 7, 1).await?;
+        Ok(())
+    }
 }
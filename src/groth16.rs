@@ -0,0 +1,229 @@
+use crate::error::{Result, SnarkError};
+use crate::file::{self, BinFile, R1csHeader, Section};
+use crate::plonk::to_n8r_bytes;
+use crate::ptau_file::{PTAU_BETA_G2_SECTION, PtauHeader};
+use crate::zkey::{ZkeyProtocol, write_zkey_protocol};
+use ::r1cs::num::BigUint;
+use std::collections::HashMap;
+
+/// Protocol id stored in section 1 of a Groth16 zkey.
+pub const ZKEY_PROTOCOL_GROTH16: u32 = 1;
+
+pub const ZKEY_GROTH16_HEADER_SECTION: u32 = 2;
+
+/// Number of sections in a Groth16 zkey: header, protocol header, IC, coefficients,
+/// A, B1, B2, C, H and contributions.
+pub const ZKEY_GROTH16_N_SECTIONS: u32 = 10;
+
+/// Contents of the Groth16 zkey header (section 2), in the order snarkjs serializes
+/// them. Points are uncompressed affine coordinates in Montgomery form, as in the ptau.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Groth16Header {
+    pub n8q: u32,
+    pub q: BigUint,
+    pub n8r: u32,
+    pub r: BigUint,
+    pub n_vars: u32,
+    pub n_public: u32,
+    pub domain_size: u32,
+    pub alpha1: Vec<u8>,
+    pub beta1: Vec<u8>,
+    pub beta2: Vec<u8>,
+    pub gamma2: Vec<u8>,
+    pub delta1: Vec<u8>,
+    pub delta2: Vec<u8>,
+}
+
+/// Groth16 domain size for a circuit: the smallest power of two strictly greater than
+/// the constraints plus one `x * 1 = x` constraint per public signal.
+pub fn groth16_domain_size(header: &R1csHeader) -> u64 {
+    let n = header.n_constraints as u64 + header.n_pub_inputs as u64 + header.n_outputs as u64;
+    (n + 1).next_power_of_two()
+}
+
+/// Builds the header of a fresh (phase-2 initial) Groth16 zkey for `r1cs` from a
+/// ptau: alpha and beta come from the ceremony, while gamma and delta start as the
+/// generators, as in snarkjs `groth16 setup`.
+pub async fn groth16_header(
+    fd_ptau: &mut BinFile,
+    sections_ptau: &HashMap<u32, Vec<Section>>,
+    ptau_header: &PtauHeader,
+    r1cs: &R1csHeader,
+) -> Result<Groth16Header> {
+    let curve = &ptau_header.curve;
+    r1cs.check_field(curve)?;
+
+    let domain_size = groth16_domain_size(r1cs);
+    let power = domain_size.trailing_zeros();
+    if power > ptau_header.power {
+        return Err(SnarkError::CircuitTooBig {
+            cir_power: power,
+            power: ptau_header.power,
+            n_constraints: r1cs.n_constraints as usize,
+        });
+    }
+
+    let g1_size = 2 * curve.n8q as u64;
+    let g2_size = 4 * curve.n8q as u64;
+    let mut points = Vec::with_capacity(5);
+    for (section, size) in [
+        (2, g1_size),
+        (3, g2_size),
+        (4, g1_size),
+        (5, g1_size),
+        (PTAU_BETA_G2_SECTION, g2_size),
+    ] {
+        points
+            .push(file::read_section(fd_ptau, sections_ptau, section, Some(0), Some(size)).await?);
+    }
+    let [delta1, delta2, alpha1, beta1, beta2]: [Vec<u8>; 5] = points.try_into().unwrap();
+
+    Ok(Groth16Header {
+        n8q: curve.n8q as u32,
+        q: curve.q.clone(),
+        n8r: curve.n8r as u32,
+        r: curve.r.clone(),
+        n_vars: r1cs.n_vars,
+        n_public: r1cs.n_outputs + r1cs.n_pub_inputs,
+        domain_size: domain_size as u32,
+        alpha1,
+        beta1,
+        beta2,
+        gamma2: delta2.clone(),
+        delta1,
+        delta2,
+    })
+}
+
+/// Writes the protocol marker (section 1) and the Groth16 header (section 2).
+pub async fn write_groth16_header(fd: &mut BinFile, header: &Groth16Header) -> Result<()> {
+    write_zkey_protocol(fd, ZkeyProtocol::Groth16).await?;
+
+    let n8q = header.n8q as usize;
+    let n8r = header.n8r as usize;
+
+    fd.start_write_section(ZKEY_GROTH16_HEADER_SECTION).await?;
+    fd.write_u32(header.n8q).await?;
+    fd.write_bytes(&to_n8r_bytes(&header.q.to_bytes_le(), n8q))
+        .await?;
+    fd.write_u32(header.n8r).await?;
+    fd.write_bytes(&to_n8r_bytes(&header.r.to_bytes_le(), n8r))
+        .await?;
+    fd.write_u32(header.n_vars).await?;
+    fd.write_u32(header.n_public).await?;
+    fd.write_u32(header.domain_size).await?;
+    for (point, size) in [
+        (&header.alpha1, 2 * n8q),
+        (&header.beta1, 2 * n8q),
+        (&header.beta2, 4 * n8q),
+        (&header.gamma2, 4 * n8q),
+        (&header.delta1, 2 * n8q),
+        (&header.delta2, 4 * n8q),
+    ] {
+        fd.write_bytes(&to_n8r_bytes(point, size)).await?;
+    }
+    fd.end_write_section().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SetupConfig;
+    use crate::plonk::ZKEY_HEADER_SECTION;
+    use crate::ptau_file::{PTAU_MAX_VERSION, PTauFile};
+    use crate::zkey::{read_plonk_header, read_zkey_protocol};
+    use tempfile::NamedTempFile;
+
+    const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+    const R1CS_PATH: &str = "src/artifacts/small.r1cs";
+
+    #[tokio::test]
+    async fn test_groth16_header_section() -> Result<()> {
+        let (fd_ptau, sections_ptau) =
+            file::read_bin_file(PTAU_PATH, "ptau", PTAU_MAX_VERSION).await?;
+        let mut ptau = PTauFile::from(fd_ptau);
+        let ptau_header = ptau.read_header(&sections_ptau).await?;
+        let (mut fd_r1cs, sections_r1cs) = file::read_bin_file(R1CS_PATH, "r1cs", 1).await?;
+        let r1cs = file::read_r1cs_header(&mut fd_r1cs, &sections_r1cs).await?;
+
+        let (mut fd, _) = file::read_bin_file(PTAU_PATH, "ptau", PTAU_MAX_VERSION).await?;
+        let header = groth16_header(&mut fd, &sections_ptau, &ptau_header, &r1cs).await?;
+        assert_eq!(header.n_public, r1cs.n_outputs + r1cs.n_pub_inputs);
+        assert!(header.domain_size as u64 > (r1cs.n_constraints + header.n_public) as u64);
+        assert_eq!(header.gamma2, header.delta2);
+
+        // Only the two header sections are written, so only those are declared.
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut zkey = BinFile::create(path, "zkey", 1, 2).await?;
+        write_groth16_header(&mut zkey, &header).await?;
+        zkey.flush().await?;
+
+        let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+        let id = file::read_section(&mut fd, &sections, ZKEY_HEADER_SECTION, None, None).await?;
+        assert_eq!(id, ZKEY_PROTOCOL_GROTH16.to_le_bytes());
+        assert_eq!(
+            read_zkey_protocol(&mut fd, &sections).await?,
+            ZkeyProtocol::Groth16
+        );
+        // n8q, q, n8r, r, nVars, nPublic, domainSize, then three G1 and three G2 points.
+        assert_eq!(
+            sections[&ZKEY_GROTH16_HEADER_SECTION][0].size,
+            4 + 32 + 4 + 32 + 12 + 3 * 64 + 3 * 128
+        );
+        assert!(matches!(
+            read_plonk_header(&mut fd, &sections).await,
+            Err(SnarkError::UnsupportedProtocol(ZKEY_PROTOCOL_GROTH16))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_setup_with_config_rejects_groth16() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("circuit.zkey");
+        let out = out.to_str().unwrap();
+        let mut config = SetupConfig::new(PTAU_PATH, R1CS_PATH, out);
+        config.protocol = ZkeyProtocol::Groth16;
+        assert!(matches!(
+            crate::zkey::setup_with_config(&config, None).await,
+            Err(SnarkError::UnsupportedProtocol(ZKEY_PROTOCOL_GROTH16))
+        ));
+        assert!(!std::path::Path::new(out).exists());
+
+        // The same config with the PLONK protocol writes the PLONK layout.
+        config.protocol = ZkeyProtocol::Plonk;
+        crate::zkey::setup_with_config(&config, None).await?;
+        let (mut fd, sections) = file::read_bin_file(out, "zkey", 1).await?;
+        assert_eq!(
+            read_zkey_protocol(&mut fd, &sections).await?,
+            ZkeyProtocol::Plonk
+        );
+        assert_eq!(sections.len(), ZkeyProtocol::Plonk.n_sections() as usize);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zkey_protocol_ids() {
+        for protocol in [ZkeyProtocol::Groth16, ZkeyProtocol::Plonk] {
+            assert_eq!(ZkeyProtocol::from_id(protocol.id()).unwrap(), protocol);
+        }
+        assert_eq!(ZkeyProtocol::Groth16.n_sections(), 10);
+        assert_eq!(ZkeyProtocol::Plonk.n_sections(), 14);
+        assert!(matches!(
+            ZkeyProtocol::from_id(3),
+            Err(SnarkError::UnsupportedProtocol(3))
+        ));
+        for protocol in [ZkeyProtocol::Groth16, ZkeyProtocol::Plonk] {
+            assert_eq!(ZkeyProtocol::from_name(protocol.name()).unwrap(), protocol);
+        }
+        assert_eq!(
+            ZkeyProtocol::from_name("Groth16").unwrap(),
+            ZkeyProtocol::Groth16
+        );
+        assert!(matches!(
+            ZkeyProtocol::from_name("fflonk"),
+            Err(SnarkError::UnknownProtocolName(_))
+        ));
+    }
+}
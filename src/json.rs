@@ -0,0 +1,438 @@
+use crate::error::{Result, SnarkError};
+use std::fmt::{self, Write};
+
+/// A minimal JSON document model, enough to emit snarkjs-compatible key files.
+///
+/// Object keys keep their insertion order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Looks up `key` in an object; `None` for missing keys and non-objects.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            Self::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Parses a JSON document. Numbers must be unsigned integers that fit a `u64`, the
+    /// only kind the model holds.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// The value under `key`, failing with [`SnarkError::InvalidJson`] if it is missing.
+    pub fn field(&self, key: &str) -> Result<&JsonValue> {
+        self.get(key)
+            .ok_or_else(|| SnarkError::InvalidJson(format!("missing field '{}'", key)))
+    }
+
+    /// The number under `key`, converted to `T`.
+    pub fn number_field<T: TryFrom<u64>>(&self, key: &str) -> Result<T> {
+        self.field(key)?
+            .as_u64()
+            .and_then(|n| T::try_from(n).ok())
+            .ok_or_else(|| {
+                SnarkError::InvalidJson(format!("field '{}' is not a valid number", key))
+            })
+    }
+
+    /// The string under `key`.
+    pub fn str_field(&self, key: &str) -> Result<&str> {
+        self.field(key)?
+            .as_str()
+            .ok_or_else(|| SnarkError::InvalidJson(format!("field '{}' is not a string", key)))
+    }
+
+    /// The boolean under `key`.
+    pub fn bool_field(&self, key: &str) -> Result<bool> {
+        self.field(key)?
+            .as_bool()
+            .ok_or_else(|| SnarkError::InvalidJson(format!("field '{}' is not a boolean", key)))
+    }
+
+    /// Serializes with one space of indentation per level, like `JSON.stringify(v, null, 1)`.
+    pub fn to_string_pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0).unwrap();
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, depth: usize) -> fmt::Result {
+        let indent = |out: &mut String, depth: usize| {
+            out.push('\n');
+            out.extend(std::iter::repeat_n(' ', depth));
+        };
+        match self {
+            Self::Array(items) if !items.is_empty() => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    indent(out, depth + 1);
+                    item.write_pretty(out, depth + 1)?;
+                }
+                indent(out, depth);
+                out.push(']');
+            }
+            Self::Object(entries) if !entries.is_empty() => {
+                out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    indent(out, depth + 1);
+                    write_escaped(out, key)?;
+                    out.push_str(": ");
+                    value.write_pretty(out, depth + 1)?;
+                }
+                indent(out, depth);
+                out.push('}');
+            }
+            _ => write!(out, "{}", self)?,
+        }
+        Ok(())
+    }
+}
+
+/// Conversion to and from [`JsonValue`], for persisting small metadata structs.
+pub trait Json: Sized {
+    fn to_json(&self) -> JsonValue;
+    fn from_json(value: &JsonValue) -> Result<Self>;
+}
+
+/// Recursive-descent parser over the raw bytes; string contents are re-validated as
+/// UTF-8 when they are sliced out.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, what: &str) -> SnarkError {
+        SnarkError::InvalidJson(format!("{} at byte {}", what, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<()> {
+        if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", literal)))
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'n') => self.expect("null").map(|_| JsonValue::Null),
+            Some(b't') => self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b'0'..=b'9') => self.number(),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.close(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.close(b']') {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(JsonValue::Array(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                if !self.close(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.skip_whitespace();
+                        self.expect(":")?;
+                        entries.push((key, self.value()?));
+                        if self.close(b'}') {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(JsonValue::Object(entries))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    /// Consumes `byte` (after whitespace) if it comes next.
+    fn close(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.bytes.get(self.pos) == Some(&byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn number(&mut self) -> Result<JsonValue> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+        if let Some(b'.' | b'e' | b'E') = self.bytes.get(self.pos) {
+            return Err(self.error("only unsigned integers are supported"));
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .unwrap()
+            .parse()
+            .map(JsonValue::Number)
+            .map_err(|_| self.error("number out of range"))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(&b) = self.bytes.get(self.pos) {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            out.push_str(
+                std::str::from_utf8(&self.bytes[start..self.pos])
+                    .map_err(|_| self.error("invalid UTF-8"))?,
+            );
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    let escaped = match self.bytes.get(self.pos + 1) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self
+                                .bytes
+                                .get(self.pos + 2..self.pos + 6)
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("invalid \\u escape"))?;
+                            self.pos += 4;
+                            hex
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    out.push(escaped);
+                    self.pos += 2;
+                }
+                _ => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+fn write_escaped(out: &mut impl Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+/// Compact serialization.
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write_escaped(f, s),
+            Self::Array(items) => {
+                f.write_char('[')?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_char(']')
+            }
+            Self::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_escaped(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_string())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+impl From<u64> for JsonValue {
+    fn from(n: u64) -> Self {
+        Self::Number(n)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+    fn from(items: Vec<T>) -> Self {
+        Self::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let value = JsonValue::Object(vec![
+            ("name".to_string(), JsonValue::from("a \"b\"\n")),
+            ("n".to_string(), JsonValue::from(3)),
+            ("xs".to_string(), JsonValue::from(vec!["1", "2"])),
+            ("empty".to_string(), JsonValue::Array(vec![])),
+        ]);
+
+        assert_eq!(
+            value.to_string(),
+            r#"{"name":"a \"b\"\n","n":3,"xs":["1","2"],"empty":[]}"#
+        );
+        assert_eq!(
+            value.to_string_pretty(),
+            "{\n \"name\": \"a \\\"b\\\"\\n\",\n \"n\": 3,\n \"xs\": [\n  \"1\",\n  \"2\"\n ],\n \"empty\": []\n}"
+        );
+        assert_eq!(value.get("n").and_then(JsonValue::as_u64), Some(3));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let value = JsonValue::Object(vec![
+            ("name".to_string(), JsonValue::from("a \"b\"\n\u{1}")),
+            ("n".to_string(), JsonValue::from(u64::MAX)),
+            ("xs".to_string(), JsonValue::from(vec!["1", "2"])),
+            (
+                "flags".to_string(),
+                JsonValue::Array(vec![JsonValue::Bool(true), JsonValue::Null]),
+            ),
+            ("empty".to_string(), JsonValue::Object(vec![])),
+        ]);
+        assert_eq!(JsonValue::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(JsonValue::parse(&value.to_string_pretty()).unwrap(), value);
+        assert_eq!(
+            JsonValue::parse(r#""\u00e9\/""#).unwrap(),
+            JsonValue::from("\u{e9}/")
+        );
+
+        for bad in [
+            "",
+            "[1,]",
+            "{\"a\" 1}",
+            "1.5",
+            "-1",
+            "\"open",
+            "18446744073709551616",
+            "[] x",
+        ] {
+            assert!(
+                matches!(JsonValue::parse(bad), Err(SnarkError::InvalidJson(_))),
+                "{:?}",
+                bad
+            );
+        }
+        assert!(matches!(
+            value.number_field::<u8>("n"),
+            Err(SnarkError::InvalidJson(_))
+        ));
+        assert_eq!(value.str_field("name").unwrap(), "a \"b\"\n\u{1}");
+    }
+}
@@ -0,0 +1,33 @@
+pub mod big_buffer;
+pub mod checkpoint;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod config;
+pub mod curves;
+pub mod domain;
+pub mod ec;
+pub mod error;
+pub mod estimate;
+pub mod fft;
+pub mod groth16;
+pub mod json;
+pub mod file;
+#[cfg(all(feature = "mmap", not(target_arch = "wasm32")))]
+pub mod mmap;
+pub mod msm;
+pub mod pairing;
+pub mod plonk;
+pub mod ptau_file;
+pub mod r1cs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod spill;
+pub mod utils;
+pub mod verification_key;
+pub mod zkey;
+
+pub use error::{Result, SnarkError};
+pub use plonk::plonk_setup_fd;
+#[cfg(not(target_arch = "wasm32"))]
+pub use plonk::plonk_setup;
@@ -0,0 +1,173 @@
+use crate::error::{Result, SnarkError};
+use crate::file::{self, Section};
+use std::collections::HashMap;
+
+/// A binary file mapped into memory, for zero-copy access to the sections of large
+/// ptau files.
+///
+/// The section map is parsed with [`read_bin_file`](file::read_bin_file), so it is
+/// validated the same way. Where the file cannot be mapped (non-unix targets, empty
+/// files, or a failing `mmap`), its contents are read into memory instead and
+/// [`is_mapped`](Self::is_mapped) returns false.
+///
+/// The file must not be truncated while mapped: reading a page past its new end
+/// raises `SIGBUS`.
+pub struct MmapBinFile {
+    data: Mapping,
+    sections: HashMap<u32, Vec<Section>>,
+}
+
+impl MmapBinFile {
+    pub async fn open(path: &str, expected_type: &str, max_version: u32) -> Result<Self> {
+        let (_, sections) = file::read_bin_file(path, expected_type, max_version).await?;
+        let data = match Mapping::map(&std::fs::File::open(path)?) {
+            Some(mapping) => mapping,
+            None => Mapping::Owned(tokio::fs::read(path).await?),
+        };
+        Ok(Self { data, sections })
+    }
+
+    pub fn sections(&self) -> &HashMap<u32, Vec<Section>> {
+        &self.sections
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.data, Mapping::Owned(_))
+    }
+
+    /// Same as [`file::read_section`], borrowing the bytes from the mapping instead of
+    /// copying them.
+    pub fn read_section(
+        &self,
+        section_id: u32,
+        offset: Option<u64>,
+        length: Option<u64>,
+    ) -> Result<&[u8]> {
+        let section = file::get_unique_section(&self.sections, section_id)?;
+
+        let off = offset.unwrap_or(0);
+        let len = length.unwrap_or(section.size.saturating_sub(off));
+
+        if off.checked_add(len).is_none_or(|end| end > section.size) {
+            return Err(SnarkError::SectionOutOfBounds {
+                section: section_id,
+                offset: off,
+                length: len,
+                size: section.size,
+            });
+        }
+
+        // The file may have shrunk between parsing the sections and mapping it.
+        let data = self.data.as_slice();
+        if section.offset + section.size > data.len() as u64 {
+            return Err(SnarkError::SectionExceedsFile {
+                section: section_id,
+                offset: section.offset,
+                size: section.size,
+                file_size: data.len() as u64,
+            });
+        }
+
+        let start = (section.offset + off) as usize;
+        Ok(&data[start..start + len as usize])
+    }
+}
+
+enum Mapping {
+    #[cfg(unix)]
+    Mapped { ptr: *mut libc::c_void, len: usize },
+    Owned(Vec<u8>),
+}
+
+// SAFETY: the mapping is private and read-only, and is only unmapped on drop.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    #[cfg(unix)]
+    fn map(file: &std::fs::File) -> Option<Self> {
+        use std::os::fd::AsRawFd;
+
+        let len = usize::try_from(file.metadata().ok()?.len()).ok()?;
+        if len == 0 {
+            return None;
+        }
+        // SAFETY: a fresh read-only private mapping of an open file descriptor; the
+        // result is checked before use.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        (ptr != libc::MAP_FAILED).then_some(Self::Mapped { ptr, len })
+    }
+
+    #[cfg(not(unix))]
+    fn map(_file: &std::fs::File) -> Option<Self> {
+        None
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            // SAFETY: `ptr` points to `len` readable bytes until the mapping is dropped.
+            #[cfg(unix)]
+            Self::Mapped { ptr, len } => unsafe {
+                std::slice::from_raw_parts(*ptr as *const u8, *len)
+            },
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Self::Mapped { ptr, len } = *self {
+            // SAFETY: unmaps exactly the region returned by mmap, which is not used again.
+            unsafe {
+                libc::munmap(ptr, len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+
+    #[tokio::test]
+    async fn test_mmap_read_section_matches_async() -> Result<()> {
+        let mapped = MmapBinFile::open(PTAU_PATH, "ptau", 1).await?;
+        assert_eq!(mapped.is_mapped(), cfg!(unix));
+
+        let (mut fd, sections) = file::read_bin_file(PTAU_PATH, "ptau", 1).await?;
+        assert_eq!(mapped.sections().len(), sections.len());
+        for &id in sections.keys() {
+            let expected = file::read_section(&mut fd, &sections, id, None, None).await?;
+            assert_eq!(mapped.read_section(id, None, None)?, expected.as_slice());
+        }
+
+        let expected = file::read_section(&mut fd, &sections, 3, Some(128), Some(128)).await?;
+        assert_eq!(mapped.read_section(3, Some(128), Some(128))?, expected.as_slice());
+        assert!(matches!(
+            mapped.read_section(6, Some(1), Some(128)),
+            Err(SnarkError::SectionOutOfBounds { section: 6, .. })
+        ));
+
+        let mut mapped = mapped;
+        let section = mapped.sections[&2][0].clone();
+        mapped.sections.get_mut(&2).unwrap().push(section);
+        assert!(matches!(
+            mapped.read_section(2, None, None),
+            Err(SnarkError::SectionDuplicated(2))
+        ));
+        Ok(())
+    }
+}
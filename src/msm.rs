@@ -0,0 +1,369 @@
+use crate::big_buffer::BigBuffer;
+use crate::curves::Curve;
+use crate::ec::{Fq, G1, G1Affine};
+use crate::error::{Result, SnarkError};
+use crate::utils::{CancelFlag, check_cancelled};
+use r1cs::num::BigUint;
+use r1cs::{Bn128, Element};
+
+/// Pippenger bucket storage, reused across windows and across calls to
+/// [`msm_with_context`] so that a series of commitments allocates it once. It keeps
+/// the capacity of the widest window it has served.
+#[derive(Default)]
+pub struct MsmContext {
+    buckets: Vec<G1>,
+}
+
+impl MsmContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `2^c - 1` cleared buckets for a window of `c` bits.
+    fn buckets(&mut self, c: usize) -> &mut [G1] {
+        self.buckets.clear();
+        self.buckets.resize((1 << c) - 1, G1::identity());
+        &mut self.buckets
+    }
+}
+
+/// Computes `sum(scalars[i] * points[i])` with Pippenger's bucket method.
+pub fn msm(points: &[G1Affine], scalars: &[Element<Bn128>]) -> Result<G1> {
+    msm_with_context(&mut MsmContext::new(), points, scalars)
+}
+
+/// Same as [`msm`], taking its buckets from `context`.
+pub fn msm_with_context(
+    context: &mut MsmContext,
+    points: &[G1Affine],
+    scalars: &[Element<Bn128>],
+) -> Result<G1> {
+    context_msm(context, points, scalars, None)
+}
+
+/// Same as [`msm_with_context`], failing with [`SnarkError::Cancelled`] soon after
+/// `cancel` is set. The flag is polled before each Pippenger window.
+pub fn msm_with_context_cancellable(
+    context: &mut MsmContext,
+    points: &[G1Affine],
+    scalars: &[Element<Bn128>],
+    cancel: CancelFlag<'_>,
+) -> Result<G1> {
+    context_msm(context, points, scalars, Some(cancel))
+}
+
+fn context_msm(
+    context: &mut MsmContext,
+    points: &[G1Affine],
+    scalars: &[Element<Bn128>],
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<G1> {
+    if points.len() != scalars.len() {
+        return Err(SnarkError::MsmLengthMismatch {
+            points: points.len(),
+            scalars: scalars.len(),
+        });
+    }
+
+    pippenger(context, points, &scalar_bytes(scalars), cancel)
+}
+
+/// Same as [`msm`] over points stored in a [`BigBuffer`] as Montgomery-form affine
+/// coordinates, the layout of ptau section 2.
+///
+/// Points are decoded `chunk_points` at a time and each chunk contributes a partial
+/// Pippenger sum, so only `n_threads` chunks are held in memory at once. With
+/// `n_threads > 1` those chunks run on scoped threads. `chunk_points` must not be zero.
+pub fn msm_over_bigbuffer(
+    curve: &Curve,
+    points: &BigBuffer,
+    scalars: &[Element<Bn128>],
+    chunk_points: usize,
+    n_threads: usize,
+) -> Result<G1> {
+    chunked_msm(curve, points, scalars, chunk_points, n_threads, None)
+}
+
+/// Same as [`msm_over_bigbuffer`], failing with [`SnarkError::Cancelled`] soon after
+/// `cancel` is set. The flag is polled before each batch of `n_threads` chunks.
+pub fn msm_over_bigbuffer_cancellable(
+    curve: &Curve,
+    points: &BigBuffer,
+    scalars: &[Element<Bn128>],
+    chunk_points: usize,
+    n_threads: usize,
+    cancel: CancelFlag<'_>,
+) -> Result<G1> {
+    chunked_msm(
+        curve,
+        points,
+        scalars,
+        chunk_points,
+        n_threads,
+        Some(cancel),
+    )
+}
+
+fn chunked_msm(
+    curve: &Curve,
+    points: &BigBuffer,
+    scalars: &[Element<Bn128>],
+    chunk_points: usize,
+    n_threads: usize,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<G1> {
+    if chunk_points == 0 {
+        return Err(SnarkError::ZeroSize("points per chunk"));
+    }
+    let point_size = 2 * curve.n8q;
+    let n_points = points.byte_length() / point_size;
+    if n_points * point_size != points.byte_length() || n_points != scalars.len() {
+        return Err(SnarkError::MsmLengthMismatch {
+            points: n_points,
+            scalars: scalars.len(),
+        });
+    }
+
+    let scalars = scalar_bytes(scalars);
+    let mut chunks = points
+        .iter_chunks(chunk_points * point_size)
+        .zip(scalars.chunks(chunk_points));
+
+    let mut result = G1::identity();
+    loop {
+        check_cancelled(cancel)?;
+        let batch: Vec<_> = chunks.by_ref().take(n_threads.max(1)).collect();
+        let partials: Vec<Result<PartialSum>> = match batch.as_slice() {
+            [] => break,
+            [(bytes, scalars)] => vec![chunk_msm(curve, bytes, scalars)],
+            _ => std::thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .iter()
+                    .map(|(bytes, scalars)| scope.spawn(move || chunk_msm(curve, bytes, scalars)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|err| std::panic::resume_unwind(err))
+                    })
+                    .collect()
+            }),
+        };
+        for partial in partials {
+            result = result.add(&partial?.into_point());
+        }
+    }
+
+    Ok(result)
+}
+
+/// The MSM of one chunk of a [`BigBuffer`], as the `BigUint` values of its projective
+/// coordinates: `G1` is `!Send` like the `Element`s it holds.
+struct PartialSum([BigUint; 3]);
+
+impl PartialSum {
+    fn new(point: G1) -> Self {
+        Self([point.x, point.y, point.z].map(|c| c.to_biguint().clone()))
+    }
+
+    fn into_point(self) -> G1 {
+        let [x, y, z] = self.0.map(Fq::from);
+        G1 { x, y, z }
+    }
+}
+
+fn chunk_msm(curve: &Curve, bytes: &[u8], scalars: &[Vec<u8>]) -> Result<PartialSum> {
+    let points = bytes
+        .chunks(2 * curve.n8q)
+        .map(|point| G1Affine::from_montgomery_bytes(curve, point))
+        .collect::<Result<Vec<_>>>()?;
+    let mut context = MsmContext::new();
+    let sum = pippenger(&mut context, &points, scalars, None)?;
+    Ok(PartialSum::new(sum))
+}
+
+/// Little-endian bytes of each scalar's canonical value.
+fn scalar_bytes(scalars: &[Element<Bn128>]) -> Vec<Vec<u8>> {
+    scalars
+        .iter()
+        .map(|s| s.to_biguint().to_bytes_le())
+        .collect()
+}
+
+fn pippenger(
+    context: &mut MsmContext,
+    points: &[G1Affine],
+    scalars: &[Vec<u8>],
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<G1> {
+    let c = window_bits(points.len());
+    let max_bits = scalars.iter().map(|s| s.len() * 8).max().unwrap_or(0);
+    let n_windows = max_bits.div_ceil(c);
+
+    let mut result = G1::identity();
+    for window in (0..n_windows).rev() {
+        check_cancelled(cancel)?;
+        for _ in 0..c {
+            result = result.double();
+        }
+
+        let buckets = context.buckets(c);
+        for (point, scalar) in points.iter().zip(scalars) {
+            let digit = window_digit(scalar, window * c, c);
+            if digit != 0 {
+                buckets[digit - 1] = buckets[digit - 1].add_affine(point);
+            }
+        }
+
+        // sum(i * bucket[i]) as a running sum from the top bucket down.
+        let mut running = G1::identity();
+        let mut window_sum = G1::identity();
+        for bucket in buckets.iter().rev() {
+            running = running.add(bucket);
+            window_sum = window_sum.add(&running);
+        }
+        result = result.add(&window_sum);
+    }
+
+    Ok(result)
+}
+
+/// Window width in bits, growing roughly with `log2(n)`.
+fn window_bits(n: usize) -> usize {
+    if n < 32 {
+        3
+    } else {
+        (usize::BITS - n.leading_zeros()) as usize * 69 / 100 + 2
+    }
+}
+
+/// Extracts `c` bits of a little-endian scalar starting at bit `start`.
+fn window_digit(scalar: &[u8], start: usize, c: usize) -> usize {
+    let mut digit = 0;
+    for i in (start..start + c).rev() {
+        let bit = scalar.get(i / 8).map_or(0, |b| (b >> (i % 8)) & 1);
+        digit = (digit << 1) | bit as usize;
+    }
+    digit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msm_matches_naive() -> Result<()> {
+        let g = G1::from(&G1Affine::generator());
+        let points: Vec<G1Affine> = (1..=20u64)
+            .map(|k| g.mul(&Element::from(k * 7919)).to_affine())
+            .chain([G1Affine::identity()])
+            .collect();
+        let scalars: Vec<Element<Bn128>> = (0..points.len() as u64 - 2)
+            .map(|i| Element::from(i * i * 104729 + 3) * Element::from(u128::MAX))
+            .chain([Element::zero(), Element::largest_element()])
+            .collect();
+
+        let naive = points
+            .iter()
+            .zip(&scalars)
+            .fold(G1::identity(), |acc, (p, s)| acc.add(&G1::from(p).mul(s)));
+
+        assert_eq!(msm(&points, &scalars)?, naive);
+        Ok(())
+    }
+
+    #[test]
+    fn test_msm_context_reuse() -> Result<()> {
+        let g = G1::from(&G1Affine::generator());
+        let mut context = MsmContext::new();
+        // Sizes on both sides of the 32-point window change, largest first.
+        for n in [40u64, 5, 33, 1, 0] {
+            let points: Vec<G1Affine> = (1..=n)
+                .map(|k| g.mul(&Element::from(k * 31)).to_affine())
+                .collect();
+            let scalars: Vec<Element<Bn128>> = (0..n)
+                .map(|i| Element::from(i * 2749 + n) * Element::from(u128::MAX))
+                .collect();
+            assert_eq!(
+                msm_with_context(&mut context, &points, &scalars)?,
+                msm(&points, &scalars)?,
+                "{} points",
+                n
+            );
+        }
+
+        let points = vec![g.to_affine(); 3];
+        let scalars: Vec<Element<Bn128>> = (1..=3u64).map(Element::from).collect();
+        let cancel = std::sync::atomic::AtomicBool::new(false);
+        let result = msm_with_context_cancellable(&mut context, &points, &scalars, &cancel)?;
+        assert_eq!(result, msm(&points, &scalars)?);
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        let result = msm_with_context_cancellable(&mut context, &points, &scalars, &cancel);
+        assert!(matches!(result, Err(SnarkError::Cancelled)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_msm_over_bigbuffer_matches_msm() -> Result<()> {
+        let curve = Curve::new();
+        let (mut fd, sections) =
+            crate::file::read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let n: usize = 16;
+        let bytes =
+            crate::file::read_section(&mut fd, &sections, 2, None, Some(n as u64 * 64)).await?;
+        let points = bytes
+            .chunks(64)
+            .map(|point| G1Affine::from_montgomery_bytes(&curve, point))
+            .collect::<Result<Vec<_>>>()?;
+        let scalars: Vec<Element<Bn128>> = (0..n as u64)
+            .map(|i| Element::from(i * 6151 + 1) * Element::from(u128::MAX))
+            .collect();
+        let expected = msm(&points, &scalars)?;
+
+        // 100-byte pages, so points straddle page boundaries.
+        let mut buffer = BigBuffer::with_page_size(bytes.len(), 100);
+        buffer.set(&bytes, 0);
+        for (chunk_points, n_threads) in [(n, 1), (3, 1), (3, 4), (5, 2)] {
+            let result = msm_over_bigbuffer(&curve, &buffer, &scalars, chunk_points, n_threads)?;
+            assert_eq!(
+                result, expected,
+                "{} points per chunk, {} threads",
+                chunk_points, n_threads
+            );
+        }
+
+        let cancel = std::sync::atomic::AtomicBool::new(true);
+        let result = msm_over_bigbuffer_cancellable(&curve, &buffer, &scalars, 4, 2, &cancel);
+        assert!(matches!(result, Err(SnarkError::Cancelled)));
+
+        let result = msm_over_bigbuffer(&curve, &buffer, &scalars, 0, 1);
+        assert!(matches!(
+            result,
+            Err(SnarkError::ZeroSize("points per chunk"))
+        ));
+
+        let result = msm_over_bigbuffer(&curve, &buffer, &scalars[1..], 4, 1);
+        assert!(matches!(
+            result,
+            Err(SnarkError::MsmLengthMismatch {
+                points: 16,
+                scalars: 15
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_msm_length_mismatch() {
+        let result = msm(&[G1Affine::generator()], &[]);
+        assert!(matches!(
+            result,
+            Err(SnarkError::MsmLengthMismatch {
+                points: 1,
+                scalars: 0
+            })
+        ));
+    }
+}
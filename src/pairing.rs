@@ -0,0 +1,461 @@
+//! A minimal BN128 pairing, enough to check products of pairings against one.
+//!
+//! This is the optimal ate pairing computed over the tower `Fq6 = Fq2[v] / (v^3 - xi)`,
+//! `Fq12 = Fq6[w] / (w^2 - v)` with `xi = 9 + u`, the tower of the Ethereum precompile.
+//! The Miller loop runs over `6x + 2` on the G2 point, in affine coordinates on the
+//! twist, and evaluates its lines at the G1 point. Nothing is precomputed or optimized:
+//! it is meant for spot checks such as
+//! [`verify_ptau_powers`](crate::ptau_file::verify_ptau_powers), not for verifying
+//! proofs in bulk.
+
+use crate::ec::{Bn128Fq, Fq, Fq2, G1Affine, G2Affine};
+use r1cs::num::BigUint;
+use r1cs::{Bn128, Field};
+
+/// Bits of `n` from the most significant one down.
+fn bits_msb_first(n: &BigUint) -> impl Iterator<Item = bool> {
+    let bytes = n.to_bytes_be();
+    let skip = bytes[0].leading_zeros() as usize;
+    bytes
+        .into_iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1))
+        .skip(skip)
+}
+
+fn fq2_neg(a: &Fq2) -> Fq2 {
+    Fq2::new(-a.c0.clone(), -a.c1.clone())
+}
+
+/// Multiplies by `xi = 9 + u`.
+fn fq2_mul_by_xi(a: &Fq2) -> Fq2 {
+    let nine = Fq::from(9u64);
+    Fq2::new(&a.c0 * &nine - &a.c1, &a.c0 + &a.c1 * nine)
+}
+
+fn fq2_scale(a: &Fq2, k: &Fq) -> Fq2 {
+    Fq2::new(&a.c0 * k, &a.c1 * k)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Fq6 {
+    c0: Fq2,
+    c1: Fq2,
+    c2: Fq2,
+}
+
+impl Fq6 {
+    fn zero() -> Self {
+        Self {
+            c0: Fq2::zero(),
+            c1: Fq2::zero(),
+            c2: Fq2::zero(),
+        }
+    }
+
+    fn one() -> Self {
+        Self {
+            c0: Fq2::one(),
+            ..Self::zero()
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self {
+            c0: self.c0.add(&other.c0),
+            c1: self.c1.add(&other.c1),
+            c2: self.c2.add(&other.c2),
+        }
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        Self {
+            c0: self.c0.sub(&other.c0),
+            c1: self.c1.sub(&other.c1),
+            c2: self.c2.sub(&other.c2),
+        }
+    }
+
+    fn neg(&self) -> Self {
+        Self {
+            c0: fq2_neg(&self.c0),
+            c1: fq2_neg(&self.c1),
+            c2: fq2_neg(&self.c2),
+        }
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        let (a, b) = (self, other);
+        // v^3 = xi folds the v^3 and v^4 terms back down.
+        let c0 =
+            a.c0.mul(&b.c0)
+                .add(&fq2_mul_by_xi(&a.c1.mul(&b.c2).add(&a.c2.mul(&b.c1))));
+        let c1 =
+            a.c0.mul(&b.c1)
+                .add(&a.c1.mul(&b.c0))
+                .add(&fq2_mul_by_xi(&a.c2.mul(&b.c2)));
+        let c2 = a.c0.mul(&b.c2).add(&a.c1.mul(&b.c1)).add(&a.c2.mul(&b.c0));
+        Self { c0, c1, c2 }
+    }
+
+    /// Multiplies by `v`.
+    fn mul_by_v(&self) -> Self {
+        Self {
+            c0: fq2_mul_by_xi(&self.c2),
+            c1: self.c0.clone(),
+            c2: self.c1.clone(),
+        }
+    }
+
+    fn inverse(&self) -> Self {
+        let t0 = self.c0.square().sub(&fq2_mul_by_xi(&self.c1.mul(&self.c2)));
+        let t1 = fq2_mul_by_xi(&self.c2.square()).sub(&self.c0.mul(&self.c1));
+        let t2 = self.c1.square().sub(&self.c0.mul(&self.c2));
+        let det = self
+            .c0
+            .mul(&t0)
+            .add(&fq2_mul_by_xi(&self.c2.mul(&t1).add(&self.c1.mul(&t2))));
+        let det_inv = det.inverse();
+        Self {
+            c0: t0.mul(&det_inv),
+            c1: t1.mul(&det_inv),
+            c2: t2.mul(&det_inv),
+        }
+    }
+}
+
+/// An element `c0 + c1 * w` of the degree-12 extension, where pairings take values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fq12 {
+    c0: Fq6,
+    c1: Fq6,
+}
+
+impl Fq12 {
+    pub fn one() -> Self {
+        Self {
+            c0: Fq6::one(),
+            c1: Fq6::zero(),
+        }
+    }
+
+    pub fn is_one(&self) -> bool {
+        *self == Self::one()
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let aa = self.c0.mul(&other.c0);
+        let bb = self.c1.mul(&other.c1);
+        let c1 = self
+            .c0
+            .add(&self.c1)
+            .mul(&other.c0.add(&other.c1))
+            .sub(&aa)
+            .sub(&bb);
+        Self {
+            c0: aa.add(&bb.mul_by_v()),
+            c1,
+        }
+    }
+
+    pub fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    pub fn inverse(&self) -> Self {
+        let norm = self.c0.mul(&self.c0).sub(&self.c1.mul(&self.c1).mul_by_v());
+        let norm_inv = norm.inverse();
+        Self {
+            c0: self.c0.mul(&norm_inv),
+            c1: self.c1.neg().mul(&norm_inv),
+        }
+    }
+
+    /// The `q^6`-power Frobenius, which maps `w` to `-w`.
+    fn conjugate(&self) -> Self {
+        Self {
+            c0: self.c0.clone(),
+            c1: self.c1.neg(),
+        }
+    }
+
+    /// The `q^2`-power Frobenius. It fixes `Fq2` and maps `w^k` to `w^k * gamma^k`, where
+    /// `gamma = xi^((q^2 - 1) / 6)`, the value of `w^(q^2 - 1)`.
+    fn frobenius_q2(&self) -> Self {
+        let q = Bn128Fq::order();
+        let gamma = xi_pow(&((&q * &q - 1u32) / 6u32));
+        let mut powers = vec![Fq2::one()];
+        for k in 1..6 {
+            powers.push(powers[k - 1].mul(&gamma));
+        }
+        let map = |c: &Fq6, j: usize| Fq6 {
+            c0: c.c0.mul(&powers[j]),
+            c1: c.c1.mul(&powers[2 + j]),
+            c2: c.c2.mul(&powers[4 + j]),
+        };
+        Self {
+            c0: map(&self.c0, 0),
+            c1: map(&self.c1, 1),
+        }
+    }
+
+    pub fn pow(&self, exponent: &BigUint) -> Self {
+        let mut acc = Self::one();
+        for bit in bits_msb_first(exponent) {
+            acc = acc.square();
+            if bit {
+                acc = acc.mul(self);
+            }
+        }
+        acc
+    }
+}
+
+/// `6x + 2` for the BN parameter `x = 4965661367192848881`: the optimal ate loop count.
+const ATE_LOOP_COUNT: u128 = 29793968203157093288;
+
+/// `xi^exponent`, for the constants of the Frobenius maps.
+fn xi_pow(exponent: &BigUint) -> Fq2 {
+    let xi = Fq2::new(Fq::from(9u64), Fq::one());
+    bits_msb_first(exponent).fold(Fq2::one(), |acc, bit| {
+        let acc = acc.square();
+        if bit { acc.mul(&xi) } else { acc }
+    })
+}
+
+/// The `q^k`-power Frobenius of the curve, acting on a twist point: with the twist
+/// mapped into `E(Fq12)` as `(x' w^2, y' w^3)`, raising to `q^k` conjugates `x'` and
+/// `y'` `k` times and scales them by `w^(2 (q^k - 1))` and `w^(3 (q^k - 1))`, which lie
+/// in `Fq2` since `w^6 = xi`.
+fn frobenius_g2(q: &G2Affine, k: u32) -> G2Affine {
+    let modulus = Bn128Fq::order();
+    let qk = (1..k).fold(modulus.clone(), |acc, _| acc * &modulus);
+    let conjugate = |a: &Fq2| {
+        if k % 2 == 1 {
+            Fq2::new(a.c0.clone(), -a.c1.clone())
+        } else {
+            a.clone()
+        }
+    };
+    let gamma_x = xi_pow(&((&qk - 1u32) / 3u32));
+    let gamma_y = xi_pow(&((&qk - 1u32) / 2u32));
+    G2Affine::new(conjugate(&q.x).mul(&gamma_x), conjugate(&q.y).mul(&gamma_y))
+}
+
+/// The line through the twist point `t` with slope `lambda`, mapped into `E(Fq12)` and
+/// evaluated at `p`: `y_p - lambda w x_p + (lambda x_t - y_t) w^3`.
+fn line(t: &G2Affine, lambda: &Fq2, p: &G1Affine) -> Fq12 {
+    Fq12 {
+        c0: Fq6 {
+            c0: Fq2::new(p.y.clone(), Fq::zero()),
+            c1: Fq2::zero(),
+            c2: Fq2::zero(),
+        },
+        c1: Fq6 {
+            c0: fq2_neg(&fq2_scale(lambda, &p.x)),
+            c1: lambda.mul(&t.x).sub(&t.y),
+            c2: Fq2::zero(),
+        },
+    }
+}
+
+/// Adds `other` to `t` on the twist, multiplying `f` by the line through them. `t` and
+/// `other` are never equal or opposite here: the loop count stays below `r`.
+fn add_step(f: &mut Fq12, t: &mut G2Affine, other: &G2Affine, p: &G1Affine) {
+    let lambda = other.y.sub(&t.y).mul(&other.x.sub(&t.x).inverse());
+    *f = f.mul(&line(t, &lambda, p));
+    let x = lambda.square().sub(&t.x).sub(&other.x);
+    let y = lambda.mul(&t.x.sub(&x)).sub(&t.y);
+    *t = G2Affine::new(x, y);
+}
+
+/// `f_{6x+2,Q}(P)` times the lines through `[6x+2]Q`, `pi(Q)` and `-pi^2(Q)`, leaving
+/// out the vertical lines, whose values lie in `Fq6` and vanish in the final
+/// exponentiation.
+fn miller_loop(p: &G1Affine, q: &G2Affine) -> Fq12 {
+    if p.infinity || q.infinity {
+        return Fq12::one();
+    }
+    let two = Fq2::new(Fq::from(2u64), Fq::zero());
+    let three = Fq2::new(Fq::from(3u64), Fq::zero());
+
+    let mut f = Fq12::one();
+    let mut t = q.clone();
+    // The leading bit is the starting point t = q.
+    for bit in bits_msb_first(&BigUint::from(ATE_LOOP_COUNT)).skip(1) {
+        let lambda = three.mul(&t.x.square()).mul(&two.mul(&t.y).inverse());
+        f = f.square().mul(&line(&t, &lambda, p));
+        let x = lambda.square().sub(&t.x).sub(&t.x);
+        let y = lambda.mul(&t.x.sub(&x)).sub(&t.y);
+        t = G2Affine::new(x, y);
+
+        if bit {
+            add_step(&mut f, &mut t, q, p);
+        }
+    }
+
+    let q1 = frobenius_g2(q, 1);
+    let q2 = frobenius_g2(q, 2);
+    add_step(&mut f, &mut t, &q1, p);
+    add_step(&mut f, &mut t, &G2Affine::new(q2.x, fq2_neg(&q2.y)), p);
+    f
+}
+
+/// Raises a Miller loop output to `(q^12 - 1) / r`, which factors as
+/// `(q^6 - 1) (q^2 + 1) (q^4 - q^2 + 1) / r`. The first two factors are Frobenius maps;
+/// only the last one takes a generic exponentiation.
+fn final_exponentiation(f: &Fq12) -> Fq12 {
+    let f = f.conjugate().mul(&f.inverse());
+    let f = f.frobenius_q2().mul(&f);
+    let q2 = Bn128Fq::order() * Bn128Fq::order();
+    f.pow(&((&q2 * &q2 - &q2 + 1u32) / Bn128::order()))
+}
+
+/// The pairing `e(p, q)`.
+pub fn pairing(p: &G1Affine, q: &G2Affine) -> Fq12 {
+    final_exponentiation(&miller_loop(p, q))
+}
+
+/// The product of `e(p, q)` over `pairs`, sharing a single final exponentiation.
+pub fn multi_pairing(pairs: &[(G1Affine, G2Affine)]) -> Fq12 {
+    let f = pairs
+        .iter()
+        .fold(Fq12::one(), |acc, (p, q)| acc.mul(&miller_loop(p, q)));
+    final_exponentiation(&f)
+}
+
+/// Whether the product of `e(p, q)` over `pairs` is one.
+pub fn pairing_check(pairs: &[(G1Affine, G2Affine)]) -> bool {
+    multi_pairing(pairs).is_one()
+}
+
+/// `-p`, for moving a pairing to the other side of an equation.
+pub fn neg_g1(p: &G1Affine) -> G1Affine {
+    if p.infinity {
+        return p.clone();
+    }
+    G1Affine::new(p.x.clone(), -p.y.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curves::Curve;
+    use crate::ec::G1;
+    use crate::error::Result;
+    use crate::file::{read_bin_file, read_section};
+
+    async fn g2_generator() -> Result<G2Affine> {
+        let curve = Curve::new();
+        let (mut fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let bytes = read_section(&mut fd, &sections, 3, Some(0), Some(128)).await?;
+        G2Affine::from_montgomery_bytes(&curve, &bytes)
+    }
+
+    #[tokio::test]
+    async fn test_pairing_is_bilinear_and_non_degenerate() -> Result<()> {
+        let p = G1Affine::generator();
+        let q = g2_generator().await?;
+        let p2 = G1::from(&p).double().to_affine();
+
+        // e(2p, q) = e(p, q)^2, while e(p, q) itself is not one.
+        assert!(pairing_check(&[
+            (p2, q.clone()),
+            (neg_g1(&p), q.clone()),
+            (neg_g1(&p), q.clone())
+        ]));
+        assert!(!pairing(&p, &q).is_one());
+        Ok(())
+    }
+
+    /// `k * q` by affine double-and-add on the twist; the crate has no G2 arithmetic.
+    fn g2_mul(q: &G2Affine, k: u64) -> G2Affine {
+        let add = |a: &G2Affine, b: &G2Affine| {
+            let (three, two) = (
+                Fq2::new(Fq::from(3u64), Fq::zero()),
+                Fq2::new(Fq::from(2u64), Fq::zero()),
+            );
+            let lambda = if a == b {
+                three.mul(&a.x.square()).mul(&two.mul(&a.y).inverse())
+            } else {
+                b.y.sub(&a.y).mul(&b.x.sub(&a.x).inverse())
+            };
+            let x = lambda.square().sub(&a.x).sub(&b.x);
+            let y = lambda.mul(&a.x.sub(&x)).sub(&a.y);
+            G2Affine::new(x, y)
+        };
+        let mut acc: Option<G2Affine> = None;
+        for i in (0..64 - k.leading_zeros()).rev() {
+            acc = acc.map(|a| add(&a, &a));
+            if k >> i & 1 == 1 {
+                acc = Some(acc.map_or(q.clone(), |a| add(&a, q)));
+            }
+        }
+        acc.unwrap()
+    }
+
+    /// `e(g1, g2)` for the standard generators, as py_ecc's ate pairing computes it
+    /// once moved onto this tower.
+    #[tokio::test]
+    async fn test_pairing_of_generators() -> Result<()> {
+        let e = pairing(&G1Affine::generator(), &g2_generator().await?);
+        let fq2 = |c0: &str, c1: &str| Fq2::new(c0.parse().unwrap(), c1.parse().unwrap());
+        assert_eq!(
+            e.c0.c0,
+            fq2(
+                "8493334370784016972005089913588211327688223499729897951716206968320726508021",
+                "3758435817766288188804561253838670030762970764366672594784247447067868088068"
+            )
+        );
+        assert_eq!(
+            e.c1.c2,
+            fq2(
+                "6223602427219597392892794664899549544171383137467762280768257680446283161705",
+                "7484542354754424633621663080190936924481536615300815203692506276894207018007"
+            )
+        );
+        // GT has order r.
+        assert!(e.pow(&Bn128::order()).is_one());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pairing_of_multiples() -> Result<()> {
+        let p = G1Affine::generator();
+        let q = g2_generator().await?;
+        let p2 = G1::from(&p).double().to_affine();
+        let q3 = g2_mul(&q, 3);
+        assert!(q3.is_on_curve());
+
+        // e(2p, 3q) = e(p, q)^6, and the batched product agrees with the single pairings.
+        let e = pairing(&p, &q);
+        assert_eq!(pairing(&p2, &q3), e.pow(&BigUint::from(6u32)));
+        assert_eq!(
+            multi_pairing(&[(p.clone(), q.clone()), (p2, q.clone())]),
+            e.pow(&BigUint::from(3u32))
+        );
+        assert!(multi_pairing(&[]).is_one());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fq12_inverse() {
+        let x = Fq12 {
+            c0: Fq6 {
+                c0: Fq2::new(Fq::from(1u64), Fq::from(2u64)),
+                c1: Fq2::new(Fq::from(3u64), Fq::from(4u64)),
+                c2: Fq2::new(Fq::from(5u64), Fq::from(6u64)),
+            },
+            c1: Fq6 {
+                c0: Fq2::new(Fq::from(7u64), Fq::from(8u64)),
+                c1: Fq2::new(Fq::from(9u64), Fq::from(10u64)),
+                c2: Fq2::new(Fq::from(11u64), Fq::from(12u64)),
+            },
+        };
+        assert!(x.mul(&x.inverse()).is_one());
+        // The q^2 Frobenius has order 6.
+        let y = (0..6).fold(x.clone(), |y, _| y.frobenius_q2());
+        assert_eq!(y, x);
+        assert_ne!(x.frobenius_q2(), x);
+        assert!(x.conjugate().mul(&x).c1 == Fq6::zero());
+    }
+}
@@ -0,0 +1,1357 @@
+use crate::big_buffer::{BigBuffer, BigBufferStorage};
+use crate::checkpoint::SetupCheckpoint;
+use crate::curves::Curve;
+use crate::domain::Domain;
+use crate::error::{Result, SnarkError};
+use crate::ec::G1Affine;
+use crate::fft::FftEngine;
+use crate::file::{self, BinFile, Section};
+use crate::msm::{MsmContext, msm_with_context, msm_with_context_cancellable};
+use crate::ptau_file::{PTAU_MAX_VERSION, PTauFile, PtauHeader};
+use crate::r1cs::{self, PlonkAddition, PlonkConstraint};
+use crate::utils::{CancelFlag, ProgressCallback, check_cancelled, compute_domain_power};
+use ::r1cs::{Bn128, Element, Field, num::BigUint};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Protocol id stored in section 1 of a PLONK zkey.
+pub const ZKEY_PROTOCOL_PLONK: u32 = 2;
+
+pub const ZKEY_HEADER_SECTION: u32 = 1;
+pub const ZKEY_PL_HEADER_SECTION: u32 = 2;
+pub const ZKEY_PL_ADDITIONS_SECTION: u32 = 3;
+pub const ZKEY_PL_A_MAP_SECTION: u32 = 4;
+pub const ZKEY_PL_B_MAP_SECTION: u32 = 5;
+pub const ZKEY_PL_C_MAP_SECTION: u32 = 6;
+pub const ZKEY_PL_QM_SECTION: u32 = 7;
+pub const ZKEY_PL_QL_SECTION: u32 = 8;
+pub const ZKEY_PL_QR_SECTION: u32 = 9;
+pub const ZKEY_PL_QO_SECTION: u32 = 10;
+pub const ZKEY_PL_QC_SECTION: u32 = 11;
+pub const ZKEY_PL_SIGMA_SECTION: u32 = 12;
+pub const ZKEY_PL_LAGRANGE_SECTION: u32 = 13;
+pub const ZKEY_PL_PTAU_SECTION: u32 = 14;
+
+/// Number of sections in a PLONK zkey.
+pub const ZKEY_PL_N_SECTIONS: u32 = 14;
+
+/// Contents of the PLONK zkey header (section 2), in the order snarkjs serializes them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlonkHeader {
+    pub n8q: u32,
+    pub q: BigUint,
+    pub n8r: u32,
+    pub r: BigUint,
+    pub n_vars: u32,
+    pub n_public: u32,
+    pub domain_size: u32,
+    pub n_additions: u32,
+    pub n_constraints: u32,
+    pub k1: Element<Bn128>,
+    pub k2: Element<Bn128>,
+    /// Affine G1 commitments to Qm, Ql, Qr, Qo, Qc, S1, S2 and S3, `2 * n8q` bytes each.
+    pub commitments: [Vec<u8>; 8],
+    /// Affine G2 point `[tau]_2`, `4 * n8q` bytes.
+    pub x2: Vec<u8>,
+}
+
+/// Runs the PLONK setup for the circuit in `r1cs_path` against the powers of tau in
+/// `ptau_path`, writing the resulting zkey to `out_path`.
+///
+/// `progress`, when given, is called as `(stage, done, total)` while the long-running
+/// stages advance.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn plonk_setup(
+    ptau_path: &str,
+    r1cs_path: &str,
+    out_path: &str,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    setup_files(ptau_path, r1cs_path, out_path, progress, None).await
+}
+
+/// Same as [`plonk_setup`], failing with [`SnarkError::Cancelled`] soon after `cancel` is
+/// set: the flag is polled before every constraint and before each FFT stage. The
+/// partial zkey is removed when the run is cancelled.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn plonk_setup_cancellable(
+    ptau_path: &str,
+    r1cs_path: &str,
+    out_path: &str,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: CancelFlag<'_>,
+) -> Result<()> {
+    let result = setup_files(ptau_path, r1cs_path, out_path, progress, Some(cancel)).await;
+    if matches!(result, Err(SnarkError::Cancelled)) && tokio::fs::try_exists(out_path).await? {
+        tokio::fs::remove_file(out_path).await?;
+    }
+    result
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn setup_files(
+    ptau_path: &str,
+    r1cs_path: &str,
+    out_path: &str,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    let (fd_ptau, sections_ptau) = file::read_bin_file(ptau_path, "ptau", PTAU_MAX_VERSION).await?;
+    let (fd_r1cs, sections_r1cs) = file::read_bin_file(r1cs_path, "r1cs", 1).await?;
+    let mut fd_zkey = BinFile::create(out_path, "zkey", 1, ZKEY_PL_N_SECTIONS).await?;
+
+    setup_fd(fd_ptau, &sections_ptau, fd_r1cs, &sections_r1cs, &mut fd_zkey, progress, cancel).await
+}
+
+/// Runs [`plonk_setup`] on the files named in `config`, after checking that its curve is
+/// the one the ptau was generated over. Configs naming another protocol are rejected;
+/// [`setup_with_config`](crate::zkey::setup_with_config) dispatches on it instead. The
+/// `verbose` flag is left to the caller, which decides what `progress` prints.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn plonk_setup_with_config(
+    config: &crate::config::SetupConfig,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    if config.protocol != crate::zkey::ZkeyProtocol::Plonk {
+        return Err(SnarkError::UnsupportedProtocol(config.protocol.id()));
+    }
+    let (fd_ptau, sections_ptau) = file::read_bin_file(&config.ptau, "ptau", PTAU_MAX_VERSION).await?;
+    let ptau_header = PTauFile::from(fd_ptau).read_header(&sections_ptau).await?;
+    config.check_ptau_curve(&ptau_header.curve)?;
+
+    plonk_setup(&config.ptau, &config.r1cs, &config.out, progress).await
+}
+
+/// Same as [`plonk_setup`], but over already opened ptau/r1cs files and their section tables.
+pub async fn plonk_setup_fd(
+    fd_ptau: BinFile,
+    sections_ptau: &HashMap<u32, Vec<Section>>,
+    fd_r1cs: BinFile,
+    sections_r1cs: &HashMap<u32, Vec<Section>>,
+    fd_zkey: &mut BinFile,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    setup_fd(fd_ptau, sections_ptau, fd_r1cs, sections_r1cs, fd_zkey, progress, None).await
+}
+
+async fn setup_fd(
+    fd_ptau: BinFile,
+    sections_ptau: &HashMap<u32, Vec<Section>>,
+    fd_r1cs: BinFile,
+    sections_r1cs: &HashMap<u32, Vec<Section>>,
+    fd_zkey: &mut BinFile,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    let mut ptau_file = PTauFile::from(fd_ptau);
+    let ptau_header = ptau_file.read_header(sections_ptau).await?;
+    let checkpoint = process_circuit(&ptau_header, fd_r1cs, sections_r1cs, progress, cancel).await?;
+    setup_from_checkpoint(&mut ptau_file, sections_ptau, ptau_header, &checkpoint, fd_zkey, progress, cancel).await
+}
+
+/// Same as [`plonk_setup_fd`], returning the zkey as bytes instead of writing it to a
+/// file, for targets without a filesystem or callers that pipe the zkey elsewhere.
+pub async fn plonk_setup_in_memory(
+    fd_ptau: BinFile,
+    sections_ptau: &HashMap<u32, Vec<Section>>,
+    fd_r1cs: BinFile,
+    sections_r1cs: &HashMap<u32, Vec<Section>>,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<BigBuffer> {
+    let mut fd_zkey = BinFile::create_in(BigBufferStorage::new(), "zkey", 1, ZKEY_PL_N_SECTIONS).await?;
+    plonk_setup_fd(fd_ptau, sections_ptau, fd_r1cs, sections_r1cs, &mut fd_zkey, progress).await?;
+    fd_zkey.flush().await?;
+    Ok(fd_zkey.into_storage::<BigBufferStorage>().unwrap().into_inner())
+}
+
+/// Like [`plonk_setup`], but saves the processed gates to `checkpoint_path` before the
+/// FFT and commitment phase. If `checkpoint_path` already holds a checkpoint, the r1cs is
+/// not read again and the setup resumes from it. The checkpoint is left in place.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn plonk_setup_with_checkpoint(
+    ptau_path: &str,
+    r1cs_path: &str,
+    out_path: &str,
+    checkpoint_path: &str,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    let (fd_ptau, sections_ptau) = file::read_bin_file(ptau_path, "ptau", PTAU_MAX_VERSION).await?;
+    let mut ptau_file = PTauFile::from(fd_ptau);
+    let ptau_header = ptau_file.read_header(&sections_ptau).await?;
+
+    let checkpoint = if tokio::fs::try_exists(checkpoint_path).await? {
+        crate::checkpoint::read_checkpoint(checkpoint_path).await?
+    } else {
+        let (fd_r1cs, sections_r1cs) = file::read_bin_file(r1cs_path, "r1cs", 1).await?;
+        let checkpoint = process_circuit(&ptau_header, fd_r1cs, &sections_r1cs, progress, None).await?;
+        crate::checkpoint::write_checkpoint(checkpoint_path, &checkpoint).await?;
+        checkpoint
+    };
+
+    let mut fd_zkey = BinFile::create(out_path, "zkey", 1, ZKEY_PL_N_SECTIONS).await?;
+    setup_from_checkpoint(&mut ptau_file, &sections_ptau, ptau_header, &checkpoint, &mut fd_zkey, progress, None).await
+}
+
+/// Reads the r1cs and converts it to PLONK gates over the ptau's curve.
+///
+/// The header is checked first: a wrong prime, or a circuit whose gates cannot fit the
+/// ptau even before addition gates, fails without reading the constraints.
+async fn process_circuit(
+    ptau_header: &PtauHeader,
+    mut fd_r1cs: BinFile,
+    sections_r1cs: &HashMap<u32, Vec<Section>>,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<SetupCheckpoint> {
+    let curve = &ptau_header.curve;
+    let header = file::read_r1cs_header(&mut fd_r1cs, sections_r1cs).await?;
+
+    // Constraint processing reduces coefficients into the ptau's scalar field, so the
+    // r1cs must be defined over that same field.
+    header.check_field(curve)?;
+
+    let min_gates = header.min_plonk_gates();
+    let (min_power, _) = compute_domain_power(min_gates);
+    if min_power > ptau_header.power {
+        return Err(SnarkError::CircuitTooBig {
+            cir_power: min_power,
+            power: ptau_header.power,
+            n_constraints: min_gates,
+        });
+    }
+
+    let r1cs = file::read_r1cs_fd(&mut fd_r1cs, sections_r1cs).await?;
+
+    let (constraints, additions, stats) = match cancel {
+        Some(cancel) => r1cs::process_constraints_cancellable(&r1cs, progress, cancel)?,
+        None => r1cs::process_constraints(&r1cs, progress)?,
+    };
+    // Sized from the gate count, never from the last gate index, so a circuit with no
+    // constraints (or no gates at all) gets the minimal power 3.
+    let (domain_power, _) = compute_domain_power(constraints.len());
+
+    Ok(SetupCheckpoint {
+        prime: r1cs.header.prime,
+        domain_power,
+        constraints,
+        additions,
+        stats,
+    })
+}
+
+/// Everything after constraint processing: the zkey sections from the additions on.
+async fn setup_from_checkpoint(
+    ptau_file: &mut PTauFile,
+    sections_ptau: &HashMap<u32, Vec<Section>>,
+    ptau_header: PtauHeader,
+    checkpoint: &SetupCheckpoint,
+    fd_zkey: &mut BinFile,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    let PtauHeader { curve, power, .. } = ptau_header;
+
+    if checkpoint.prime != curve.r {
+        return Err(SnarkError::PrimeMismatch {
+            r1cs: checkpoint.prime.clone(),
+            ptau: curve.r.clone(),
+        });
+    }
+
+    let n8r = curve.n8r;
+    let SetupCheckpoint {
+        constraints: plonk_constraints,
+        additions: plonk_additions,
+        stats,
+        ..
+    } = checkpoint;
+
+    // A checkpoint read from disk may claim any power; the domain needs a root of unity.
+    let domain = Domain::with_power(&curve, checkpoint.domain_power)?;
+    let cir_power = domain.log_size;
+    let domain_size = domain.size;
+
+    if cir_power > power {
+        return Err(SnarkError::CircuitTooBig {
+            cir_power,
+            power,
+            n_constraints: plonk_constraints.len(),
+        });
+    }
+
+    // 4. Check that section 12 holds the Lagrange points for the circuit's domain
+    crate::ptau_file::check_lagrange_points(sections_ptau, &curve, cir_power)?;
+
+    write_additions(fd_zkey, ZKEY_PL_ADDITIONS_SECTION, "Additions", n8r, plonk_additions, progress).await?;
+
+    write_signal_maps(fd_zkey, plonk_constraints, progress).await?;
+
+    // write_p4 extends each selector to 4 * domain_size, so the engine needs two extra bits.
+    let max_bits = cir_power as usize + 2;
+    let fft_engine = FftEngine::<Bn128>::new(max_bits)?;
+
+    check_cancelled(cancel)?;
+    let selectors = compute_selectors(plonk_constraints, domain_size, &fft_engine);
+    write_selectors(fd_zkey, &selectors, n8r, &fft_engine, progress, cancel).await?;
+
+    check_cancelled(cancel)?;
+    let sigma = build_permutation(plonk_constraints, domain_size, &domain.k1, &domain.k2, &domain.generator);
+    let sigma = sigma.map(|evaluations| fft_engine.ifft(&evaluations));
+    write_sigma_coefficients(fd_zkey, &sigma, n8r, &fft_engine, progress, cancel).await?;
+
+    check_cancelled(cancel)?;
+    write_lagrange(fd_zkey, stats.n_public, domain_size, n8r, &fft_engine, cancel).await?;
+
+    // The prover needs domain_size + 6 powers of tau to commit to the blinded polynomials.
+    let tau_g1 = ptau_file.read_tau_g1(sections_ptau, &curve, domain_size + 6).await?;
+    fd_zkey.start_write_section(ZKEY_PL_PTAU_SECTION).await?;
+    fd_zkey.write_bytes(&tau_g1).await?;
+    fd_zkey.end_write_section().await?;
+
+    let commitments = compute_commitments(&curve, &tau_g1, &selectors, &sigma, progress, cancel)?;
+
+    let x2 = ptau_file.read_ptau_g2_point(sections_ptau, &curve).await?;
+
+    // Like snarkjs, the headers go last.
+    let header = PlonkHeader {
+        n8q: curve.n8q as u32,
+        q: curve.q.clone(),
+        n8r: n8r as u32,
+        r: curve.r.clone(),
+        n_vars: stats.final_n_vars,
+        n_public: stats.n_public as u32,
+        domain_size: domain_size as u32,
+        n_additions: plonk_additions.len() as u32,
+        n_constraints: plonk_constraints.len() as u32,
+        k1: domain.k1.clone(),
+        k2: domain.k2.clone(),
+        commitments,
+        x2,
+    };
+    write_zkey_header(fd_zkey, &header).await?;
+
+    fd_zkey.flush().await?;
+    Ok(())
+}
+
+/// Writes the protocol marker (section 1) and the PLONK header (section 2).
+pub async fn write_zkey_header(fd: &mut BinFile, header: &PlonkHeader) -> Result<()> {
+    crate::zkey::write_zkey_protocol(fd, crate::zkey::ZkeyProtocol::Plonk).await?;
+
+    let n8q = header.n8q as usize;
+    let n8r = header.n8r as usize;
+
+    fd.start_write_section(ZKEY_PL_HEADER_SECTION).await?;
+    fd.write_u32(header.n8q).await?;
+    fd.write_bytes(&to_n8r_bytes(&header.q.to_bytes_le(), n8q)).await?;
+    fd.write_u32(header.n8r).await?;
+    fd.write_bytes(&to_n8r_bytes(&header.r.to_bytes_le(), n8r)).await?;
+    fd.write_u32(header.n_vars).await?;
+    fd.write_u32(header.n_public).await?;
+    fd.write_u32(header.domain_size).await?;
+    fd.write_u32(header.n_additions).await?;
+    fd.write_u32(header.n_constraints).await?;
+    fd.write_field(&header.k1, n8r).await?;
+    fd.write_field(&header.k2, n8r).await?;
+    for commitment in &header.commitments {
+        fd.write_bytes(&to_n8r_bytes(commitment, 2 * n8q)).await?;
+    }
+    fd.write_bytes(&to_n8r_bytes(&header.x2, 4 * n8q)).await?;
+    fd.end_write_section().await?;
+
+    Ok(())
+}
+
+/// Width of a BN128 scalar in bytes; the Montgomery radix is `R = 2^(8 * FR_N8)`.
+const FR_N8: usize = 32;
+
+fn montgomery_radix() -> Element<Bn128> {
+    static R: OnceLock<BigUint> = OnceLock::new();
+    let r = R.get_or_init(|| (BigUint::from(1u32) << (8 * FR_N8)) % Bn128::order());
+    Element::from(r.clone())
+}
+
+/// `R^-1 mod r`, inverted once: every decoded element is multiplied by it.
+fn montgomery_radix_inv() -> Element<Bn128> {
+    static R_INV: OnceLock<BigUint> = OnceLock::new();
+    let r_inv = R_INV.get_or_init(|| {
+        montgomery_radix().multiplicative_inverse().to_biguint().clone()
+    });
+    Element::from(r_inv.clone())
+}
+
+/// Serializes field elements the way snarkjs stores them: `x * R mod r`, little-endian.
+pub trait ToMontgomeryBytes {
+    fn as_montgomery_bytes(&self) -> Vec<u8>;
+}
+
+impl ToMontgomeryBytes for Element<Bn128> {
+    fn as_montgomery_bytes(&self) -> Vec<u8> {
+        let mont = self * montgomery_radix();
+        to_n8r_bytes(&mont.to_biguint().to_bytes_le(), FR_N8)
+    }
+}
+
+/// Inverse of [`ToMontgomeryBytes`]: reads a little-endian Montgomery-form element.
+/// Values at or above the modulus fail with [`SnarkError::FieldNotReduced`], as
+/// snarkjs never writes them.
+pub trait FromMontgomeryBytes: Sized {
+    fn from_montgomery_bytes(bytes: &[u8]) -> Result<Self>;
+}
+
+impl FromMontgomeryBytes for Element<Bn128> {
+    fn from_montgomery_bytes(bytes: &[u8]) -> Result<Self> {
+        let raw = BigUint::from_bytes_le(bytes);
+        if raw >= Bn128::order() {
+            return Err(SnarkError::FieldNotReduced);
+        }
+        Ok(Element::from(raw) * montgomery_radix_inv())
+    }
+}
+
+/// Additions buffered per write by [`write_additions`].
+pub const ADDITIONS_BATCH: usize = 4096;
+
+pub async fn write_additions(
+    fd: &mut BinFile,
+    section_num: u32,
+    name: &str,
+    n8r: usize,
+    plonk_additions: &[PlonkAddition],
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    write_additions_batched(fd, section_num, name, n8r, plonk_additions, ADDITIONS_BATCH, progress).await
+}
+
+/// Same as [`write_additions`], encoding `batch` additions into one buffer per write.
+/// The section bytes do not depend on `batch`, which must not be zero.
+pub async fn write_additions_batched(
+    fd: &mut BinFile,
+    section_num: u32,
+    name: &str,
+    n8r: usize,
+    plonk_additions: &[PlonkAddition],
+    batch: usize,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    if batch == 0 {
+        return Err(SnarkError::ZeroSize("batch"));
+    }
+    fd.start_write_section(section_num).await?;
+
+    let entry_size = 2 * 4 + 2 * n8r;
+    let mut buffer = Vec::with_capacity(batch.min(plonk_additions.len()) * entry_size);
+    for (i, addition) in plonk_additions.iter().enumerate() {
+        encode_addition(&mut buffer, addition, n8r);
+
+        if buffer.len() == batch * entry_size {
+            fd.write_bytes(&buffer).await?;
+            buffer.clear();
+        }
+
+        if i % 1_000_000 == 0
+            && let Some(progress) = progress
+        {
+            progress(name, i, plonk_additions.len());
+        }
+    }
+    fd.write_bytes(&buffer).await?;
+
+    fd.end_write_section().await?;
+    Ok(())
+}
+
+/// The additions section body without any I/O, for callers that keep the setup in
+/// memory: per addition, both signals as `u32` and both coefficients in Montgomery form.
+pub fn serialize_additions(plonk_additions: &[PlonkAddition], n8r: usize) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(plonk_additions.len() * (2 * 4 + 2 * n8r));
+    for addition in plonk_additions {
+        encode_addition(&mut buffer, addition, n8r);
+    }
+    buffer
+}
+
+fn encode_addition(buffer: &mut Vec<u8>, (a, b, v1, v2): &PlonkAddition, n8r: usize) {
+    buffer.extend_from_slice(&a.to_le_bytes());
+    buffer.extend_from_slice(&b.to_le_bytes());
+    buffer.extend_from_slice(&to_n8r_bytes(&v1.as_montgomery_bytes(), n8r));
+    buffer.extend_from_slice(&to_n8r_bytes(&v2.as_montgomery_bytes(), n8r));
+}
+
+pub(crate) fn to_n8r_bytes(raw: &[u8], n8r: usize) -> Vec<u8> {
+    let mut out = vec![0u8; n8r];
+    let len = raw.len().min(n8r);
+    out[..len].copy_from_slice(&raw[..len]);
+    out
+}
+
+/// Writes the a/b/c signal columns of every gate into the A, B and C map sections.
+///
+/// Public input gates only use their `a` wire: `b` and `c` point at signal 0 and are
+/// written as such, so every column has exactly one entry per gate.
+pub async fn write_signal_maps(
+    fd: &mut BinFile,
+    constraints: &[PlonkConstraint],
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    let columns = [
+        (ZKEY_PL_A_MAP_SECTION, "Amap"),
+        (ZKEY_PL_B_MAP_SECTION, "Bmap"),
+        (ZKEY_PL_C_MAP_SECTION, "Cmap"),
+    ];
+    for (pos, (section_num, name)) in columns.into_iter().enumerate() {
+        write_witness_map(fd, section_num, constraints, pos, name, progress).await?;
+    }
+    Ok(())
+}
+
+pub async fn write_witness_map(
+    fd: &mut BinFile,
+    section_num: u32,
+    constraints: &[PlonkConstraint],
+    pos_constraint: usize,
+    name: &str,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    fd.start_write_section(section_num).await?;
+
+    for (i, constraint) in constraints.iter().enumerate() {
+        let val = match pos_constraint {
+            0 => constraint.0,
+            1 => constraint.1,
+            2 => constraint.2,
+            _ => return Err(SnarkError::InvalidColumn(pos_constraint)),
+        };
+        fd.write_u32(val).await?;
+
+        if i % 1_000_000 == 0
+            && let Some(progress) = progress
+        {
+            progress(name, i, constraints.len());
+        }
+    }
+
+    fd.end_write_section().await?;
+    Ok(())
+}
+
+/// Selector section ids and names, in the (qm, ql, qr, qo, qc) order of a [`PlonkConstraint`].
+pub(crate) const SELECTORS: [(u32, &str); 5] = [
+    (ZKEY_PL_QM_SECTION, "Qm"),
+    (ZKEY_PL_QL_SECTION, "Ql"),
+    (ZKEY_PL_QR_SECTION, "Qr"),
+    (ZKEY_PL_QO_SECTION, "Qo"),
+    (ZKEY_PL_QC_SECTION, "Qc"),
+];
+
+/// The Qm, Ql, Qr, Qo and Qc selectors in Lagrange basis: their evaluations over a
+/// domain of `domain_size` points, with gate `i` at the `i`-th point and unused points
+/// zero. No FFT is involved.
+pub fn selectors_lagrange(
+    plonk_constraints: &[PlonkConstraint],
+    domain_size: usize,
+) -> [Vec<Element<Bn128>>; 5] {
+    let mut evaluations: [Vec<Element<Bn128>>; 5] =
+        std::array::from_fn(|_| vec![Element::<Bn128>::zero(); domain_size]);
+
+    for (i, (_, _, _, qm, ql, qr, qo, qc)) in plonk_constraints.iter().enumerate() {
+        for (evals, q) in evaluations.iter_mut().zip([qm, ql, qr, qo, qc]) {
+            evals[i] = q.clone();
+        }
+    }
+
+    evaluations
+}
+
+/// Interpolates the Qm, Ql, Qr, Qo and Qc selector polynomials over a domain of
+/// `domain_size` points, returning their coefficients.
+///
+/// Gate `i` fixes the evaluation at the `i`-th domain point; unused points are zero.
+pub fn compute_selectors(
+    plonk_constraints: &[PlonkConstraint],
+    domain_size: usize,
+    fft: &FftEngine<Bn128>,
+) -> [Vec<Element<Bn128>>; 5] {
+    selectors_lagrange(plonk_constraints, domain_size).map(|evals| fft.ifft(&evals))
+}
+
+/// Writes each selector into its zkey section: the `domain_size` coefficients followed
+/// by the evaluations over the 4x extended domain.
+pub async fn write_selectors(
+    fd: &mut BinFile,
+    selectors: &[Vec<Element<Bn128>>; 5],
+    n8r: usize,
+    fft: &FftEngine<Bn128>,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    for (coefficients, (section_num, name)) in selectors.iter().zip(SELECTORS) {
+        fd.start_write_section(section_num).await?;
+        write_p4(fd, coefficients, n8r, fft, cancel).await?;
+        fd.end_write_section().await?;
+
+        if let Some(progress) = progress {
+            progress(name, 1, 1);
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`write_selectors`], but serializes each selector into a [`BigBuffer`] and
+/// writes it page by page. Only one selector is serialized at a time.
+pub async fn write_selectors_streamed(
+    fd: &mut BinFile,
+    selectors: &[Vec<Element<Bn128>>; 5],
+    n8r: usize,
+    fft: &FftEngine<Bn128>,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    for (coefficients, (section_num, name)) in selectors.iter().zip(SELECTORS) {
+        let buffer = p4_buffer(coefficients, n8r, fft);
+        fd.start_write_section(section_num).await?;
+        fd.write_big_buffer(&buffer).await?;
+        fd.end_write_section().await?;
+
+        if let Some(progress) = progress {
+            progress(name, 1, 1);
+        }
+    }
+    Ok(())
+}
+
+/// The identity permutation the copy constraints start from: row `i` of the a, b and c
+/// columns holds `w^i`, `k1 * w^i` and `k2 * w^i`, `w` being the domain generator `root`.
+pub fn identity_permutation(
+    domain_size: usize,
+    k1: &Element<Bn128>,
+    k2: &Element<Bn128>,
+    root: &Element<Bn128>,
+) -> [Vec<Element<Bn128>>; 3] {
+    let mut columns: [Vec<Element<Bn128>>; 3] = Default::default();
+    let mut w = Element::<Bn128>::one();
+    for _ in 0..domain_size {
+        columns[0].push(w.clone());
+        columns[1].push(k1 * &w);
+        columns[2].push(k2 * &w);
+        w *= root;
+    }
+    columns
+}
+
+/// Builds the evaluations of the three sigma polynomials encoding the copy constraints.
+///
+/// Position `i` of column `j` is identified with its entry in [`identity_permutation`].
+/// All positions holding the same signal are linked in a cycle, visiting gates in order
+/// and a, b, c within a gate: each position maps to the previous occurrence of its
+/// signal, and the first occurrence to the last one.
+/// A signal used only once maps to itself. Rows past the last gate are padded with signal 0.
+pub fn build_permutation(
+    plonk_constraints: &[PlonkConstraint],
+    domain_size: usize,
+    k1: &Element<Bn128>,
+    k2: &Element<Bn128>,
+    root: &Element<Bn128>,
+) -> [Vec<Element<Bn128>>; 3] {
+    // Positions are flattened as `column * domain_size + row`.
+    let identity = identity_permutation(domain_size, k1, k2, root).concat();
+    let mut sigma = vec![Element::<Bn128>::zero(); 3 * domain_size];
+    let mut first_pos: HashMap<u32, usize> = HashMap::new();
+    let mut last_value: HashMap<u32, Element<Bn128>> = HashMap::new();
+
+    for i in 0..domain_size {
+        let signals = plonk_constraints.get(i).map_or([0; 3], |c| [c.0, c.1, c.2]);
+
+        for (column, signal) in signals.into_iter().enumerate() {
+            let pos = column * domain_size + i;
+            match last_value.insert(signal, identity[pos].clone()) {
+                Some(previous) => sigma[pos] = previous,
+                None => {
+                    first_pos.insert(signal, pos);
+                }
+            }
+        }
+    }
+
+    for (signal, pos) in first_pos {
+        sigma[pos] = last_value.remove(&signal).unwrap();
+    }
+
+    let mut columns = sigma.chunks_exact(domain_size).map(<[_]>::to_vec);
+    std::array::from_fn(|_| columns.next().unwrap())
+}
+
+/// Interpolates the sigma polynomials from their evaluations and writes them into the
+/// sigma section, each as coefficients followed by the 4x extended evaluations.
+pub async fn write_sigma(
+    fd: &mut BinFile,
+    sigma: &[Vec<Element<Bn128>>; 3],
+    n8r: usize,
+    fft: &FftEngine<Bn128>,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<()> {
+    let coefficients = std::array::from_fn(|i| fft.ifft(&sigma[i]));
+    write_sigma_coefficients(fd, &coefficients, n8r, fft, progress, None).await
+}
+
+/// Same as [`write_sigma`] for polynomials already in coefficient form, failing with
+/// [`SnarkError::Cancelled`] soon after `cancel` is set.
+pub async fn write_sigma_coefficients(
+    fd: &mut BinFile,
+    sigma: &[Vec<Element<Bn128>>; 3],
+    n8r: usize,
+    fft: &FftEngine<Bn128>,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    fd.start_write_section(ZKEY_PL_SIGMA_SECTION).await?;
+    for (i, coefficients) in sigma.iter().enumerate() {
+        write_p4(fd, coefficients, n8r, fft, cancel).await?;
+
+        if let Some(progress) = progress {
+            progress("Sigma", i + 1, sigma.len());
+        }
+    }
+    fd.end_write_section().await?;
+    Ok(())
+}
+
+/// Commitments to Qm, Ql, Qr, Qo, Qc, S1, S2 and S3, as snarkjs `expTau` computes them:
+/// the MSM of each polynomial's coefficients against the powers of tau in `tau_g1` (ptau
+/// section 2 bytes), encoded like the header points. One [`MsmContext`] serves all eight.
+///
+/// With `cancel`, each MSM polls it once per Pippenger window.
+pub fn compute_commitments(
+    curve: &Curve,
+    tau_g1: &[u8],
+    selectors: &[Vec<Element<Bn128>>; 5],
+    sigma: &[Vec<Element<Bn128>>; 3],
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<[Vec<u8>; 8]> {
+    let polynomials: Vec<&Vec<Element<Bn128>>> = selectors.iter().chain(sigma).collect();
+    let n_points = polynomials.iter().map(|p| p.len()).max().unwrap_or(0);
+    let points = tau_g1
+        .chunks_exact(2 * curve.n8q)
+        .take(n_points)
+        .map(|point| G1Affine::from_montgomery_bytes(curve, point))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut context = MsmContext::new();
+    let mut commitments: [Vec<u8>; 8] = Default::default();
+    for (i, (commitment, coefficients)) in commitments.iter_mut().zip(polynomials).enumerate() {
+        let points = &points[..coefficients.len().min(points.len())];
+        let point = match cancel {
+            Some(cancel) => {
+                msm_with_context_cancellable(&mut context, points, coefficients, cancel)?
+            }
+            None => msm_with_context(&mut context, points, coefficients)?,
+        };
+        *commitment = point.to_affine().to_montgomery_bytes(curve);
+
+        if let Some(progress) = progress {
+            progress("Commitments", i + 1, 8);
+        }
+    }
+    Ok(commitments)
+}
+
+/// Writes the Lagrange basis polynomials of the public inputs into section 13 (at least one,
+/// as snarkjs does for circuits without public inputs).
+pub async fn write_lagrange(
+    fd: &mut BinFile,
+    n_public: usize,
+    domain_size: usize,
+    n8r: usize,
+    fft: &FftEngine<Bn128>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    fd.start_write_section(ZKEY_PL_LAGRANGE_SECTION).await?;
+    for i in 0..n_public.max(1) {
+        let mut evaluations = vec![Element::<Bn128>::zero(); domain_size];
+        evaluations[i] = Element::one();
+        write_p4(fd, &fft.ifft(&evaluations), n8r, fft, cancel).await?;
+    }
+    fd.end_write_section().await?;
+    Ok(())
+}
+
+/// Writes a polynomial's coefficients followed by its evaluations over four times its
+/// domain. `cancel` is polled after the FFT and before each element written.
+pub async fn write_p4(
+    fd: &mut BinFile,
+    coefficients: &[Element<Bn128>],
+    n8r: usize,
+    fft: &FftEngine<Bn128>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    let domain_size = coefficients.len();
+    let mut q4_input = vec![Element::<Bn128>::zero(); domain_size * 4];
+    q4_input[..domain_size].clone_from_slice(coefficients);
+
+    let q4_fft = fft.fft(&q4_input);
+
+    for elem in coefficients.iter().chain(&q4_fft) {
+        check_cancelled(cancel)?;
+        fd.write_field(elem, n8r).await?;
+    }
+
+    Ok(())
+}
+
+/// The bytes [`write_p4`] writes, in a paged buffer. The extended evaluations are freed
+/// once serialized.
+pub fn p4_buffer(coefficients: &[Element<Bn128>], n8r: usize, fft: &FftEngine<Bn128>) -> BigBuffer {
+    let domain_size = coefficients.len();
+    let mut q4_input = vec![Element::<Bn128>::zero(); domain_size * 4];
+    q4_input[..domain_size].clone_from_slice(coefficients);
+    let q4_fft = fft.fft(&q4_input);
+    drop(q4_input);
+
+    let mut buffer = BigBuffer::new(5 * domain_size * n8r);
+    for (i, elem) in coefficients.iter().chain(&q4_fft).enumerate() {
+        buffer.set(&to_n8r_bytes(&elem.as_montgomery_bytes(), n8r), i * n8r);
+    }
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ec::G1;
+    use crate::fft::pow_u64;
+    use std::collections::HashSet;
+    use tempfile::{NamedTempFile, tempdir};
+
+    #[tokio::test]
+    async fn test_commitments_match_naive_sum() -> Result<()> {
+        let dir = tempdir()?;
+        let out = dir.path().join("circuit.zkey");
+        let out = out.to_str().unwrap();
+        plonk_setup("src/artifacts/pot8.ptau", "src/artifacts/small.r1cs", out, None).await?;
+        let zkey = crate::zkey::read_zkey(out).await?;
+        let domain_size = zkey.header.domain_size as usize;
+
+        let curve = Curve::new();
+        let (mut fd, sections) = file::read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let tau_g1 = file::read_section(&mut fd, &sections, 2, None, Some(64 * domain_size as u64)).await?;
+        let points = tau_g1
+            .chunks(64)
+            .map(|point| G1Affine::from_montgomery_bytes(&curve, point))
+            .collect::<Result<Vec<_>>>()?;
+        let naive = |coefficients: &[Element<Bn128>]| {
+            let sum = coefficients
+                .iter()
+                .zip(&points)
+                .fold(G1::identity(), |acc, (c, p)| acc.add(&G1::from(p).mul(c)));
+            sum.to_affine().to_montgomery_bytes(&curve)
+        };
+
+        for (commitment, coefficients) in zkey.header.commitments.iter().zip(&zkey.selectors) {
+            assert_eq!(commitment, &naive(coefficients));
+        }
+
+        // S1 opens the sigma section, ahead of its 4x evaluations.
+        let (mut fd, sections) = file::read_bin_file(out, "zkey", 1).await?;
+        let sigma = file::read_section(&mut fd, &sections, ZKEY_PL_SIGMA_SECTION, None, None).await?;
+        let s1: Vec<Element<Bn128>> = sigma[..32 * domain_size]
+            .chunks(32)
+            .map(Element::from_montgomery_bytes)
+            .collect::<Result<_>>()?;
+        assert_eq!(zkey.header.commitments[5], naive(&s1));
+        assert!(zkey.header.commitments[5].iter().any(|&b| b != 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_setup_removes_zkey() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("circuit.zkey");
+        let out = out.to_str().unwrap();
+        let ptau = "src/artifacts/pot8.ptau";
+        let r1cs = "src/artifacts/small.r1cs";
+
+        // Set before the constraints are processed: no stage reports progress.
+        let cancel = std::sync::atomic::AtomicBool::new(true);
+        let stages = std::cell::RefCell::new(Vec::new());
+        let progress = |stage: &str, _: usize, _: usize| stages.borrow_mut().push(stage.to_string());
+        let result = plonk_setup_cancellable(ptau, r1cs, out, Some(&progress), &cancel).await;
+        assert!(matches!(result, Err(SnarkError::Cancelled)));
+        assert!(stages.borrow().is_empty());
+        assert!(!std::path::Path::new(out).exists());
+
+        // Never set: same zkey as plonk_setup.
+        cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+        plonk_setup_cancellable(ptau, r1cs, out, None, &cancel).await?;
+        let expected = dir.path().join("expected.zkey");
+        plonk_setup(ptau, r1cs, expected.to_str().unwrap(), None).await?;
+        assert_eq!(std::fs::read(out)?, std::fs::read(expected)?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_during_polynomials_and_commitments() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("circuit.zkey");
+        let out = out.to_str().unwrap();
+
+        // Set from the progress callback once `stop_at` reports: the next selector
+        // write or commitment MSM must notice it, so no later stage reports.
+        for (stop_at, done) in [("Qm", 1), ("Sigma", 1), ("Commitments", 1)] {
+            let cancel = std::sync::atomic::AtomicBool::new(false);
+            let stages = std::cell::RefCell::new(Vec::new());
+            let progress = |stage: &str, i: usize, _: usize| {
+                stages.borrow_mut().push((stage.to_string(), i));
+                if stage == stop_at && i == done {
+                    cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+            };
+            let result = plonk_setup_cancellable(
+                "src/artifacts/pot8.ptau",
+                "src/artifacts/small.r1cs",
+                out,
+                Some(&progress),
+                &cancel,
+            )
+            .await;
+            assert!(matches!(result, Err(SnarkError::Cancelled)), "{}", stop_at);
+            assert_eq!(stages.borrow().last(), Some(&(stop_at.to_string(), done)));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prime_mismatch_aborts_before_processing() -> Result<()> {
+        let r1cs_path = "src/artifacts/small.r1cs";
+        let (_, sections) = file::read_bin_file(r1cs_path, "r1cs", 1).await?;
+        // The prime follows the u32 field size at the start of the header section.
+        let prime_offset = sections[&1][0].offset as usize + 4;
+        let mut bytes = std::fs::read(r1cs_path)?;
+        bytes[prime_offset] ^= 1;
+
+        let (fd_ptau, sections_ptau) = file::read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let (fd_r1cs, sections_r1cs) = file::read_bin_file_fd(BinFile::from_bytes(bytes), "r1cs", 1).await?;
+        let mut fd_zkey = BinFile::from_bytes(Vec::new());
+
+        let stages = std::cell::RefCell::new(Vec::new());
+        let progress = |stage: &str, _: usize, _: usize| stages.borrow_mut().push(stage.to_string());
+        let result =
+            plonk_setup_fd(fd_ptau, &sections_ptau, fd_r1cs, &sections_r1cs, &mut fd_zkey, Some(&progress))
+                .await;
+
+        assert!(matches!(result, Err(SnarkError::PrimeMismatch { .. })));
+        assert!(stages.borrow().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_too_big_fails_before_processing() -> Result<()> {
+        let dir = tempdir()?;
+        let ptau_path = dir.path().join("pot3.ptau");
+        let ptau_path = ptau_path.to_str().unwrap();
+        crate::ptau_file::truncate_ptau("src/artifacts/pot8.ptau", ptau_path, 3).await?;
+
+        let (fd_ptau, sections_ptau) = file::read_bin_file(ptau_path, "ptau", 1).await?;
+        let (fd_r1cs, sections_r1cs) = file::read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let mut fd_zkey = BinFile::from_bytes(Vec::new());
+
+        let stages = std::cell::RefCell::new(Vec::new());
+        let progress = |stage: &str, _: usize, _: usize| stages.borrow_mut().push(stage.to_string());
+        let result =
+            plonk_setup_fd(fd_ptau, &sections_ptau, fd_r1cs, &sections_r1cs, &mut fd_zkey, Some(&progress))
+                .await;
+
+        assert!(matches!(result, Err(SnarkError::CircuitTooBig { power: 3, .. })));
+        assert!(stages.borrow().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_setup_resumes_from_checkpoint() -> Result<()> {
+        let (ptau_path, r1cs_path) = ("src/artifacts/pot8.ptau", "src/artifacts/small.r1cs");
+        let dir = tempdir()?;
+        let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+        let checkpoint_path = path("setup.pchk");
+
+        plonk_setup(ptau_path, r1cs_path, &path("direct.zkey"), None).await?;
+        plonk_setup_with_checkpoint(ptau_path, r1cs_path, &path("first.zkey"), &checkpoint_path, None).await?;
+        assert!(std::fs::exists(&checkpoint_path)?);
+        // The r1cs is not needed once the checkpoint exists.
+        plonk_setup_with_checkpoint(ptau_path, "missing.r1cs", &path("resumed.zkey"), &checkpoint_path, None)
+            .await?;
+
+        let direct = std::fs::read(path("direct.zkey"))?;
+        assert_eq!(std::fs::read(path("first.zkey"))?, direct);
+        assert_eq!(std::fs::read(path("resumed.zkey"))?, direct);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_additions_batch_size_does_not_change_output() -> Result<()> {
+        let additions: Vec<PlonkAddition> = (0..10u32)
+            .map(|i| (i, i + 1, Element::from(i as u64 * 31 + 2), -Element::<Bn128>::from(i as u64)))
+            .collect();
+
+        let mut outputs = Vec::new();
+        for batch in [1, 3, 10, 64] {
+            let mut fd = BinFile::create_in(std::io::Cursor::new(Vec::new()), "zkey", 1, 1).await?;
+            write_additions_batched(&mut fd, ZKEY_PL_ADDITIONS_SECTION, "Additions", 32, &additions, batch, None)
+                .await?;
+            fd.flush().await?;
+            let (mut fd, sections) = file::read_bin_file_fd(fd, "zkey", 1).await?;
+            outputs.push(file::read_section(&mut fd, &sections, ZKEY_PL_ADDITIONS_SECTION, None, None).await?);
+        }
+
+        assert_eq!(outputs[0].len(), 10 * 72);
+        assert!(outputs.iter().all(|o| *o == outputs[0]));
+
+        let mut fd = BinFile::create_in(std::io::Cursor::new(Vec::new()), "zkey", 1, 1).await?;
+        let result =
+            write_additions_batched(&mut fd, ZKEY_PL_ADDITIONS_SECTION, "Additions", 32, &additions, 0, None).await;
+        assert!(matches!(result, Err(SnarkError::ZeroSize("batch"))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_serialize_additions_matches_section() -> Result<()> {
+        let additions: Vec<PlonkAddition> = (0..5u32)
+            .map(|i| (i + 3, i, -Element::<Bn128>::from(i as u64 + 1), Element::from(i as u64 * 7)))
+            .collect();
+
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut fd = BinFile::create(path, "zkey", 1, 1).await?;
+        write_additions(&mut fd, ZKEY_PL_ADDITIONS_SECTION, "Additions", 32, &additions, None).await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+        let section = file::read_section(&mut fd, &sections, ZKEY_PL_ADDITIONS_SECTION, None, None).await?;
+        assert_eq!(serialize_additions(&additions, 32), section);
+        assert!(serialize_additions(&[], 32).is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_setup_matches_file() -> Result<()> {
+        let (ptau_path, r1cs_path) = ("src/artifacts/pot8.ptau", "src/artifacts/small.r1cs");
+        let tmp = NamedTempFile::new()?;
+        plonk_setup(ptau_path, r1cs_path, tmp.path().to_str().unwrap(), None).await?;
+        let on_disk = std::fs::read(tmp.path())?;
+
+        let (fd_ptau, sections_ptau) = file::read_bin_file(ptau_path, "ptau", PTAU_MAX_VERSION).await?;
+        let (fd_r1cs, sections_r1cs) = file::read_bin_file(r1cs_path, "r1cs", 1).await?;
+        let zkey = plonk_setup_in_memory(fd_ptau, &sections_ptau, fd_r1cs, &sections_r1cs, None).await?;
+        assert_eq!(zkey.byte_length(), on_disk.len());
+        assert_eq!(zkey.slice(0, zkey.byte_length()), on_disk);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streamed_selectors_match_in_memory() -> Result<()> {
+        let domain_size = 8;
+        let selectors: [Vec<Element<Bn128>>; 5] =
+            std::array::from_fn(|s| (0..domain_size).map(|i| Element::from((s * 31 + i * i) as u64)).collect());
+        let fft = FftEngine::<Bn128>::new(5)?;
+
+        let mut sections_bytes = Vec::new();
+        for streamed in [false, true] {
+            let tmp = NamedTempFile::new()?;
+            let path = tmp.path().to_str().unwrap();
+            let mut fd = BinFile::create(path, "zkey", 1, 5).await?;
+            if streamed {
+                write_selectors_streamed(&mut fd, &selectors, 32, &fft, None).await?;
+            } else {
+                write_selectors(&mut fd, &selectors, 32, &fft, None, None).await?;
+            }
+            fd.flush().await?;
+
+            let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+            let mut bytes = Vec::new();
+            for (section_num, _) in SELECTORS {
+                bytes.push(file::read_section(&mut fd, &sections, section_num, None, None).await?);
+            }
+            sections_bytes.push(bytes);
+        }
+        assert_eq!(sections_bytes[0], sections_bytes[1]);
+        assert_eq!(sections_bytes[1][0].len(), 5 * domain_size * 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_montgomery_bytes_round_trip() -> Result<()> {
+        let values = [
+            Element::<Bn128>::zero(),
+            Element::one(),
+            Element::from(123456789u64),
+            -Element::<Bn128>::one(),
+        ];
+        for x in values {
+            let bytes = x.as_montgomery_bytes();
+            assert_eq!(bytes.len(), 32);
+            assert_eq!(Element::<Bn128>::from_montgomery_bytes(&bytes)?, x);
+        }
+        // One is stored as R mod r, not as 1.
+        assert_eq!(
+            BigUint::from_bytes_le(&Element::<Bn128>::one().as_montgomery_bytes()),
+            (BigUint::from(1u32) << 256) % Bn128::order()
+        );
+
+        // The modulus itself, and anything above it, is not a canonical encoding.
+        let r = Bn128::order();
+        for raw in [r.clone(), r + 1u32] {
+            let mut bytes = raw.to_bytes_le();
+            bytes.resize(32, 0);
+            assert!(matches!(
+                Element::<Bn128>::from_montgomery_bytes(&bytes),
+                Err(SnarkError::FieldNotReduced)
+            ));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_zkey_header_round_trip() -> Result<()> {
+        let header = PlonkHeader {
+            n8q: 32,
+            q: Curve::q(),
+            n8r: 32,
+            r: Curve::r(),
+            n_vars: 43,
+            n_public: 3,
+            domain_size: 128,
+            n_additions: 29,
+            n_constraints: 72,
+            k1: Element::from(2u64),
+            k2: Element::from(3u64),
+            commitments: std::array::from_fn(|i| vec![i as u8 + 1; 64]),
+            x2: vec![0xee; 128],
+        };
+
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut fd = BinFile::create(path, "zkey", 1, 2).await?;
+        write_zkey_header(&mut fd, &header).await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+        let protocol = file::read_section(&mut fd, &sections, ZKEY_HEADER_SECTION, None, None).await?;
+        assert_eq!(protocol, ZKEY_PROTOCOL_PLONK.to_le_bytes());
+
+        let buf = file::read_section(&mut fd, &sections, ZKEY_PL_HEADER_SECTION, None, None).await?;
+        assert_eq!(buf.len(), 4 + 32 + 4 + 32 + 5 * 4 + 2 * 32 + 8 * 64 + 128);
+
+        let mut pos = 0;
+        let mut take = |n: usize| {
+            pos += n;
+            &buf[pos - n..pos]
+        };
+        let u32_at = |b: &[u8]| u32::from_le_bytes(b.try_into().unwrap());
+
+        assert_eq!(u32_at(take(4)), header.n8q);
+        assert_eq!(BigUint::from_bytes_le(take(32)), header.q);
+        assert_eq!(u32_at(take(4)), header.n8r);
+        assert_eq!(BigUint::from_bytes_le(take(32)), header.r);
+        assert_eq!(u32_at(take(4)), header.n_vars);
+        assert_eq!(u32_at(take(4)), header.n_public);
+        assert_eq!(u32_at(take(4)), header.domain_size);
+        assert_eq!(u32_at(take(4)), header.n_additions);
+        assert_eq!(u32_at(take(4)), header.n_constraints);
+        assert_eq!(take(32), to_n8r_bytes(&header.k1.as_montgomery_bytes(), 32));
+        assert_eq!(take(32), to_n8r_bytes(&header.k2.as_montgomery_bytes(), 32));
+        for commitment in &header.commitments {
+            assert_eq!(take(64), &commitment[..]);
+        }
+        assert_eq!(take(128), &header.x2[..]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_signal_maps() -> Result<()> {
+        let (mut fd_r1cs, sections_r1cs) =
+            file::read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = file::read_r1cs_fd(&mut fd_r1cs, &sections_r1cs).await?;
+        let (constraints, _, stats) = r1cs::process_constraints(&r1cs, None)?;
+
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut fd = BinFile::create(path, "zkey", 1, 3).await?;
+        write_signal_maps(&mut fd, &constraints, None).await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+        let mut columns = Vec::new();
+        for section_num in [ZKEY_PL_A_MAP_SECTION, ZKEY_PL_B_MAP_SECTION, ZKEY_PL_C_MAP_SECTION] {
+            let buf = file::read_section(&mut fd, &sections, section_num, None, None).await?;
+            let column: Vec<u32> = buf
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            assert_eq!(column.len(), constraints.len());
+            columns.push(column);
+        }
+
+        let rows: Vec<_> = (0..constraints.len())
+            .map(|i| (columns[0][i], columns[1][i], columns[2][i]))
+            .collect();
+        for (row, constraint) in rows.iter().zip(&constraints) {
+            assert_eq!(*row, (constraint.0, constraint.1, constraint.2));
+        }
+        for (i, row) in rows.iter().take(stats.n_public).enumerate() {
+            assert_eq!(*row, (i as u32 + 1, 0, 0));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_selectors_interpolates_gates() -> Result<()> {
+        let (mut fd_r1cs, sections_r1cs) =
+            file::read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = file::read_r1cs_fd(&mut fd_r1cs, &sections_r1cs).await?;
+        let (constraints, _, _) = r1cs::process_constraints(&r1cs, None)?;
+
+        let bits = 7;
+        let domain_size = 1 << bits;
+        assert!(constraints.len() <= domain_size);
+        let fft = FftEngine::<Bn128>::new(bits)?;
+        let selectors = compute_selectors(&constraints, domain_size, &fft);
+
+        let zero = Element::<Bn128>::zero();
+        let mut x = Element::<Bn128>::one();
+        for i in 0..domain_size {
+            let gate = constraints.get(i);
+            let expected = [
+                gate.map_or(&zero, |g| &g.3),
+                gate.map_or(&zero, |g| &g.4),
+                gate.map_or(&zero, |g| &g.5),
+                gate.map_or(&zero, |g| &g.6),
+                gate.map_or(&zero, |g| &g.7),
+            ];
+            for (coefficients, expected) in selectors.iter().zip(expected) {
+                assert_eq!(coefficients.len(), domain_size);
+                let eval = coefficients
+                    .iter()
+                    .rev()
+                    .fold(Element::<Bn128>::zero(), |acc, c| acc * &x + c);
+                assert_eq!(&eval, expected, "gate {}", i);
+            }
+            x *= &fft.w[bits];
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_selectors_lagrange_interpolates_to_coefficients() -> Result<()> {
+        let (mut fd_r1cs, sections_r1cs) =
+            file::read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = file::read_r1cs_fd(&mut fd_r1cs, &sections_r1cs).await?;
+        let (constraints, _, _) = r1cs::process_constraints(&r1cs, None)?;
+
+        let domain_size = 128;
+        let fft = FftEngine::<Bn128>::new(7)?;
+        let lagrange = selectors_lagrange(&constraints, domain_size);
+        for (i, gate) in constraints.iter().enumerate() {
+            assert_eq!(lagrange[0][i], gate.3);
+            assert_eq!(lagrange[4][i], gate.7);
+        }
+        assert!(lagrange[4][constraints.len()..].iter().all(Element::is_zero));
+
+        let coefficients = compute_selectors(&constraints, domain_size, &fft);
+        for (evals, coefficients) in lagrange.iter().zip(&coefficients) {
+            assert_eq!(&fft.ifft(evals), coefficients);
+            assert_eq!(&fft.fft(coefficients), evals);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_permutation_is_a_permutation() {
+        let zero = Element::<Bn128>::zero;
+        let gate = |a: u32, b: u32, c: u32| (a, b, c, zero(), zero(), zero(), zero(), zero());
+        // Signal 4 appears once (self-cycle), signal 1 three times across columns.
+        let constraints = vec![gate(1, 0, 0), gate(1, 2, 3), gate(2, 1, 4)];
+
+        let bits = 2;
+        let domain_size = 1 << bits;
+        let fft = FftEngine::<Bn128>::new(bits).unwrap();
+        let root = &fft.w[bits];
+        let k1 = Element::<Bn128>::from(2u64);
+        let k2 = Element::<Bn128>::from(3u64);
+
+        let sigma = build_permutation(&constraints, domain_size, &k1, &k2, root);
+
+        let mut ids = Vec::new();
+        let mut w = Element::<Bn128>::one();
+        for _ in 0..domain_size {
+            ids.push([w.clone(), k1.clone() * &w, k2.clone() * &w]);
+            w *= root;
+        }
+
+        let expected: HashSet<_> = ids.iter().flatten().cloned().collect();
+        let got: Vec<_> = sigma.iter().flatten().cloned().collect();
+        assert_eq!(expected.len(), 3 * domain_size);
+        assert_eq!(got.len(), 3 * domain_size);
+        assert_eq!(got.into_iter().collect::<HashSet<_>>(), expected);
+
+        // Single occurrence maps to itself.
+        assert_eq!(sigma[2][2], ids[2][2]);
+        // Signal 1 cycles a[0] -> a[1] -> b[2] -> a[0].
+        assert_eq!(sigma[0][1], ids[0][0]);
+        assert_eq!(sigma[1][2], ids[1][0]);
+        assert_eq!(sigma[0][0], ids[2][1]);
+    }
+
+    #[test]
+    fn test_identity_permutation() {
+        let fft = FftEngine::<Bn128>::new(3).unwrap();
+        let root = &fft.w[3];
+        let k1 = Element::<Bn128>::from(2u64);
+        let k2 = Element::<Bn128>::from(3u64);
+
+        let [a, b, c] = identity_permutation(8, &k1, &k2, root);
+        assert_eq!((a.len(), b.len(), c.len()), (8, 8, 8));
+        for i in 0..8 {
+            let w = pow_u64(root, i as u64);
+            assert_eq!(a[i], w);
+            assert_eq!(b[i], &k1 * &w);
+            assert_eq!(c[i], &k2 * &w);
+        }
+        assert!(identity_permutation(0, &k1, &k2, root).iter().all(Vec::is_empty));
+    }
+
+    #[tokio::test]
+    async fn test_setup_without_constraints() -> Result<()> {
+        // No constraints: only the public input gates, or no gate at all. Both still get
+        // the minimal domain of 8 points.
+        for n_public in [2u32, 0] {
+            let mut header = 32u32.to_le_bytes().to_vec();
+            header.extend(to_n8r_bytes(&Curve::r().to_bytes_le(), 32));
+            for count in [n_public + 1, n_public / 2, n_public - n_public / 2, 0] {
+                header.extend(count.to_le_bytes());
+            }
+            header.extend((n_public as u64 + 1).to_le_bytes());
+            header.extend(0u32.to_le_bytes());
+            let mut r1cs = b"r1cs".to_vec();
+            r1cs.extend(1u32.to_le_bytes());
+            r1cs.extend(2u32.to_le_bytes());
+            for (id, data) in [(1u32, header), (2, Vec::new())] {
+                r1cs.extend(id.to_le_bytes());
+                r1cs.extend((data.len() as u64).to_le_bytes());
+                r1cs.extend(data);
+            }
+
+            let (fd_r1cs, sections_r1cs) = file::read_bin_file_fd(BinFile::from_bytes(r1cs), "r1cs", 1).await?;
+            let (fd_ptau, sections_ptau) = file::read_bin_file("src/artifacts/pot8.ptau", "ptau", PTAU_MAX_VERSION).await?;
+            let zkey = plonk_setup_in_memory(fd_ptau, &sections_ptau, fd_r1cs, &sections_r1cs, None).await?;
+
+            let fd = BinFile::from_bytes(zkey.slice(0, zkey.byte_length()));
+            let (mut fd, sections) = file::read_bin_file_fd(fd, "zkey", 1).await?;
+            let header = crate::zkey::read_plonk_header(&mut fd, &sections).await?;
+            assert_eq!(header.n_constraints, n_public);
+            assert_eq!(header.n_public, n_public);
+            assert_eq!(header.domain_size, 8);
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,58 @@
 use std::collections::HashMap;
 use std::io::SeekFrom;
 
-use crate::file::{BinFile, Section};
+use crate::file::{self, BinFile, Section, SectionCursor};
 use crate::curves::Curve;
-use anyhow::{Result, anyhow};
-use r1cs::num::BigUint;
+use crate::ec::{G1Affine, G2Affine};
+use crate::pairing::{neg_g1, pairing_check};
+use crate::error::{Result, SnarkError};
 use tokio::io::AsyncSeekExt;
 
+/// Highest ptau format version understood by [`PTauFile::read_header`]. snarkjs only
+/// writes version 1.
+pub const PTAU_MAX_VERSION: u32 = 1;
+
+/// Metadata from the header section of a ptau file.
+#[derive(Debug)]
+pub struct PtauHeader {
+    pub version: u32,
+    pub curve: Curve,
+    pub power: u32,
+    pub ceremony_power: u32,
+}
+
+/// Section holding `[beta]_2`, a single G2 point.
+pub const PTAU_BETA_G2_SECTION: u32 = 6;
+
+/// Section holding the contribution history of the ceremony.
+pub const PTAU_CONTRIBUTIONS_SECTION: u32 = 7;
+
+/// The G2 points of a ptau a verification key draws from, as raw Montgomery-form bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtauVerificationPoints {
+    /// `[tau]_2`, the second point of section 3; PLONK keys store it as `X_2`.
+    pub tau_g2: Vec<u8>,
+    /// `[beta]_2`, from section 6.
+    pub beta_g2: Vec<u8>,
+}
+
+/// One entry of the ptau contribution history.
+///
+/// Only the transcript metadata is kept; the contributed points and public keys are
+/// skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    /// 1-based position in the history.
+    pub index: u32,
+    /// 0 for a regular contribution, 1 for a beacon.
+    pub contribution_type: u32,
+    pub name: Option<String>,
+    /// BLAKE2b-512 challenge hash the next contributor starts from.
+    pub next_challenge: Vec<u8>,
+    pub num_iterations_exp: Option<u8>,
+    pub beacon_hash: Option<Vec<u8>>,
+}
+
 pub struct PTauFile {
     bin_file: BinFile,
 }
@@ -17,46 +63,608 @@ impl PTauFile {
         Self { bin_file }
     }
 
+    /// Reads the header section, checking that the power does not exceed the ceremony
+    /// power and, if section 2 is present, that it holds `2^(power+1) - 1` G1 points.
     pub async fn read_header(
         &mut self,
         sections: &HashMap<u32, Vec<Section>>,
-    ) -> Result<(Curve, u32, u32)> {
-        let section = sections
-            .get(&1)
-            .and_then(|v| v.first())
-            .ok_or_else(|| anyhow!("{}: File has no header section (1)", "ptau"))?;
-    
-        if sections[&1].len() > 1 {
-            return Err(anyhow!("ptau: File has more than one header section"));
-        }
+    ) -> Result<PtauHeader> {
+        self.bin_file.file.seek(SeekFrom::Start(4)).await?;
+        self.bin_file.pos = 4;
+        let version = self.bin_file.read_u32().await?;
+
+        let section = file::get_unique_section(sections, 1)?;
     
         self.bin_file.file.seek(SeekFrom::Start(section.offset)).await?;
         self.bin_file.pos = section.offset;
         let n8 = self.bin_file.read_u32().await?;
-        let buff = self.bin_file.read_bytes(n8 as usize).await?;
-        let q_biguint = BigUint::from_bytes_le(&buff);
-        let curve = Curve::from_q(&q_biguint).unwrap();
+        let q = self.bin_file.read_biguint(n8 as usize).await?;
+        let curve = Curve::from_q(&q)?;
     
-        if (curve.f1.n64 * 8) != n8.try_into().unwrap() {
-            return Err(anyhow!(
-                "Invalid size: expected {} bytes, got {}",
-                curve.f1.n64 * 8,
-                n8
-            ));
+        if curve.f1.n64 * 8 != n8 as usize {
+            return Err(SnarkError::InvalidFieldSize {
+                expected: curve.f1.n64 * 8,
+                got: n8 as usize,
+            });
         }
     
         let power = self.bin_file.read_u32().await?;
         let ceremony_power = self.bin_file.read_u32().await?;
+        // A ptau can be truncated below its ceremony, never extended past it.
+        if ceremony_power < power {
+            return Err(SnarkError::CeremonyPowerTooSmall {
+                power,
+                ceremony_power,
+            });
+        }
     
         let read_bytes = self.bin_file.pos - section.offset;
         if read_bytes != section.size {
-            return Err(anyhow!(
-                "Invalid PTau header size: read {}, expected {}",
-                read_bytes,
-                section.size
-            ));
+            return Err(SnarkError::SectionSizeMismatch {
+                section: 1,
+                expected: section.size,
+                got: read_bytes,
+            });
         }
     
-        Ok((curve, power, ceremony_power))
+        // Header-only files have no points to check.
+        if sections.contains_key(&2) {
+            let tau_g1 = file::get_unique_section(sections, 2)?;
+            let g1 = 2 * curve.n8q as u64;
+            let expected = (2u64 << power) - 1;
+            if tau_g1.size != expected * g1 {
+                return Err(SnarkError::PtauPowerMismatch {
+                    power,
+                    points: tau_g1.size / g1,
+                });
+            }
+        }
+
+        Ok(PtauHeader {
+            version,
+            curve,
+            power,
+            ceremony_power,
+        })
+    }
+
+    /// Reads `[tau]_2`, the second point of section 3, which the verification key
+    /// stores as `X_2`. The point is checked to lie on the twist and returned as raw
+    /// bytes, in the layout the zkey header uses.
+    pub async fn read_ptau_g2_point(
+        &mut self,
+        sections: &HashMap<u32, Vec<Section>>,
+        curve: &Curve,
+    ) -> Result<Vec<u8>> {
+        let g2_size = 4 * curve.n8q as u64;
+        let bytes =
+            file::read_section(&mut self.bin_file, sections, 3, Some(g2_size), Some(g2_size))
+                .await?;
+        G2Affine::from_montgomery_bytes(curve, &bytes)?;
+        Ok(bytes)
+    }
+
+    /// Reads the first `n_points` tau powers in G1 (section 2) as raw point bytes.
+    pub async fn read_tau_g1(
+        &mut self,
+        sections: &HashMap<u32, Vec<Section>>,
+        curve: &Curve,
+        n_points: usize,
+    ) -> Result<Vec<u8>> {
+        let length = (n_points * 2 * curve.n8q) as u64;
+        file::read_section(&mut self.bin_file, sections, 2, None, Some(length)).await
+    }
+}
+
+/// Reads the contribution history (section 7). Files without one yield an empty list.
+pub async fn read_ptau_contributions(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+) -> Result<Vec<Contribution>> {
+    if !sections.contains_key(&PTAU_CONTRIBUTIONS_SECTION) {
+        return Ok(Vec::new());
+    }
+    file::get_unique_section(sections, PTAU_CONTRIBUTIONS_SECTION)?;
+
+    let header = file::read_section(fd, sections, 1, Some(0), Some(4)).await?;
+    let n8q = fd.byte_order().u32(&header) as usize;
+    let (g1, g2) = (2 * n8q, 4 * n8q);
+    // tauG1, tauG2, alphaG1, betaG1, betaG2, then the tau/alpha/beta public keys
+    // (two G1 points and one G2 point each) and the 216-byte partial hash.
+    let skipped = 3 * g1 + 2 * g2 + 3 * (2 * g1 + g2) + 216;
+
+    let buf = file::read_section(fd, sections, PTAU_CONTRIBUTIONS_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(PTAU_CONTRIBUTIONS_SECTION, &buf);
+    let n_contributions = cursor.read_u32()?;
+
+    let mut contributions = Vec::with_capacity(n_contributions as usize);
+    for index in 1..=n_contributions {
+        cursor.take(skipped)?;
+        let next_challenge = cursor.take(64)?.to_vec();
+        let contribution_type = cursor.read_u32()?;
+
+        let mut contribution = Contribution {
+            index,
+            contribution_type,
+            name: None,
+            next_challenge,
+            num_iterations_exp: None,
+            beacon_hash: None,
+        };
+
+        let params_len = cursor.read_u32()? as usize;
+        let mut params = SectionCursor::new(PTAU_CONTRIBUTIONS_SECTION, cursor.take(params_len)?);
+        let mut last_type = 0;
+        // Parameters are tagged by type and stored in increasing type order.
+        while !params.is_empty() {
+            let param_type = params.take(1)?[0];
+            if param_type <= last_type {
+                return Err(SnarkError::InvalidContribution("parameters are not sorted"));
+            }
+            last_type = param_type;
+            match param_type {
+                1 => {
+                    let len = params.take(1)?[0] as usize;
+                    let name = params.take(len)?;
+                    contribution.name = Some(String::from_utf8_lossy(name).into_owned());
+                }
+                2 => contribution.num_iterations_exp = Some(params.take(1)?[0]),
+                3 => {
+                    let len = params.take(1)?[0] as usize;
+                    contribution.beacon_hash = Some(params.take(len)?.to_vec());
+                }
+                _ => return Err(SnarkError::InvalidContribution("unknown parameter")),
+            }
+        }
+
+        contributions.push(contribution);
+    }
+    cursor.finish()?;
+
+    Ok(contributions)
+}
+
+/// Reads `[tau]_2` and `[beta]_2`, checking that section 6 holds exactly one G2 point
+/// and that both points lie on the twist.
+pub async fn read_ptau_verification_points(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    curve: &Curve,
+) -> Result<PtauVerificationPoints> {
+    let g2_size = 4 * curve.n8q as u64;
+
+    let beta_section = file::get_unique_section(sections, PTAU_BETA_G2_SECTION)?;
+    if beta_section.size != g2_size {
+        return Err(SnarkError::SectionSizeMismatch {
+            section: PTAU_BETA_G2_SECTION,
+            expected: g2_size,
+            got: beta_section.size,
+        });
+    }
+
+    let tau_g2 = file::read_section(fd, sections, 3, Some(g2_size), Some(g2_size)).await?;
+    let beta_g2 = file::read_section(fd, sections, PTAU_BETA_G2_SECTION, None, None).await?;
+    for bytes in [&tau_g2, &beta_g2] {
+        G2Affine::from_montgomery_bytes(curve, bytes)?;
+    }
+
+    Ok(PtauVerificationPoints { tau_g2, beta_g2 })
+}
+
+/// Pairing-checks `sample` pairs of consecutive G1 powers of tau, evenly spread over
+/// section 2 and starting with the first: `e(P_i, [tau]_2) = e(P_{i+1}, [1]_2)`.
+///
+/// Each pair costs two Miller loops and a final exponentiation, so this is a spot
+/// check, not a full verification of the file.
+pub async fn verify_ptau_powers(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    curve: &Curve,
+    sample: usize,
+) -> Result<()> {
+    let (g1, g2) = (2 * curve.n8q as u64, 4 * curve.n8q as u64);
+    let n_points = file::get_unique_section(sections, 2)?.size / g1;
+    if n_points < 2 || sample == 0 {
+        return Ok(());
+    }
+
+    let g2_points = file::read_section(fd, sections, 3, Some(0), Some(2 * g2)).await?;
+    let one_g2 = G2Affine::from_montgomery_bytes(curve, &g2_points[..g2 as usize])?;
+    let tau_g2 = G2Affine::from_montgomery_bytes(curve, &g2_points[g2 as usize..])?;
+
+    let last = n_points - 2;
+    let sample = (sample as u64).min(n_points - 1);
+    for k in 0..sample {
+        let index = if sample == 1 { 0 } else { k * last / (sample - 1) };
+        let bytes = file::read_section(fd, sections, 2, Some(index * g1), Some(2 * g1)).await?;
+        let p = G1Affine::from_montgomery_bytes(curve, &bytes[..g1 as usize])?;
+        let p_next = G1Affine::from_montgomery_bytes(curve, &bytes[g1 as usize..])?;
+        if !pairing_check(&[(p, tau_g2.clone()), (neg_g1(&p_next), one_g2.clone())]) {
+            return Err(SnarkError::PairingCheckFailed { index });
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of [`verify_ptau`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PtauReport {
+    pub version: u32,
+    pub power: u32,
+    pub ceremony_power: u32,
+    /// G1 powers of tau in section 2.
+    pub tau_g1_points: u64,
+    /// G1 points in section 12, or `None` if the file was not prepared for PLONK.
+    pub lagrange_points: Option<u64>,
+}
+
+impl PtauReport {
+    /// Points a ptau of this power holds in sections 2 and 12: `2^(power+1) - 1`.
+    pub fn expected_points(&self) -> u64 {
+        (2u64 << self.power) - 1
+    }
+
+    /// Checks the counts against the declared power.
+    pub fn check(&self) -> Result<()> {
+        if self.tau_g1_points != self.expected_points() {
+            return Err(SnarkError::PtauPowerMismatch {
+                power: self.power,
+                points: self.tau_g1_points,
+            });
+        }
+        if self.lagrange_points != Some(self.expected_points()) {
+            return Err(SnarkError::PtauNotPrepared);
+        }
+        Ok(())
+    }
+}
+
+/// Checks that section 12 reaches the Lagrange points for a domain of `power`. The
+/// section stores one block of `2^p` points per power `p` in increasing order, so the
+/// block for `power` ends after `2^(power+1) - 1` points.
+pub fn check_lagrange_points(
+    sections: &HashMap<u32, Vec<Section>>,
+    curve: &Curve,
+    power: u32,
+) -> Result<()> {
+    if !sections.contains_key(&12) {
+        return Err(SnarkError::PtauNotPrepared);
+    }
+    let section = file::get_unique_section(sections, 12)?;
+    let available = section.size / (2 * curve.n8q as u64);
+    let required = (2u64 << power) - 1;
+    if available < required {
+        return Err(SnarkError::LagrangeSectionTooShort {
+            power,
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the ptau at `path` is usable for a PLONK setup: the curve is supported,
+/// section 2 holds as many G1 powers as the header's power implies, and section 12 is
+/// present with the Lagrange points for every power up to it. Structural problems,
+/// including a section 2 that does not match the power (see [`PTauFile::read_header`]),
+/// are returned as errors; the counts are reported so that [`PtauReport::check`] or the
+/// caller can decide.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn verify_ptau(path: &str) -> Result<PtauReport> {
+    let (fd, sections) = file::read_bin_file(path, "ptau", PTAU_MAX_VERSION).await?;
+    let header = PTauFile::from(fd).read_header(&sections).await?;
+    let g1 = 2 * header.curve.n8q as u64;
+
+    let count = |id: u32| -> Result<Option<u64>> {
+        if !sections.contains_key(&id) {
+            return Ok(None);
+        }
+        let section = file::get_unique_section(&sections, id)?;
+        if section.size % g1 != 0 {
+            return Err(SnarkError::SectionSizeMismatch {
+                section: id,
+                expected: section.size / g1 * g1,
+                got: section.size,
+            });
+        }
+        Ok(Some(section.size / g1))
+    };
+
+    Ok(PtauReport {
+        version: header.version,
+        power: header.power,
+        ceremony_power: header.ceremony_power,
+        tau_g1_points: count(2)?.ok_or(SnarkError::SectionMissing(2))?,
+        lagrange_points: count(12)?,
+    })
+}
+
+/// Writes a copy of the ptau at `in_path` reduced to `new_power`: the header records the
+/// new power, and each section keeps only the points a ceremony of that power would have.
+/// The Lagrange sections (12 to 15) store one block per power in increasing order, so
+/// they are cut after the block for `new_power`. Sections other than 1 to 7 and 12 to
+/// 15 are not copied.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn truncate_ptau(in_path: &str, out_path: &str, new_power: u32) -> Result<()> {
+    let (fd_in, sections) = file::read_bin_file(in_path, "ptau", PTAU_MAX_VERSION).await?;
+    let mut ptau = PTauFile::from(fd_in);
+    let header = ptau.read_header(&sections).await?;
+    if new_power > header.power {
+        return Err(SnarkError::PowerTooLarge {
+            power: new_power,
+            max: header.power,
+        });
+    }
+
+    let (g1, g2) = (2 * header.curve.n8q as u64, 4 * header.curve.n8q as u64);
+    let n = 1u64 << new_power;
+    let lengths = [
+        (1, None),
+        (2, Some((2 * n - 1) * g1)),
+        (3, Some(n * g2)),
+        (4, Some(n * g1)),
+        (5, Some(n * g1)),
+        (6, None),
+        (PTAU_CONTRIBUTIONS_SECTION, None),
+        (12, Some((2 * n - 1) * g1)),
+        (13, Some((2 * n - 1) * g2)),
+        (14, Some((2 * n - 1) * g1)),
+        (15, Some((2 * n - 1) * g1)),
+    ];
+    let present: Vec<_> = lengths
+        .into_iter()
+        .filter(|(id, _)| sections.contains_key(id))
+        .collect();
+
+    let mut fd_out = BinFile::create(out_path, "ptau", header.version, present.len() as u32).await?;
+    for (id, length) in present {
+        let mut buf = file::read_section(&mut ptau.bin_file, &sections, id, None, length).await?;
+        if id == 1 {
+            // n8, q, then the power.
+            let offset = 4 + header.curve.n8q;
+            buf[offset..offset + 4].copy_from_slice(&new_power.to_le_bytes());
+        }
+        fd_out.start_write_section(id).await?;
+        fd_out.write_bytes(&buf).await?;
+        fd_out.end_write_section().await?;
+    }
+    fd_out.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::read_bin_file;
+    use tempfile::{NamedTempFile, tempdir};
+
+    fn ptau_with_header(version: u32, header: &[u8]) -> Vec<u8> {
+        let mut contents = b"ptau".to_vec();
+        contents.extend(version.to_le_bytes());
+        contents.extend(1u32.to_le_bytes());
+        contents.extend(1u32.to_le_bytes());
+        contents.extend((header.len() as u64).to_le_bytes());
+        contents.extend(header);
+        contents
+    }
+
+    #[tokio::test]
+    async fn test_read_header_version() -> Result<()> {
+        let mut header = 32u32.to_le_bytes().to_vec();
+        header.extend(Curve::q().to_bytes_le());
+        header.extend(10u32.to_le_bytes());
+        header.extend(12u32.to_le_bytes());
+
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        std::fs::write(path, ptau_with_header(1, &header))?;
+        let (fd, sections) = read_bin_file(path, "ptau", PTAU_MAX_VERSION).await?;
+        let ptau = PTauFile::from(fd).read_header(&sections).await?;
+        assert_eq!(ptau.version, 1);
+        assert_eq!(ptau.power, 10);
+        assert_eq!(ptau.ceremony_power, 12);
+
+        // No later version is documented, so none is guessed at.
+        std::fs::write(path, ptau_with_header(2, &header))?;
+        assert!(matches!(
+            read_bin_file(path, "ptau", PTAU_MAX_VERSION).await,
+            Err(SnarkError::UnsupportedVersion { version: 2, max: 1 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_header_checks_powers() -> Result<()> {
+        let header_with = |power: u32, ceremony_power: u32| {
+            let mut header = 32u32.to_le_bytes().to_vec();
+            header.extend(Curve::q().to_bytes_le());
+            header.extend(power.to_le_bytes());
+            header.extend(ceremony_power.to_le_bytes());
+            header
+        };
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+
+        std::fs::write(path, ptau_with_header(1, &header_with(10, 8)))?;
+        let (fd, sections) = read_bin_file(path, "ptau", PTAU_MAX_VERSION).await?;
+        let result = PTauFile::from(fd).read_header(&sections).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::CeremonyPowerTooSmall { power: 10, ceremony_power: 8 })
+        ));
+
+        // pot8 with its header claiming power 9: section 2 is half as long as needed.
+        let mut bytes = std::fs::read("src/artifacts/pot8.ptau")?;
+        let (_, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let offset = sections[&1][0].offset as usize + 4 + 32;
+        bytes[offset..offset + 8].copy_from_slice(&[9, 0, 0, 0, 9, 0, 0, 0]);
+        std::fs::write(path, bytes)?;
+        let (fd, sections) = read_bin_file(path, "ptau", PTAU_MAX_VERSION).await?;
+        let result = PTauFile::from(fd).read_header(&sections).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::PtauPowerMismatch { power: 9, points: 511 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_ptau_g2_point() -> Result<()> {
+        let curve = Curve::new();
+        let (fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let bytes = PTauFile::from(fd).read_ptau_g2_point(&sections, &curve).await?;
+        assert_eq!(bytes.len(), 128);
+
+        let x2 = G2Affine::from_montgomery_bytes(&curve, &bytes)?;
+        assert!(!x2.infinity);
+        assert!(x2.is_on_curve());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_ptau_verification_points() -> Result<()> {
+        let curve = Curve::new();
+        let (mut fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let points = read_ptau_verification_points(&mut fd, &sections, &curve).await?;
+        assert_eq!(points.beta_g2.len(), 128);
+
+        let beta = G2Affine::from_montgomery_bytes(&curve, &points.beta_g2)?;
+        assert!(!beta.infinity);
+        assert!(beta.is_on_curve());
+        assert_ne!(points.beta_g2, points.tau_g2);
+        let mut ptau = PTauFile::from(fd);
+        assert_eq!(points.tau_g2, ptau.read_ptau_g2_point(&sections, &curve).await?);
+
+        let mut long = sections.clone();
+        long.get_mut(&PTAU_BETA_G2_SECTION).unwrap()[0].size = 256;
+        let result = read_ptau_verification_points(&mut ptau.bin_file, &long, &curve).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::SectionSizeMismatch { section: 6, expected: 128, got: 256 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_ptau_powers() -> Result<()> {
+        const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+        let curve = Curve::new();
+        // Checks the first and the last pair of the 511 powers.
+        let (mut fd, sections) = read_bin_file(PTAU_PATH, "ptau", 1).await?;
+        verify_ptau_powers(&mut fd, &sections, &curve, 2).await?;
+
+        // Replace [tau]_1 with [tau^2]_1, which is still on the curve.
+        let mut bytes = std::fs::read(PTAU_PATH)?;
+        let offset = sections[&2][0].offset as usize;
+        bytes.copy_within(offset + 128..offset + 192, offset + 64);
+        let tmp = NamedTempFile::new()?;
+        std::fs::write(tmp.path(), bytes)?;
+        let (mut fd, sections) = read_bin_file(tmp.path().to_str().unwrap(), "ptau", 1).await?;
+        let result = verify_ptau_powers(&mut fd, &sections, &curve, 1).await;
+        assert!(matches!(result, Err(SnarkError::PairingCheckFailed { index: 0 })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_ptau_contributions() -> Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let contributions = read_ptau_contributions(&mut fd, &sections).await?;
+        assert_eq!(contributions.len(), 1);
+        assert_eq!(contributions[0].index, 1);
+        assert_eq!(contributions[0].next_challenge.len(), 64);
+
+        let mut without = sections.clone();
+        without.remove(&PTAU_CONTRIBUTIONS_SECTION);
+        assert!(read_ptau_contributions(&mut fd, &without).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_ptau() -> Result<()> {
+        // pot24.ptau is not bundled; pot8 exercises the same checks.
+        let report = verify_ptau("src/artifacts/pot8.ptau").await?;
+        assert_eq!(report.power, 8);
+        assert_eq!(report.tau_g1_points, 511);
+        assert_eq!(report.lagrange_points, Some(511));
+        report.check()?;
+
+        let unprepared = PtauReport {
+            lagrange_points: None,
+            ..report.clone()
+        };
+        assert!(matches!(unprepared.check(), Err(SnarkError::PtauNotPrepared)));
+        let short = PtauReport {
+            tau_g1_points: 255,
+            ..report
+        };
+        assert!(matches!(
+            short.check(),
+            Err(SnarkError::PtauPowerMismatch { power: 8, points: 255 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_truncate_ptau() -> Result<()> {
+        const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+        let dir = tempdir()?;
+        let out_path = dir.path().join("pot3.ptau");
+        let out = out_path.to_str().unwrap();
+        truncate_ptau(PTAU_PATH, out, 3).await?;
+
+        let (fd, sections) = read_bin_file(out, "ptau", PTAU_MAX_VERSION).await?;
+        let mut ptau = PTauFile::from(fd);
+        let header = ptau.read_header(&sections).await?;
+        assert_eq!(header.power, 3);
+        assert_eq!(header.ceremony_power, 8);
+        assert_eq!(sections[&2][0].size, 15 * 64);
+        assert_eq!(sections[&3][0].size, 8 * 128);
+        assert_eq!(sections[&12][0].size, 15 * 64);
+        assert_eq!(sections[&13][0].size, 15 * 128);
+
+        // The kept points are the leading points of the original.
+        let (fd, original_sections) = read_bin_file(PTAU_PATH, "ptau", PTAU_MAX_VERSION).await?;
+        let mut original = PTauFile::from(fd);
+        let curve = Curve::new();
+        assert_eq!(
+            ptau.read_tau_g1(&sections, &curve, 15).await?,
+            original.read_tau_g1(&original_sections, &curve, 15).await?
+        );
+        assert_eq!(
+            ptau.read_ptau_g2_point(&sections, &curve).await?,
+            original.read_ptau_g2_point(&original_sections, &curve).await?
+        );
+
+        let result = truncate_ptau(PTAU_PATH, out, 9).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::PowerTooLarge { power: 9, max: 8 })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_lagrange_points() -> Result<()> {
+        let curve = Curve::new();
+        let (_, mut sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        check_lagrange_points(&sections, &curve, 8)?;
+
+        // Keep only the blocks for powers 0 to 3 in section 12.
+        sections.get_mut(&12).unwrap()[0].size = 15 * 64;
+        check_lagrange_points(&sections, &curve, 3)?;
+        assert!(matches!(
+            check_lagrange_points(&sections, &curve, 4),
+            Err(SnarkError::LagrangeSectionTooShort {
+                power: 4,
+                required: 31,
+                available: 15
+            })
+        ));
+
+        sections.remove(&12);
+        assert!(matches!(
+            check_lagrange_points(&sections, &curve, 3),
+            Err(SnarkError::PtauNotPrepared)
+        ));
+        Ok(())
     }
 }
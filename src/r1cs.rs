@@ -1,178 +1,305 @@
-use crate::file::R1cs;
+use crate::error::{Result, SnarkError};
+use crate::file::{
+    Constraint, ConstraintStream, CustomGate, CustomGateUse, FieldConstraint, R1cs, R1csHeader,
+};
+use crate::json::{Json, JsonValue};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::spill::{SPILL_BATCH, SpillWriter, SpilledConstraints};
+use crate::utils::{CancelFlag, ProgressCallback, check_cancelled};
 use r1cs::Bn128;
-use r1cs::Element;
+use r1cs::num::BigUint;
+use r1cs::{Element, Field};
 use std::collections::HashMap;
 
-pub fn process_constraints(
-    r1cs: &mut R1cs,
-) -> (
-    Vec<(
-        u32,
-        u32,
-        u32,
-        Element<Bn128>,
-        Element<Bn128>,
-        Element<Bn128>,
-        Element<Bn128>,
-        Element<Bn128>,
-    )>,
-    Vec<(u32, u32, Element<Bn128>, Element<Bn128>)>,
+/// A PLONK gate: wire signals (a, b, c) and selector coefficients (qm, ql, qr, qo, qc).
+pub type PlonkConstraint<F = Bn128> = (
+    u32,
+    u32,
+    u32,
+    Element<F>,
+    Element<F>,
+    Element<F>,
+    Element<F>,
+    Element<F>,
+);
+
+/// A synthesized addition gate: the two summed signals and their coefficients.
+pub type PlonkAddition<F = Bn128> = (u32, u32, Element<F>, Element<F>);
+
+/// Gates, addition gates and stats returned by [`process_constraints`].
+pub type ProcessedConstraints<F = Bn128> =
+    (Vec<PlonkConstraint<F>>, Vec<PlonkAddition<F>>, PlonkStats);
+
+/// Breakdown of the gates produced by [`process_constraints`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlonkStats {
+    /// Gates coming from R1CS constraints with a genuine multiplication.
+    pub n_mul: usize,
+    /// Gates coming from R1CS constraints that reduce to a linear sum.
+    pub n_sum: usize,
+    /// Gates exposing the public signals.
+    pub n_public: usize,
+    /// Addition gates synthesized to split long linear combinations.
+    pub n_additions: usize,
+    /// Number of PLONK variables, including the synthesized ones.
+    pub final_n_vars: u32,
+}
+
+impl Json for PlonkStats {
+    fn to_json(&self) -> JsonValue {
+        JsonValue::Object(vec![
+            ("nMul".to_string(), JsonValue::from(self.n_mul as u64)),
+            ("nSum".to_string(), JsonValue::from(self.n_sum as u64)),
+            ("nPublic".to_string(), JsonValue::from(self.n_public as u64)),
+            (
+                "nAdditions".to_string(),
+                JsonValue::from(self.n_additions as u64),
+            ),
+            (
+                "finalNVars".to_string(),
+                JsonValue::from(self.final_n_vars as u64),
+            ),
+        ])
+    }
+
+    fn from_json(value: &JsonValue) -> Result<Self> {
+        Ok(Self {
+            n_mul: value.number_field("nMul")?,
+            n_sum: value.number_field("nSum")?,
+            n_public: value.number_field("nPublic")?,
+            n_additions: value.number_field("nAdditions")?,
+            final_n_vars: value.number_field("finalNVars")?,
+        })
+    }
+}
+
+/// Where the gates exposing the public signals go relative to the constraint gates.
+///
+/// snarkjs provers expect [`Leading`](Self::Leading): the Lagrange polynomials in the
+/// zkey select public input `i` at gate `i`, so [`plonk_setup`](crate::plonk::plonk_setup)
+/// always uses it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PublicInputPlacement {
+    /// Public input gates first, as gates `0..n_public`.
+    #[default]
+    Leading,
+    /// Public input gates after all constraint gates.
+    Trailing,
+}
+
+/// Coefficients by wire. Wire 0 is the constant one: the r1cs format fixes it there and
+/// its header has no field to move it, so its coefficient is the constant term.
+type LinearCombination<F> = HashMap<u32, Element<F>>;
+
+/// The exact terms `(sl, cl, sr, cr)` summed by an addition gate.
+type AdditionKey<F> = (u32, Element<F>, u32, Element<F>);
+
+/// A linear combination reduced to its constant term and padded signals and coefficients.
+type ReducedCoefs<F> = (Element<F>, Vec<u32>, Vec<Element<F>>);
+
+fn normalize<F: Field>(lc: &mut LinearCombination<F>) {
+    lc.retain(|_, v| !v.is_zero());
+}
+
+/// Sets `dst` to `k * lc1 + lc2`, normalized. `dst` is cleared first, so a scratch map
+/// can be reused across constraints without reallocating.
+fn join_into<F: Field>(
+    dst: &mut LinearCombination<F>,
+    lc1: &LinearCombination<F>,
+    k: &Element<F>,
+    lc2: &LinearCombination<F>,
 ) {
-    type LinearCombination = HashMap<u32, Element<Bn128>>;
+    dst.clear();
+    dst.extend(lc1.iter().map(|(&s, v)| (s, k * v)));
+    for (&s, v) in lc2 {
+        match dst.get_mut(&s) {
+            Some(e) => *e += v,
+            None => {
+                dst.insert(s, v.clone());
+            }
+        }
+    }
+    normalize(dst);
+}
 
-    let mut plonk_n_vars = r1cs.header.n_vars;
-    let n_public = r1cs.header.n_outputs + r1cs.header.n_pub_inputs;
+fn get_lc_type<F: Field>(lc: &mut LinearCombination<F>) -> String {
+    let mut k = Element::<F>::zero();
+    let mut n = 0;
+    let keys: Vec<_> = lc.keys().cloned().collect();
+    for s in keys {
+        if lc[&s].is_zero() {
+            lc.remove(&s);
+        } else if s == 0 {
+            k += &lc[&s];
+        } else {
+            n += 1;
+        }
+    }
+    if n > 0 {
+        n.to_string()
+    } else if !k.is_zero() {
+        "k".to_string()
+    } else {
+        "0".to_string()
+    }
+}
+
+fn to_lc<F: Field>(lc: &HashMap<u32, BigUint>) -> LinearCombination<F> {
+    lc.iter()
+        .map(|(&k, v)| (k, Element::<F>::from(v.clone())))
+        .collect()
+}
 
-    let mut plonk_constraints: Vec<(
-        u32,
-        u32,
-        u32,
-        Element<Bn128>,
-        Element<Bn128>,
-        Element<Bn128>,
-        Element<Bn128>,
-        Element<Bn128>,
-    )> = vec![];
-    let mut plonk_additions: Vec<(u32, u32, Element<Bn128>, Element<Bn128>)> = vec![];
+/// Accumulates the PLONK gates produced for a run of R1CS constraints.
+///
+/// Synthesized addition variables are numbered from `n_vars` upward, so builders for
+/// independent chunks can be merged by renumbering each chunk's new ids.
+struct PlonkBuilder<F: Field> {
+    n_vars: u32,
+    /// Bound on `n_vars`; only lowered by tests.
+    max_vars: u32,
+    constraints: Vec<PlonkConstraint<F>>,
+    additions: Vec<PlonkAddition<F>>,
+    /// Index in `constraints` of the gate backing each entry of `additions`.
+    addition_gates: Vec<usize>,
+    /// Variable synthesized for each addition emitted so far, so repeated sums are shared.
+    addition_cache: HashMap<AdditionKey<F>, u32>,
+    n_mul: usize,
+    n_sum: usize,
+    /// Reused by [`join_into`] for constraints with a constant factor.
+    scratch: LinearCombination<F>,
+}
+
+/// The output of a [`PlonkBuilder`] run on one chunk, with coefficients as `BigUint`.
+/// `Element<F>` is `!Send`, so worker threads hand their gates back in this form.
+struct ChunkGates {
+    constraints: Vec<(u32, u32, u32, [BigUint; 5])>,
+    additions: Vec<(u32, u32, BigUint, BigUint)>,
+    addition_gates: Vec<usize>,
+    n_mul: usize,
+    n_sum: usize,
+}
 
-    fn normalize(lc: &mut LinearCombination) {
-        lc.retain(|_, v| !v.is_zero());
+impl<F: Field> PlonkBuilder<F> {
+    fn new(n_vars: u32) -> Self {
+        Self::with_max_vars(n_vars, u32::MAX)
     }
 
-    fn join(
-        lc1: &LinearCombination,
-        k: &Element<Bn128>,
-        lc2: &LinearCombination,
-    ) -> LinearCombination {
-        let mut res = HashMap::new();
-        for (s, v) in lc1 {
-            let val = k.clone() * v.clone();
-            res.entry(*s)
-                .and_modify(|e: &mut Element<Bn128>| *e = e.clone() + val.clone())
-                .or_insert(val);
+    fn with_max_vars(n_vars: u32, max_vars: u32) -> Self {
+        Self {
+            n_vars,
+            max_vars,
+            constraints: vec![],
+            additions: vec![],
+            addition_gates: vec![],
+            addition_cache: HashMap::new(),
+            n_mul: 0,
+            n_sum: 0,
+            scratch: HashMap::new(),
         }
-        for (s, v) in lc2 {
-            res.entry(*s)
-                .and_modify(|e: &mut Element<Bn128>| *e = e.clone() + v.clone())
-                .or_insert(v.clone());
+    }
+
+    /// Returns the variable holding `cl * sl + cr * sr`, emitting an addition gate for it
+    /// unless an identical sum was already synthesized.
+    ///
+    /// Fails with [`SnarkError::TooManyVariables`] rather than wrap the `u32` ids.
+    fn add_addition(
+        &mut self,
+        (sl, cl): (u32, Element<F>),
+        (sr, cr): (u32, Element<F>),
+    ) -> Result<u32> {
+        let key = (sl, cl.clone(), sr, cr.clone());
+        if let Some(&so) = self.addition_cache.get(&key) {
+            return Ok(so);
         }
-        normalize(&mut res);
-        res
+
+        if self.n_vars >= self.max_vars {
+            return Err(SnarkError::TooManyVariables { max: self.max_vars });
+        }
+        let so = self.n_vars;
+        self.n_vars += 1;
+
+        let qm = Element::<F>::zero();
+        let ql = -cl.clone();
+        let qr = -cr.clone();
+        let qo = Element::<F>::one();
+        let qc = Element::<F>::zero();
+
+        self.addition_gates.push(self.constraints.len());
+        self.constraints.push((sl, sr, so, qm, ql, qr, qo, qc));
+        self.additions.push((sl, sr, cl, cr));
+        self.addition_cache.insert(key, so);
+        Ok(so)
     }
 
-    fn reduce_coefs(
-        lc: &LinearCombination,
-        max_c: usize,
-        plonk_n_vars: &mut u32,
-        plonk_constraints: &mut Vec<(
-            u32,
-            u32,
-            u32,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-        )>,
-        plonk_additions: &mut Vec<(u32, u32, Element<Bn128>, Element<Bn128>)>,
-    ) -> (Element<Bn128>, Vec<u32>, Vec<Element<Bn128>>) {
-        let mut k = Element::<Bn128>::zero();
+    fn reduce_coefs(&mut self, lc: &LinearCombination<F>, max_c: usize) -> Result<ReducedCoefs<F>> {
+        let mut k = Element::<F>::zero();
         let mut cs = vec![];
 
         for (&s, v) in lc {
             if s == 0 {
-                k = k + v.clone();
+                k += v;
             } else {
                 cs.push((s, v.clone()));
             }
         }
+        // Signals are reduced in ascending order (as snarkjs does) so the output is deterministic.
+        cs.sort_by_key(|(s, _)| *s);
 
         while cs.len() > max_c {
             let c1 = cs.remove(0);
             let c2 = cs.remove(0);
 
-            let sl = c1.0;
-            let sr = c2.0;
-            let so = *plonk_n_vars;
-            *plonk_n_vars += 1;
-
-            let qm = Element::<Bn128>::zero();
-            let ql = -c1.1.clone();
-            let qr = -c2.1.clone();
-            let qo = Element::<Bn128>::one();
-            let qc = Element::<Bn128>::zero();
-
-            plonk_constraints.push((
-                sl,
-                sr,
-                so,
-                qm.clone(),
-                ql.clone(),
-                qr.clone(),
-                qo.clone(),
-                qc.clone(),
-            ));
-            plonk_additions.push((sl, sr, c1.1, c2.1));
-            cs.push((so, Element::<Bn128>::one()));
+            let so = self.add_addition(c1, c2)?;
+            cs.push((so, Element::<F>::one()));
         }
 
         let (mut s, mut coefs): (Vec<_>, Vec<_>) = cs.into_iter().unzip();
         while coefs.len() < max_c {
             s.push(0);
-            coefs.push(Element::<Bn128>::zero());
+            coefs.push(Element::<F>::zero());
         }
 
-        (k, s, coefs)
-    }
-
-    fn add_constraint_sum(
-        lc: &LinearCombination,
-        plonk_constraints: &mut Vec<(
-            u32,
-            u32,
-            u32,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-        )>,
-        plonk_n_vars: &mut u32,
-        plonk_additions: &mut Vec<(u32, u32, Element<Bn128>, Element<Bn128>)>,
-    ) {
-        let (k, s, coefs) = reduce_coefs(lc, 3, plonk_n_vars, plonk_constraints, plonk_additions);
-        plonk_constraints.push((
+        Ok((k, s, coefs))
+    }
+
+    /// Gate for `lc = 0`. `lc` must be normalized; if it is empty the constraint holds
+    /// for every witness and no gate is emitted, since an all-zero gate would only pad
+    /// the domain.
+    fn add_constraint_sum(&mut self, lc: &LinearCombination<F>) -> Result<()> {
+        if lc.is_empty() {
+            return Ok(());
+        }
+        let (k, s, coefs) = self.reduce_coefs(lc, 3)?;
+        self.n_sum += 1;
+        self.constraints.push((
             s[0],
             s[1],
             s[2],
-            Element::<Bn128>::zero(),
+            Element::<F>::zero(),
             coefs[0].clone(),
             coefs[1].clone(),
             coefs[2].clone(),
             k,
         ));
+        Ok(())
     }
 
+    /// Gate for `(ka + ca·sa) · (kb + cb·sb) = kc + cc·sc`.
+    ///
+    /// `a` and `b` always hold a signal here (constant factors take the sum path), so
+    /// only `c` can come back padded with signal 0 and a zero coefficient, which leaves
+    /// `qo` zero. `c` must be normalized: a zero-valued entry would count as a term and
+    /// force a needless addition gate.
     fn add_constraint_mul(
-        a: &LinearCombination,
-        b: &LinearCombination,
-        c: &LinearCombination,
-        plonk_constraints: &mut Vec<(
-            u32,
-            u32,
-            u32,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-        )>,
-        plonk_n_vars: &mut u32,
-        plonk_additions: &mut Vec<(u32, u32, Element<Bn128>, Element<Bn128>)>,
-    ) {
-        let (ka, sa, ca) = reduce_coefs(a, 1, plonk_n_vars, plonk_constraints, plonk_additions);
-        let (kb, sb, cb) = reduce_coefs(b, 1, plonk_n_vars, plonk_constraints, plonk_additions);
-        let (kc, sc, cc) = reduce_coefs(c, 1, plonk_n_vars, plonk_constraints, plonk_additions);
+        &mut self,
+        a: &LinearCombination<F>,
+        b: &LinearCombination<F>,
+        c: &LinearCombination<F>,
+    ) -> Result<()> {
+        let (ka, sa, ca) = self.reduce_coefs(a, 1)?;
+        let (kb, sb, cb) = self.reduce_coefs(b, 1)?;
+        let (kc, sc, cc) = self.reduce_coefs(c, 1)?;
 
         let qm = ca[0].clone() * cb[0].clone();
         let ql = ca[0].clone() * kb.clone();
@@ -180,110 +307,927 @@ pub fn process_constraints(
         let qo = -cc[0].clone();
         let qc = ka * kb - kc;
 
-        plonk_constraints.push((sa[0], sb[0], sc[0], qm, ql, qr, qo, qc));
-    }
-
-    fn get_lc_type(lc: &mut LinearCombination) -> String {
-        let mut k = Element::<Bn128>::zero();
-        let mut n = 0;
-        let keys: Vec<_> = lc.keys().cloned().collect();
-        for s in keys {
-            if lc[&s].is_zero() {
-                lc.remove(&s);
-            } else if s == 0 {
-                k = k + lc[&s].clone();
-            } else {
-                n += 1;
-            }
-        }
-        if n > 0 {
-            n.to_string()
-        } else if !k.is_zero() {
-            "k".to_string()
-        } else {
-            "0".to_string()
-        }
+        self.n_mul += 1;
+        self.constraints
+            .push((sa[0], sb[0], sc[0], qm, ql, qr, qo, qc));
+        Ok(())
     }
 
     fn process(
-        mut a: LinearCombination,
-        mut b: LinearCombination,
-        mut c: LinearCombination,
-        plonk_constraints: &mut Vec<(
-            u32,
-            u32,
-            u32,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-            Element<Bn128>,
-        )>,
-        plonk_n_vars: &mut u32,
-        plonk_additions: &mut Vec<(u32, u32, Element<Bn128>, Element<Bn128>)>,
-    ) {
+        &mut self,
+        mut a: LinearCombination<F>,
+        mut b: LinearCombination<F>,
+        mut c: LinearCombination<F>,
+    ) -> Result<()> {
         let ta = get_lc_type(&mut a);
         let tb = get_lc_type(&mut b);
         if ta == "0" || tb == "0" {
             normalize(&mut c);
-            add_constraint_sum(&c, plonk_constraints, plonk_n_vars, plonk_additions);
-        } else if ta == "k" {
-            let k = a.get(&0).unwrap();
-            let cc = join(&b, k, &c);
-            add_constraint_sum(&cc, plonk_constraints, plonk_n_vars, plonk_additions);
-        } else if tb == "k" {
-            let k = b.get(&0).unwrap();
-            let cc = join(&a, k, &c);
-            add_constraint_sum(&cc, plonk_constraints, plonk_n_vars, plonk_additions);
+            self.add_constraint_sum(&c)
+        } else if ta == "k" || tb == "k" {
+            let (k, lc) = if ta == "k" {
+                (&a[&0], &b)
+            } else {
+                (&b[&0], &a)
+            };
+            let mut cc = std::mem::take(&mut self.scratch);
+            join_into(&mut cc, lc, k, &c);
+            let result = self.add_constraint_sum(&cc);
+            self.scratch = cc;
+            result
         } else {
-            add_constraint_mul(&a, &b, &c, plonk_constraints, plonk_n_vars, plonk_additions);
+            normalize(&mut c);
+            self.add_constraint_mul(&a, &b, &c)
         }
     }
 
-    for s in 1..=n_public {
-        plonk_constraints.push((
-            s,
-            0,
-            0,
-            Element::<Bn128>::zero(),
-            Element::<Bn128>::one(),
-            Element::<Bn128>::zero(),
-            Element::<Bn128>::zero(),
-            Element::<Bn128>::zero(),
-        ));
+    /// Adds the gates exposing public signals `1..=n_public`.
+    fn add_public_inputs(&mut self, n_public: u32) {
+        for s in 1..=n_public {
+            self.constraints.push((
+                s,
+                0,
+                0,
+                Element::<F>::zero(),
+                Element::<F>::one(),
+                Element::<F>::zero(),
+                Element::<F>::zero(),
+                Element::<F>::zero(),
+            ));
+        }
     }
 
-    let mut progress = 0;
-    for constraint in &r1cs.constraints {
+    fn process_r1cs_constraint(&mut self, constraint: &[HashMap<u32, BigUint>; 3]) -> Result<()> {
         let [a, b, c] = constraint;
-        let a = a
-            .iter()
-            .map(|(&k, v)| (k, Element::<Bn128>::from(v.clone())))
-            .collect();
-        let b = b
-            .iter()
-            .map(|(&k, v)| (k, Element::<Bn128>::from(v.clone())))
+        self.process(to_lc(a), to_lc(b), to_lc(c))
+    }
+
+    /// Hands out the gates and additions emitted so far, leaving the builder empty but
+    /// still numbering variables and sharing sums as before. The builder can no longer
+    /// be passed to [`Self::append`] afterwards.
+    fn take_output(&mut self) -> (Vec<PlonkConstraint<F>>, Vec<PlonkAddition<F>>) {
+        self.addition_gates.clear();
+        (
+            std::mem::take(&mut self.constraints),
+            std::mem::take(&mut self.additions),
+        )
+    }
+
+    fn process_field_constraint(&mut self, constraint: FieldConstraint<F>) -> Result<()> {
+        let [a, b, c] = constraint;
+        self.process(a, b, c)
+    }
+
+    fn into_chunk(self) -> ChunkGates {
+        ChunkGates {
+            constraints: self
+                .constraints
+                .into_iter()
+                .map(|(a, b, c, qm, ql, qr, qo, qc)| {
+                    (
+                        a,
+                        b,
+                        c,
+                        [qm, ql, qr, qo, qc].map(|q| q.to_biguint().clone()),
+                    )
+                })
+                .collect(),
+            additions: self
+                .additions
+                .into_iter()
+                .map(|(sl, sr, cl, cr)| (sl, sr, cl.to_biguint().clone(), cr.to_biguint().clone()))
+                .collect(),
+            addition_gates: self.addition_gates,
+            n_mul: self.n_mul,
+            n_sum: self.n_sum,
+        }
+    }
+
+    /// Appends the gates of a chunk that numbered its new variables from `base`.
+    ///
+    /// Its addition gates are replayed through [`Self::add_addition`], so they are
+    /// renumbered after the variables already allocated here and shared with identical
+    /// sums from earlier chunks, exactly as a sequential run would.
+    fn append(&mut self, other: ChunkGates, base: u32) -> Result<()> {
+        self.n_mul += other.n_mul;
+        self.n_sum += other.n_sum;
+
+        // New id of each variable synthesized by `other`, indexed by `id - base`.
+        let mut renamed: Vec<u32> = Vec::with_capacity(other.additions.len());
+        let map = |renamed: &[u32], s: u32| {
+            if s >= base {
+                renamed[(s - base) as usize]
+            } else {
+                s
+            }
+        };
+
+        let mut additions = other
+            .additions
+            .into_iter()
+            .zip(other.addition_gates)
+            .peekable();
+
+        for (i, (a, b, c, q)) in other.constraints.into_iter().enumerate() {
+            if let Some(((sl, sr, cl, cr), _)) = additions.next_if(|(_, gate)| *gate == i) {
+                let sl = map(&renamed, sl);
+                let sr = map(&renamed, sr);
+                let so = self.add_addition((sl, Element::from(cl)), (sr, Element::from(cr)))?;
+                renamed.push(so);
+                continue;
+            }
+            let [qm, ql, qr, qo, qc] = q.map(Element::from);
+            self.constraints.push((
+                map(&renamed, a),
+                map(&renamed, b),
+                map(&renamed, c),
+                qm,
+                ql,
+                qr,
+                qo,
+                qc,
+            ));
+        }
+        Ok(())
+    }
+
+    fn finish(self, n_public: usize) -> ProcessedConstraints<F> {
+        let stats = PlonkStats {
+            n_mul: self.n_mul,
+            n_sum: self.n_sum,
+            n_public,
+            n_additions: self.additions.len(),
+            final_n_vars: self.n_vars,
+        };
+        (self.constraints, self.additions, stats)
+    }
+}
+
+/// Converts the R1CS constraints into PLONK gates over `F`, using all available cores.
+/// The r1cs must be defined over `F`: coefficients are taken as elements, not reduced.
+///
+/// Fails with [`SnarkError::CustomGatesNotSupported`] if any custom gate is instantiated,
+/// since those constraints have no R1CS row to translate.
+pub fn process_constraints<F: Field>(
+    r1cs: &R1cs,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<ProcessedConstraints<F>> {
+    let n_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    process_constraints_with_threads(r1cs, n_threads, PublicInputPlacement::default(), progress)
+}
+
+/// Same as [`process_constraints`], failing with [`SnarkError::Cancelled`] soon after
+/// `cancel` is set. The flag is polled before every constraint.
+pub fn process_constraints_cancellable<F: Field>(
+    r1cs: &R1cs,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: CancelFlag<'_>,
+) -> Result<ProcessedConstraints<F>> {
+    let n_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let placement = PublicInputPlacement::default();
+    run_constraints(r1cs, n_threads, placement, progress, Some(cancel))
+}
+
+/// Same as [`process_constraints`] with an explicit number of worker threads and
+/// placement of the public input gates.
+///
+/// Constraints are split into contiguous chunks processed independently and merged in
+/// order, so the output is identical to the sequential (`n_threads == 1`) run.
+pub fn process_constraints_with_threads<F: Field>(
+    r1cs: &R1cs,
+    n_threads: usize,
+    placement: PublicInputPlacement,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<ProcessedConstraints<F>> {
+    run_constraints(r1cs, n_threads, placement, progress, None)
+}
+
+fn run_constraints<F: Field>(
+    r1cs: &R1cs,
+    n_threads: usize,
+    placement: PublicInputPlacement,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<ProcessedConstraints<F>> {
+    if r1cs.header.use_custom_gates {
+        check_custom_gates(&r1cs.custom_gates, &r1cs.custom_gates_uses)?;
+    }
+
+    let n_vars = r1cs.header.n_vars;
+    let n_public = r1cs.header.n_outputs + r1cs.header.n_pub_inputs;
+
+    let mut builder = PlonkBuilder::new(n_vars);
+    if placement == PublicInputPlacement::Leading {
+        builder.add_public_inputs(n_public);
+    }
+
+    let total = r1cs.constraints.len();
+
+    if n_threads <= 1 {
+        for (i, constraint) in r1cs.constraints.iter().enumerate() {
+            check_cancelled(cancel)?;
+            builder.process_r1cs_constraint(constraint)?;
+
+            let done = i + 1;
+            if (done % 100000 == 0 || done == total)
+                && let Some(progress) = progress
+            {
+                progress("Constraints", done, total);
+            }
+        }
+    } else {
+        process_chunks(&mut builder, &r1cs.constraints, n_threads, progress, cancel)?;
+    }
+
+    if placement == PublicInputPlacement::Trailing {
+        builder.add_public_inputs(n_public);
+    }
+    Ok(builder.finish(n_public as usize))
+}
+
+/// Fails with [`SnarkError::CustomGatesNotSupported`], naming the template, if any custom
+/// gate is instantiated. Gates that are declared but never used are accepted.
+pub fn check_custom_gates(gates: &[CustomGate], uses: &[CustomGateUse]) -> Result<()> {
+    if let Some(gate_use) = uses.first() {
+        let name = gates.get(gate_use.id as usize).map_or_else(
+            || format!("#{}", gate_use.id),
+            |gate| gate.template_name.clone(),
+        );
+        return Err(SnarkError::CustomGatesNotSupported(name));
+    }
+    Ok(())
+}
+
+/// Processes `constraints` on `n_threads` threads and appends the gates to `builder`.
+/// Each worker polls `cancel` before every constraint.
+fn process_chunks<F: Field>(
+    builder: &mut PlonkBuilder<F>,
+    constraints: &[Constraint],
+    n_threads: usize,
+    progress: Option<ProgressCallback<'_>>,
+    cancel: Option<CancelFlag<'_>>,
+) -> Result<()> {
+    let (n_vars, max_vars) = (builder.n_vars, builder.max_vars);
+    let total = constraints.len();
+
+    let chunk_size = total.div_ceil(n_threads).max(1);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = constraints
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let handle = scope.spawn(move || -> Result<ChunkGates> {
+                    let mut local = PlonkBuilder::<F>::with_max_vars(n_vars, max_vars);
+                    for constraint in chunk {
+                        check_cancelled(cancel)?;
+                        local.process_r1cs_constraint(constraint)?;
+                    }
+                    Ok(local.into_chunk())
+                });
+                (chunk.len(), handle)
+            })
             .collect();
-        let c = c
+
+        let mut done = 0;
+        for (len, handle) in handles {
+            let local = handle
+                .join()
+                .unwrap_or_else(|err| std::panic::resume_unwind(err))?;
+            builder.append(local, n_vars)?;
+
+            done += len;
+            if let Some(progress) = progress {
+                progress("Constraints", done, total);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Header-only counterpart of [`check_custom_gates`] for callers without the custom gate
+/// uses (section 5): any circuit declaring custom gates fails with
+/// [`SnarkError::CustomGatesDeclared`], since which template is used cannot be told.
+fn reject_custom_gates(header: &R1csHeader) -> Result<()> {
+    if header.use_custom_gates {
+        return Err(SnarkError::CustomGatesDeclared);
+    }
+    Ok(())
+}
+
+/// Sequential [`process_constraints`] fed from a [`ConstraintStream`], so the R1CS
+/// constraints never have to be held in memory all at once.
+///
+/// The stream cannot see the custom gate uses, so a header with `use_custom_gates` is
+/// rejected outright; run [`check_custom_gates`] and [`process_constraints`] instead.
+pub async fn process_constraint_stream<F: Field>(
+    header: &R1csHeader,
+    stream: &mut ConstraintStream<'_>,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<ProcessedConstraints<F>> {
+    reject_custom_gates(header)?;
+
+    let n_public = header.n_outputs + header.n_pub_inputs;
+    let total = header.n_constraints as usize;
+
+    let mut builder = PlonkBuilder::new(header.n_vars);
+    builder.add_public_inputs(n_public);
+
+    let mut done = 0;
+    while let Some(constraint) = stream.next().await? {
+        builder.process_r1cs_constraint(&constraint)?;
+
+        done += 1;
+        if (done % 100000 == 0 || done == total)
+            && let Some(progress) = progress
+        {
+            progress("Constraints", done, total);
+        }
+    }
+
+    Ok(builder.finish(n_public as usize))
+}
+
+/// Same as [`process_constraint_stream`], but the gates and additions are flushed to
+/// files in `dir` every [`SPILL_BATCH`](crate::spill::SPILL_BATCH) gates instead of
+/// being collected, so neither the r1cs nor the PLONK circuit is held in memory.
+/// [`SpilledConstraints::read`] loads the same output `process_constraint_stream` returns.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn process_constraint_stream_spilled(
+    header: &R1csHeader,
+    stream: &mut ConstraintStream<'_>,
+    dir: &std::path::Path,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<SpilledConstraints> {
+    spill_constraint_stream(header, stream, dir, SPILL_BATCH, progress).await
+}
+
+/// [`process_constraint_stream_spilled`] flushing every `batch` gates.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn spill_constraint_stream(
+    header: &R1csHeader,
+    stream: &mut ConstraintStream<'_>,
+    dir: &std::path::Path,
+    batch: usize,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<SpilledConstraints> {
+    reject_custom_gates(header)?;
+
+    let n_public = header.n_outputs + header.n_pub_inputs;
+    let total = header.n_constraints as usize;
+
+    let mut writer = SpillWriter::create(dir).await?;
+    let mut builder = PlonkBuilder::<Bn128>::new(header.n_vars);
+    builder.add_public_inputs(n_public);
+
+    let mut done = 0;
+    while let Some(constraint) = stream.next().await? {
+        builder.process_r1cs_constraint(&constraint)?;
+        if builder.constraints.len() >= batch {
+            let (constraints, additions) = builder.take_output();
+            writer.write(&constraints, &additions).await?;
+        }
+
+        done += 1;
+        if (done % 100000 == 0 || done == total)
+            && let Some(progress) = progress
+        {
+            progress("Constraints", done, total);
+        }
+    }
+
+    let (constraints, additions) = builder.take_output();
+    writer.write(&constraints, &additions).await?;
+    let (_, _, mut stats) = builder.finish(n_public as usize);
+    stats.n_additions = writer.n_additions();
+    writer.finish(stats).await
+}
+
+/// Sequential [`process_constraints`] over constraints read with
+/// [`read_constraints_as_field`](crate::file::read_constraints_as_field), whose
+/// coefficients need no further conversion.
+///
+/// Like [`process_constraint_stream`], a header with `use_custom_gates` is rejected.
+pub fn process_field_constraints<F: Field>(
+    header: &R1csHeader,
+    constraints: Vec<FieldConstraint<F>>,
+    progress: Option<ProgressCallback<'_>>,
+) -> Result<ProcessedConstraints<F>> {
+    reject_custom_gates(header)?;
+
+    let n_public = header.n_outputs + header.n_pub_inputs;
+    let total = constraints.len();
+
+    let mut builder = PlonkBuilder::new(header.n_vars);
+    builder.add_public_inputs(n_public);
+
+    for (i, constraint) in constraints.into_iter().enumerate() {
+        builder.process_field_constraint(constraint)?;
+
+        let done = i + 1;
+        if (done % 100000 == 0 || done == total)
+            && let Some(progress) = progress
+        {
+            progress("Constraints", done, total);
+        }
+    }
+
+    Ok(builder.finish(n_public as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{
+        read_bin_file, read_constraints_as_field, read_constraints_streaming, read_r1cs_fd,
+        read_r1cs_header,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_parallel_matches_sequential() -> crate::error::Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+
+        let (seq_constraints, seq_additions, seq_stats) =
+            process_constraints_with_threads::<Bn128>(
+                &r1cs,
+                1,
+                PublicInputPlacement::Leading,
+                None,
+            )
+            .unwrap();
+        for n_threads in [2, 3, 8, 64] {
+            let (constraints, additions, stats) = process_constraints_with_threads::<Bn128>(
+                &r1cs,
+                n_threads,
+                PublicInputPlacement::Leading,
+                None,
+            )
+            .unwrap();
+            assert_eq!(constraints, seq_constraints, "{} threads", n_threads);
+            assert_eq!(additions, seq_additions, "{} threads", n_threads);
+            assert_eq!(stats, seq_stats, "{} threads", n_threads);
+        }
+        assert!(!seq_additions.is_empty());
+
+        Ok(())
+    }
+
+    fn r1cs_from(n_vars: u32, constraints: &[[&[(u32, u64)]; 3]]) -> R1cs {
+        R1cs {
+            header: R1csHeader {
+                n8: 32,
+                prime: Bn128::order(),
+                n_vars,
+                n_outputs: 0,
+                n_pub_inputs: 0,
+                n_prv_inputs: n_vars - 1,
+                n_labels: n_vars as u64,
+                n_constraints: constraints.len() as u32,
+                use_custom_gates: false,
+            },
+            constraints: constraints
+                .iter()
+                .map(|triple| {
+                    triple.map(|lc| {
+                        lc.iter()
+                            .map(|&(s, v)| (s, BigUint::from(v)))
+                            .collect::<HashMap<_, _>>()
+                    })
+                })
+                .collect(),
+            custom_gates: Vec::new(),
+            custom_gates_uses: Vec::new(),
+        }
+    }
+
+    /// The allocating merge [`join_into`] replaced, kept as a reference.
+    fn join(
+        lc1: &LinearCombination<Bn128>,
+        k: &Element<Bn128>,
+        lc2: &LinearCombination<Bn128>,
+    ) -> LinearCombination<Bn128> {
+        let mut res = HashMap::new();
+        for (s, v) in lc1 {
+            let val = k.clone() * v.clone();
+            res.entry(*s)
+                .and_modify(|e: &mut Element<Bn128>| *e = e.clone() + val.clone())
+                .or_insert(val);
+        }
+        for (s, v) in lc2 {
+            res.entry(*s)
+                .and_modify(|e: &mut Element<Bn128>| *e = e.clone() + v.clone())
+                .or_insert(v.clone());
+        }
+        normalize(&mut res);
+        res
+    }
+
+    #[test]
+    fn test_join_into_matches_join() {
+        let lc = |terms: &[(u32, i64)]| -> LinearCombination<Bn128> {
+            terms.iter().map(|&(s, v)| (s, el(v))).collect()
+        };
+        let lc1 = lc(&[(0, 1), (1, 2), (2, -3), (4, 1)]);
+        let lc2 = lc(&[(1, -6), (2, 9), (3, 5)]);
+        let k = el(3);
+
+        // Stale entries in the scratch map must not leak into the result.
+        let mut dst = lc(&[(7, 7), (1, 1)]);
+        join_into(&mut dst, &lc1, &k, &lc2);
+        assert_eq!(dst, join(&lc1, &k, &lc2));
+        // 3·2 - 6 and 3·-3 + 9 cancel out.
+        assert_eq!(dst, lc(&[(0, 3), (3, 5), (4, 3)]));
+
+        join_into(&mut dst, &lc2, &el(0), &LinearCombination::new());
+        assert!(dst.is_empty());
+    }
+
+    fn el(v: i64) -> Element<Bn128> {
+        let abs = Element::<Bn128>::from(v.unsigned_abs());
+        if v < 0 { -abs } else { abs }
+    }
+
+    /// Gates with small coefficients read back as signed integers, so gates over
+    /// different fields can be compared.
+    fn signed_gates<F: Field>(gates: &[PlonkConstraint<F>]) -> Vec<(u32, u32, u32, [i64; 5])> {
+        let signed = |e: &Element<F>| {
+            let v = e.to_biguint().clone();
+            let (neg, abs) = if v > F::order() >> 1 {
+                (true, F::order() - v)
+            } else {
+                (false, v)
+            };
+            let abs = abs.to_u32_digits().first().copied().unwrap_or(0) as i64;
+            if neg { -abs } else { abs }
+        };
+        gates
             .iter()
-            .map(|(&k, v)| (k, Element::<Bn128>::from(v.clone())))
-            .collect();
-        process(
-            a,
-            b,
-            c,
-            &mut plonk_constraints,
-            &mut plonk_n_vars,
-            &mut plonk_additions,
+            .map(|(a, b, c, qm, ql, qr, qo, qc)| (*a, *b, *c, [qm, ql, qr, qo, qc].map(signed)))
+            .collect()
+    }
+
+    #[test]
+    fn test_process_constraints_is_field_agnostic() {
+        use r1cs::Bls12_381;
+
+        // A mul gate and a sum long enough to need addition gates.
+        let r1cs = r1cs_from(
+            7,
+            &[
+                [&[(1, 3)], &[(0, 2), (2, 5)], &[(0, 4), (3, 7)]],
+                [&[(0, 1)], &[(1, 2), (2, 3), (4, 4), (5, 6)], &[(6, 1)]],
+            ],
         );
-        progress += 1;
-        if progress % 100000 == 0 {
-            println!(
-                "ℹ️  Processed {}% of constraints",
-                progress * 100 / r1cs.constraints.len()
+        let placement = PublicInputPlacement::Leading;
+        let bn = process_constraints_with_threads::<Bn128>(&r1cs, 1, placement, None).unwrap();
+        let bls = process_constraints_with_threads::<Bls12_381>(&r1cs, 1, placement, None).unwrap();
+
+        assert_eq!(signed_gates(&bn.0), signed_gates(&bls.0));
+        assert_eq!(bn.2, bls.2);
+        assert!(!bls.1.is_empty());
+    }
+
+    #[test]
+    fn test_mul_gate_coefficients() {
+        // 3·s1 · (2 + 5·s2) = 4 + 7·s3
+        let r1cs = r1cs_from(4, &[[&[(1, 3)], &[(0, 2), (2, 5)], &[(0, 4), (3, 7)]]]);
+        let (constraints, additions, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert!(additions.is_empty());
+        assert_eq!(
+            constraints,
+            vec![(1, 2, 3, el(15), el(6), el(0), el(-7), el(-4))]
+        );
+
+        // (1 + 2·s1) · 4·s2 = 0: C pads to signal 0 with qo = 0.
+        let r1cs = r1cs_from(3, &[[&[(0, 1), (1, 2)], &[(2, 4)], &[]]]);
+        let (constraints, _, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            constraints,
+            vec![(1, 2, 0, el(8), el(0), el(4), el(0), el(0))]
+        );
+
+        // s1 · s2 = s3, with an explicit zero coefficient on s4 in C.
+        let r1cs = r1cs_from(5, &[[&[(1, 1)], &[(2, 1)], &[(3, 1), (4, 0)]]]);
+        let (constraints, additions, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert!(additions.is_empty());
+        assert_eq!(
+            constraints,
+            vec![(1, 2, 3, el(1), el(0), el(0), el(-1), el(0))]
+        );
+    }
+
+    #[test]
+    fn test_empty_constraint_is_dropped() {
+        // 0 · s1 = 0 and s1 · 0 = 0 hold for any witness; s1 · s1 = s2 does not.
+        let mut r1cs = r1cs_from(
+            3,
+            &[
+                [&[], &[(1, 1)], &[]],
+                [&[(1, 1)], &[(1, 1)], &[(2, 1)]],
+                [&[(1, 1)], &[(2, 0)], &[(0, 0)]],
+            ],
+        );
+        r1cs.header.n_pub_inputs = 1;
+        r1cs.header.n_prv_inputs = 1;
+
+        let (constraints, _, stats) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            constraints,
+            vec![
+                (1, 0, 0, el(0), el(1), el(0), el(0), el(0)),
+                (1, 1, 2, el(1), el(0), el(0), el(-1), el(0)),
+            ]
+        );
+        assert_eq!((stats.n_mul, stats.n_sum, stats.n_public), (1, 0, 1));
+    }
+
+    #[test]
+    fn test_plonk_stats_json_round_trip() -> Result<()> {
+        let stats = PlonkStats {
+            n_mul: 3,
+            n_sum: 4,
+            n_public: 1,
+            n_additions: 9,
+            final_n_vars: 20,
+        };
+        let text = stats.to_json().to_string();
+        assert_eq!(PlonkStats::from_json(&JsonValue::parse(&text)?)?, stats);
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_wire_is_the_constant_term() {
+        // s1 · s1 = 5, written with the constant on wire 0: no output wire, qc = -5.
+        let r1cs = r1cs_from(2, &[[&[(1, 1)], &[(1, 1)], &[(0, 5)]]]);
+        let (constraints, _, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            constraints,
+            vec![(1, 1, 0, el(1), el(0), el(0), el(0), el(-5))]
+        );
+
+        // 3 · s1 = 2 + s2: the factor on wire 0 takes the sum path, C's constant lands in qc.
+        let r1cs = r1cs_from(3, &[[&[(0, 3)], &[(1, 1)], &[(0, 2), (2, 1)]]]);
+        let (constraints, _, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(constraints[0].7, el(2));
+    }
+
+    #[test]
+    fn test_shared_linear_combination_reuses_addition() {
+        let lc: &[(u32, u64)] = &[(1, 1), (2, 1), (3, 1), (4, 1)];
+        let r1cs = r1cs_from(5, &[[&[], &[], lc], [&[], &[], lc]]);
+
+        let (constraints, additions, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(additions.len(), 1);
+        // One addition gate plus one sum gate per constraint.
+        assert_eq!(constraints.len(), 3);
+        assert_eq!(constraints[1].2, constraints[2].2);
+
+        // Parallel chunks share the addition across the chunk boundary as well.
+        let (par_constraints, par_additions, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            2,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(par_constraints, constraints);
+        assert_eq!(par_additions, additions);
+    }
+
+    #[test]
+    fn test_too_many_variables() {
+        // Five signals in C need two addition gates to fit the three sum slots.
+        let lc: &[(u32, u64)] = &[(1, 1), (2, 1), (3, 1), (4, 1), (5, 1)];
+        let r1cs = r1cs_from(6, &[[&[], &[], lc]]);
+
+        let mut builder = PlonkBuilder::<Bn128>::with_max_vars(6, 8);
+        builder
+            .process_r1cs_constraint(&r1cs.constraints[0])
+            .unwrap();
+        assert_eq!(builder.n_vars, 8);
+
+        let mut builder = PlonkBuilder::<Bn128>::with_max_vars(6, 7);
+        let result = builder.process_r1cs_constraint(&r1cs.constraints[0]);
+        assert!(matches!(
+            result,
+            Err(SnarkError::TooManyVariables { max: 7 })
+        ));
+
+        // Each chunk fits on its own; merging them needs a third variable.
+        let lc2: &[(u32, u64)] = &[(1, 2), (2, 1), (3, 1), (4, 1)];
+        let r1cs = r1cs_from(6, &[[&[], &[], lc], [&[], &[], lc2]]);
+        let mut builder = PlonkBuilder::<Bn128>::with_max_vars(6, 8);
+        let result = process_chunks(&mut builder, &r1cs.constraints, 2, None, None);
+        assert!(matches!(
+            result,
+            Err(SnarkError::TooManyVariables { max: 8 })
+        ));
+    }
+
+    #[test]
+    fn test_process_constraints_cancelled() {
+        let lc: &[(u32, u64)] = &[(1, 1), (2, 1), (3, 1), (4, 1)];
+        let r1cs = r1cs_from(5, &[[&[], &[], lc], [&[], &[], lc]]);
+        let placement = PublicInputPlacement::Leading;
+
+        let cancel = AtomicBool::new(true);
+        for n_threads in [1, 2] {
+            let result = run_constraints::<Bn128>(&r1cs, n_threads, placement, None, Some(&cancel));
+            assert!(
+                matches!(result, Err(SnarkError::Cancelled)),
+                "{} threads",
+                n_threads
+            );
+        }
+
+        // An unset flag changes nothing.
+        cancel.store(false, Ordering::Relaxed);
+        assert_eq!(
+            process_constraints_cancellable::<Bn128>(&r1cs, None, &cancel).unwrap(),
+            process_constraints::<Bn128>(&r1cs, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_different_coefficients_are_not_shared() {
+        let lc1: &[(u32, u64)] = &[(1, 1), (2, 1), (3, 1), (4, 1)];
+        let lc2: &[(u32, u64)] = &[(1, 2), (2, 1), (3, 1), (4, 1)];
+        let r1cs = r1cs_from(5, &[[&[], &[], lc1], [&[], &[], lc2]]);
+
+        let (_, additions, _) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )
+        .unwrap();
+        assert_eq!(additions.len(), 2);
+        assert_eq!(additions[1].2, Element::<Bn128>::from(2u64));
+    }
+
+    #[tokio::test]
+    async fn test_public_input_placement() -> crate::error::Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+
+        let (leading, leading_additions, leading_stats) = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        )?;
+        let n_public = leading_stats.n_public;
+        assert_eq!(n_public, 3);
+
+        for n_threads in [1, 4] {
+            let (trailing, trailing_additions, trailing_stats) =
+                process_constraints_with_threads::<Bn128>(
+                    &r1cs,
+                    n_threads,
+                    PublicInputPlacement::Trailing,
+                    None,
+                )?;
+            assert_eq!(trailing_stats, leading_stats);
+            assert_eq!(trailing_additions, leading_additions);
+
+            // Same gates, with the public block moved from the front to the back.
+            let split = trailing.len() - n_public;
+            assert_eq!(trailing[split..], leading[..n_public]);
+            assert_eq!(trailing[..split], leading[n_public..]);
+            assert!(
+                trailing[split..]
+                    .iter()
+                    .map(|g| g.0)
+                    .eq(1..=n_public as u32)
             );
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_gate_use_is_rejected() {
+        let mut r1cs = r1cs_from(3, &[[&[(1, 1)], &[(1, 1)], &[(2, 1)]]]);
+        r1cs.header.use_custom_gates = true;
+        r1cs.custom_gates.push(CustomGate {
+            template_name: "CMul".to_string(),
+            parameters: Vec::new(),
+        });
+
+        // Declared but unused gates are harmless.
+        assert!(
+            process_constraints_with_threads::<Bn128>(
+                &r1cs,
+                1,
+                PublicInputPlacement::Leading,
+                None
+            )
+            .is_ok()
+        );
+
+        r1cs.custom_gates_uses.push(CustomGateUse {
+            id: 0,
+            signals: vec![0, 1, 2],
+        });
+        let result = process_constraints_with_threads::<Bn128>(
+            &r1cs,
+            1,
+            PublicInputPlacement::Leading,
+            None,
+        );
+        assert!(matches!(result, Err(SnarkError::CustomGatesNotSupported(name)) if name == "CMul"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_add_up() -> crate::error::Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+
+        let (constraints, additions, stats) = process_constraints::<Bn128>(&r1cs, None)?;
+        assert_eq!(
+            stats.n_mul + stats.n_sum + stats.n_public + stats.n_additions,
+            constraints.len()
+        );
+        assert_eq!(stats.n_mul + stats.n_sum, r1cs.constraints.len());
+        assert_eq!(stats.n_public, 3);
+        assert_eq!(stats.n_additions, additions.len());
+        assert_eq!(
+            stats.final_n_vars,
+            r1cs.header.n_vars + additions.len() as u32
+        );
+
+        Ok(())
     }
 
-    (plonk_constraints, plonk_additions)
+    #[tokio::test]
+    async fn test_process_constraint_stream_matches_eager() -> crate::error::Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+        let expected = process_constraints::<Bn128>(&r1cs, None)?;
+
+        let header = read_r1cs_header(&mut fd, &sections).await?;
+        let mut stream = read_constraints_streaming(&mut fd, &sections, &header).await?;
+        let streamed = process_constraint_stream::<Bn128>(&header, &mut stream, None).await?;
+
+        assert_eq!(streamed, expected);
+
+        let constraints = read_constraints_as_field::<Bn128>(&mut fd, &sections, &header).await?;
+        assert_eq!(
+            process_field_constraints(&header, constraints, None)?,
+            expected
+        );
+
+        let header = R1csHeader {
+            use_custom_gates: true,
+            ..header
+        };
+        let mut stream = read_constraints_streaming(&mut fd, &sections, &header).await?;
+        let result = process_constraint_stream::<Bn128>(&header, &mut stream, None).await;
+        assert!(matches!(result, Err(SnarkError::CustomGatesDeclared)));
+        let result = process_field_constraints::<Bn128>(&header, Vec::new(), None);
+        assert!(matches!(result, Err(SnarkError::CustomGatesDeclared)));
+        Ok(())
+    }
 }
@@ -0,0 +1,174 @@
+//! On-disk buffers for the PLONK gates and additions, so that large circuits do not
+//! have to hold them in memory while the r1cs is being converted. See
+//! [`process_constraint_stream_spilled`](crate::r1cs::process_constraint_stream_spilled).
+
+use crate::checkpoint::{read_addition, read_gate, write_addition, write_gate};
+use crate::error::Result;
+use crate::file::{self, BinFile, SectionCursor};
+use crate::r1cs::{PlonkAddition, PlonkConstraint, PlonkStats, ProcessedConstraints};
+use std::path::{Path, PathBuf};
+
+/// Format version of the spill files.
+pub const SPILL_VERSION: u32 = 1;
+
+/// The single section of a spill file, holding its records back to back.
+pub const SPILL_RECORDS_SECTION: u32 = 1;
+
+/// Gates buffered in memory before they are flushed to the spill files.
+pub const SPILL_BATCH: usize = 1 << 16;
+
+const CONSTRAINTS_FILE: &str = "constraints.pspl";
+const ADDITIONS_FILE: &str = "additions.pspl";
+
+/// Appends gates and additions to two "pspl" files in a directory.
+pub(crate) struct SpillWriter {
+    constraints: BinFile,
+    additions: BinFile,
+    n_constraints: usize,
+    n_additions: usize,
+    dir: PathBuf,
+}
+
+impl SpillWriter {
+    /// Creates the spill files in `dir`, replacing previous ones.
+    pub(crate) async fn create(dir: &Path) -> Result<Self> {
+        let mut constraints =
+            BinFile::create(dir.join(CONSTRAINTS_FILE), "pspl", SPILL_VERSION, 1).await?;
+        constraints
+            .start_write_section(SPILL_RECORDS_SECTION)
+            .await?;
+        let mut additions =
+            BinFile::create(dir.join(ADDITIONS_FILE), "pspl", SPILL_VERSION, 1).await?;
+        additions.start_write_section(SPILL_RECORDS_SECTION).await?;
+
+        Ok(Self {
+            constraints,
+            additions,
+            n_constraints: 0,
+            n_additions: 0,
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    pub(crate) async fn write(
+        &mut self,
+        constraints: &[PlonkConstraint],
+        additions: &[PlonkAddition],
+    ) -> Result<()> {
+        for gate in constraints {
+            write_gate(&mut self.constraints, gate).await?;
+        }
+        for addition in additions {
+            write_addition(&mut self.additions, addition).await?;
+        }
+        self.n_constraints += constraints.len();
+        self.n_additions += additions.len();
+        Ok(())
+    }
+
+    pub(crate) fn n_additions(&self) -> usize {
+        self.n_additions
+    }
+
+    /// Closes both files. `stats` should count every addition written.
+    pub(crate) async fn finish(mut self, stats: PlonkStats) -> Result<SpilledConstraints> {
+        for fd in [&mut self.constraints, &mut self.additions] {
+            fd.end_write_section().await?;
+            fd.flush().await?;
+        }
+        Ok(SpilledConstraints {
+            dir: self.dir,
+            n_constraints: self.n_constraints,
+            stats,
+        })
+    }
+}
+
+/// Output of a spilled conversion: the gates and additions stay on disk until read back.
+/// The files live in the directory given to the conversion, which the caller removes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpilledConstraints {
+    dir: PathBuf,
+    pub n_constraints: usize,
+    pub stats: PlonkStats,
+}
+
+impl SpilledConstraints {
+    pub async fn read_constraints(&self) -> Result<Vec<PlonkConstraint>> {
+        read_records(
+            &self.dir.join(CONSTRAINTS_FILE),
+            self.n_constraints,
+            read_gate,
+        )
+        .await
+    }
+
+    pub async fn read_additions(&self) -> Result<Vec<PlonkAddition>> {
+        read_records(
+            &self.dir.join(ADDITIONS_FILE),
+            self.stats.n_additions,
+            read_addition,
+        )
+        .await
+    }
+
+    /// Reads both files back into the tuple [`process_constraints`](crate::r1cs::process_constraints) returns.
+    pub async fn read(&self) -> Result<ProcessedConstraints> {
+        Ok((
+            self.read_constraints().await?,
+            self.read_additions().await?,
+            self.stats.clone(),
+        ))
+    }
+}
+
+async fn read_records<T>(
+    path: &Path,
+    count: usize,
+    decode: impl Fn(&mut SectionCursor<'_>) -> Result<T>,
+) -> Result<Vec<T>> {
+    let fd = BinFile::open(path).await?;
+    let (mut fd, sections) = file::read_bin_file_fd(fd, "pspl", SPILL_VERSION).await?;
+    let buf = file::read_section(&mut fd, &sections, SPILL_RECORDS_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(SPILL_RECORDS_SECTION, &buf);
+    let mut records = Vec::with_capacity(count);
+    for _ in 0..count {
+        records.push(decode(&mut cursor)?);
+    }
+    cursor.finish()?;
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{read_bin_file, read_constraints_streaming, read_r1cs_fd};
+    use crate::r1cs::{
+        process_constraint_stream_spilled, process_constraints, spill_constraint_stream,
+    };
+    use ::r1cs::Bn128;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_spilled_output_matches_in_memory() -> Result<()> {
+        let (mut fd, sections) = read_bin_file("src/artifacts/small.r1cs", "r1cs", 1).await?;
+        let r1cs = read_r1cs_fd(&mut fd, &sections).await?;
+        let expected = process_constraints::<Bn128>(&r1cs, None)?;
+        let header = &r1cs.header;
+
+        let dir = tempdir()?;
+        let mut stream = read_constraints_streaming(&mut fd, &sections, header).await?;
+        let spilled =
+            process_constraint_stream_spilled(header, &mut stream, dir.path(), None).await?;
+        assert_eq!(spilled.n_constraints, expected.0.len());
+        assert_eq!(spilled.read().await?, expected);
+
+        // Flushing every few gates splits the additions across batches too.
+        let mut stream = read_constraints_streaming(&mut fd, &sections, header).await?;
+        let spilled = spill_constraint_stream(header, &mut stream, dir.path(), 3, None).await?;
+        assert_eq!(spilled.read_constraints().await?, expected.0);
+        assert_eq!(spilled.read_additions().await?, expected.1);
+        assert_eq!(spilled.stats, expected.2);
+        Ok(())
+    }
+}
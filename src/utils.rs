@@ -1,7 +1,58 @@
+use crate::error::{Result, SnarkError};
+use r1cs::num::BigUint;
+use r1cs::{Bn128, Element, Field};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Progress hook invoked as `(stage, done, total)` by long-running setup stages.
+pub type ProgressCallback<'a> = &'a dyn Fn(&str, usize, usize);
+
+/// Flag polled by long-running setup stages. Setting it, from any thread, makes them
+/// return [`SnarkError::Cancelled`].
+pub type CancelFlag<'a> = &'a AtomicBool;
+
+/// Fails with [`SnarkError::Cancelled`] once `cancel` has been set.
+pub fn check_cancelled(cancel: Option<CancelFlag<'_>>) -> Result<()> {
+    match cancel {
+        Some(flag) if flag.load(Ordering::Relaxed) => Err(SnarkError::Cancelled),
+        _ => Ok(()),
+    }
+}
+
 pub fn log2_floor(v: u32) -> u32 {
     31 - v.leading_zeros()
 }
 
+/// Smallest domain that fits `n_constraints` gates, as `(power, size)` with `size = 2^power`.
+///
+/// As in snarkjs the power is never below 3: the quotient polynomial is split in three
+/// parts of degree `size`, which needs at least 8 points.
+pub fn compute_domain_power(n_constraints: usize) -> (u32, usize) {
+    let size = n_constraints.next_power_of_two().max(8);
+    (log2_floor(size as u32), size)
+}
+
+/// Canonical (non-Montgomery) value of `x` as lowercase hex with a `0x` prefix, the way
+/// snarkjs prints field elements with `toString(16)`.
+pub fn to_hex(x: &Element<Bn128>) -> String {
+    format!("0x{:x}", x.to_biguint())
+}
+
+/// Parses a hex field element, with or without a `0x` prefix. Values at or above the
+/// modulus are rejected rather than reduced.
+pub fn from_hex(s: &str) -> Result<Element<Bn128>> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    let value = BigUint::parse_bytes(digits.as_bytes(), 16)
+        .ok_or_else(|| SnarkError::InvalidHex(s.to_string()))?;
+    if value >= Bn128::order() {
+        return Err(SnarkError::InvalidHex(s.to_string()));
+    }
+    Ok(Element::from(value))
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -26,4 +77,39 @@ mod tests {
         // Edge case
         assert_eq!(log2_floor(u32::MAX), 31);
     }
+
+    #[test]
+    fn test_compute_domain_power() {
+        // Fewer than 8 constraints still get the minimum domain.
+        assert_eq!(compute_domain_power(0), (3, 8));
+        assert_eq!(compute_domain_power(5), (3, 8));
+        // Exactly a power of two fits without growing.
+        assert_eq!(compute_domain_power(8), (3, 8));
+        assert_eq!(compute_domain_power(1024), (10, 1024));
+        // One over doubles the domain.
+        assert_eq!(compute_domain_power(9), (4, 16));
+        assert_eq!(compute_domain_power(1025), (11, 2048));
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        let one = Element::<Bn128>::one();
+        assert_eq!(to_hex(&one), "0x1");
+        assert_eq!(from_hex("0x1").unwrap(), one);
+        assert_eq!(from_hex("1").unwrap(), one);
+
+        let max = -Element::<Bn128>::one();
+        let hex = to_hex(&max);
+        assert_eq!(
+            hex,
+            "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000000"
+        );
+        assert_eq!(from_hex(&hex).unwrap(), max);
+
+        // The modulus itself is not a canonical value.
+        let modulus = format!("{:x}", Bn128::order());
+        assert!(matches!(from_hex(&modulus), Err(SnarkError::InvalidHex(_))));
+        assert!(matches!(from_hex("0xzz"), Err(SnarkError::InvalidHex(_))));
+        assert!(matches!(from_hex("0x"), Err(SnarkError::InvalidHex(_))));
+    }
 }
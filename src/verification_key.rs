@@ -0,0 +1,148 @@
+use crate::curves::Curve;
+use crate::ec::{G1Affine, G2Affine};
+use crate::error::{Result, SnarkError};
+use crate::fft::FftEngine;
+use crate::json::JsonValue;
+use crate::plonk::PlonkHeader;
+use r1cs::Bn128;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Names of the header commitments, in [`PlonkHeader::commitments`] order.
+const COMMITMENT_NAMES: [&str; 8] = ["Qm", "Ql", "Qr", "Qo", "Qc", "S1", "S2", "S3"];
+
+/// Builds the snarkjs `verification_key.json` document for a PLONK zkey header.
+///
+/// Points are given in projective form with decimal coordinates, as snarkjs prints them.
+/// Fails with [`SnarkError::IdentityCommitment`] if a selector or sigma commitment is
+/// the point at infinity, as in a zkey whose commitments were never computed.
+pub fn export_verification_key(curve: &Curve, header: &PlonkHeader) -> Result<JsonValue> {
+    let power = header.domain_size.trailing_zeros();
+    let w = FftEngine::<Bn128>::new(power as usize)?.w[power as usize].clone();
+
+    let mut entries = vec![
+        ("protocol".to_string(), JsonValue::from("plonk")),
+        ("curve".to_string(), JsonValue::from(curve.name())),
+        (
+            "nPublic".to_string(),
+            JsonValue::from(header.n_public as u64),
+        ),
+        ("power".to_string(), JsonValue::from(power as u64)),
+        ("k1".to_string(), JsonValue::from(header.k1.to_string())),
+        ("k2".to_string(), JsonValue::from(header.k2.to_string())),
+    ];
+    for (name, bytes) in COMMITMENT_NAMES.iter().zip(&header.commitments) {
+        let point = G1Affine::from_montgomery_bytes(curve, bytes)?;
+        if point.infinity {
+            return Err(SnarkError::IdentityCommitment(name));
+        }
+        entries.push((name.to_string(), g1_to_json(&point)));
+    }
+    let x2 = G2Affine::from_montgomery_bytes(curve, &header.x2)?;
+    entries.push(("X_2".to_string(), g2_to_json(&x2)));
+    entries.push(("w".to_string(), JsonValue::from(w.to_string())));
+
+    Ok(JsonValue::Object(entries))
+}
+
+/// Writes the verification key of `header` to `path` as pretty-printed JSON.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn write_verification_key<P: AsRef<Path>>(
+    path: P,
+    curve: &Curve,
+    header: &PlonkHeader,
+) -> Result<()> {
+    let vkey = export_verification_key(curve, header)?;
+    tokio::fs::write(path, vkey.to_string_pretty()).await?;
+    Ok(())
+}
+
+fn g1_to_json(p: &G1Affine) -> JsonValue {
+    if p.infinity {
+        return JsonValue::from(vec!["0", "1", "0"]);
+    }
+    JsonValue::from(vec![p.x.to_string(), p.y.to_string(), "1".to_string()])
+}
+
+fn g2_to_json(p: &G2Affine) -> JsonValue {
+    if p.infinity {
+        return JsonValue::from(vec![vec!["0", "0"], vec!["1", "0"], vec!["0", "0"]]);
+    }
+    JsonValue::from(vec![
+        vec![p.x.c0.to_string(), p.x.c1.to_string()],
+        vec![p.y.c0.to_string(), p.y.c1.to_string()],
+        vec!["1".to_string(), "0".to_string()],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::{read_bin_file, read_section};
+    use crate::domain::get_k1_k2;
+
+    #[tokio::test]
+    async fn test_export_verification_key() -> Result<()> {
+        let curve = Curve::new();
+        let (mut fd, sections) = read_bin_file("src/artifacts/pot8.ptau", "ptau", 1).await?;
+        let g1 = read_section(&mut fd, &sections, 2, Some(0), Some(64)).await?;
+        let tau_g2 = read_section(&mut fd, &sections, 3, Some(128), Some(128)).await?;
+
+        let (k1, k2) = get_k1_k2::<Bn128>(3);
+        let header = PlonkHeader {
+            n8q: 32,
+            q: curve.q.clone(),
+            n8r: 32,
+            r: curve.r.clone(),
+            n_vars: 10,
+            n_public: 2,
+            domain_size: 8,
+            n_additions: 0,
+            n_constraints: 8,
+            k1: k1.clone(),
+            k2: k2.clone(),
+            commitments: std::array::from_fn(|_| g1.clone()),
+            x2: tau_g2,
+        };
+
+        let vkey = export_verification_key(&curve, &header)?;
+        for key in [
+            "protocol", "curve", "nPublic", "power", "k1", "k2", "Qm", "Ql", "Qr", "Qo", "Qc",
+            "S1", "S2", "S3", "X_2", "w",
+        ] {
+            assert!(vkey.get(key).is_some(), "missing {}", key);
+        }
+        assert_eq!(
+            vkey.get("protocol").and_then(JsonValue::as_str),
+            Some("plonk")
+        );
+        assert_eq!(vkey.get("nPublic").and_then(JsonValue::as_u64), Some(2));
+        assert_eq!(vkey.get("power").and_then(JsonValue::as_u64), Some(3));
+        assert_eq!(
+            vkey.get("k1").and_then(JsonValue::as_str),
+            Some(k1.to_string().as_str())
+        );
+        assert_eq!(
+            vkey.get("k2").and_then(JsonValue::as_str),
+            Some(k2.to_string().as_str())
+        );
+        assert_eq!(vkey.get("Qm"), Some(&JsonValue::from(vec!["1", "2", "1"])));
+        assert_eq!(vkey.get("S3"), Some(&JsonValue::from(vec!["1", "2", "1"])));
+        assert_eq!(
+            vkey.get("X_2")
+                .and_then(JsonValue::as_array)
+                .map(<[_]>::len),
+            Some(3)
+        );
+
+        // A zeroed commitment cannot verify any proof.
+        let mut zeroed = header.clone();
+        zeroed.commitments[5] = vec![0; 64];
+        assert!(matches!(
+            export_verification_key(&curve, &zeroed),
+            Err(SnarkError::IdentityCommitment("S1"))
+        ));
+
+        Ok(())
+    }
+}
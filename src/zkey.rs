@@ -0,0 +1,419 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::curves::Curve;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::domain::Domain;
+use crate::error::{Result, SnarkError};
+use crate::file::{self, BinFile, Section, SectionCursor};
+use crate::groth16::{ZKEY_GROTH16_N_SECTIONS, ZKEY_PROTOCOL_GROTH16};
+use crate::plonk::{
+    FromMontgomeryBytes, PlonkHeader, SELECTORS, ZKEY_HEADER_SECTION, ZKEY_PL_ADDITIONS_SECTION,
+    ZKEY_PL_HEADER_SECTION, ZKEY_PL_N_SECTIONS, ZKEY_PROTOCOL_PLONK,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::r1cs;
+use crate::r1cs::PlonkAddition;
+use ::r1cs::{Bn128, Element};
+use std::collections::HashMap;
+
+/// Proving system a zkey is laid out for, as declared by its section 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ZkeyProtocol {
+    Groth16,
+    #[default]
+    Plonk,
+}
+
+impl ZkeyProtocol {
+    /// Protocol id stored in section 1.
+    pub fn id(self) -> u32 {
+        match self {
+            Self::Groth16 => ZKEY_PROTOCOL_GROTH16,
+            Self::Plonk => ZKEY_PROTOCOL_PLONK,
+        }
+    }
+
+    /// Number of sections in a complete zkey of this protocol.
+    pub fn n_sections(self) -> u32 {
+        match self {
+            Self::Groth16 => ZKEY_GROTH16_N_SECTIONS,
+            Self::Plonk => ZKEY_PL_N_SECTIONS,
+        }
+    }
+
+    /// Lowercase name, as snarkjs spells it on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Groth16 => "groth16",
+            Self::Plonk => "plonk",
+        }
+    }
+
+    /// Inverse of [`Self::name`], ignoring case.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "groth16" => Ok(Self::Groth16),
+            "plonk" => Ok(Self::Plonk),
+            _ => Err(SnarkError::UnknownProtocolName(name.to_string())),
+        }
+    }
+
+    pub fn from_id(id: u32) -> Result<Self> {
+        match id {
+            ZKEY_PROTOCOL_GROTH16 => Ok(Self::Groth16),
+            ZKEY_PROTOCOL_PLONK => Ok(Self::Plonk),
+            _ => Err(SnarkError::UnsupportedProtocol(id)),
+        }
+    }
+}
+
+/// Runs the setup of `config.protocol`. Only PLONK is supported, through
+/// [`plonk_setup_with_config`](crate::plonk::plonk_setup_with_config): a Groth16 zkey
+/// also needs the IC, A, B1, B2, C and H sections, and without a G2 MSM those cannot be
+/// generated yet, so Groth16 fails with [`SnarkError::UnsupportedProtocol`].
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn setup_with_config(
+    config: &crate::config::SetupConfig,
+    progress: Option<crate::utils::ProgressCallback<'_>>,
+) -> Result<()> {
+    match config.protocol {
+        ZkeyProtocol::Plonk => crate::plonk::plonk_setup_with_config(config, progress).await,
+        ZkeyProtocol::Groth16 => Err(SnarkError::UnsupportedProtocol(config.protocol.id())),
+    }
+}
+
+/// Writes the protocol marker (section 1) shared by every zkey layout.
+pub async fn write_zkey_protocol(fd: &mut BinFile, protocol: ZkeyProtocol) -> Result<()> {
+    fd.start_write_section(ZKEY_HEADER_SECTION).await?;
+    fd.write_u32(protocol.id()).await?;
+    fd.end_write_section().await
+}
+
+/// Reads the protocol marker (section 1).
+pub async fn read_zkey_protocol(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+) -> Result<ZkeyProtocol> {
+    let buf = file::read_section(fd, sections, ZKEY_HEADER_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(ZKEY_HEADER_SECTION, &buf);
+    let protocol = cursor.read_u32()?;
+    cursor.finish()?;
+    ZkeyProtocol::from_id(protocol)
+}
+
+/// A PLONK zkey as written by [`plonk_setup`](crate::plonk::plonk_setup).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zkey {
+    pub header: PlonkHeader,
+    pub additions: Vec<PlonkAddition>,
+    /// Coefficients of Qm, Ql, Qr, Qo and Qc, in that order.
+    pub selectors: [Vec<Element<Bn128>>; 5],
+}
+
+/// Reads a PLONK zkey, returning its header, additions and selector polynomials.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn read_zkey(path: &str) -> Result<Zkey> {
+    let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+    read_zkey_fd(&mut fd, &sections).await
+}
+
+/// Same as [`read_zkey`], over an already opened zkey and its section table.
+pub async fn read_zkey_fd(fd: &mut BinFile, sections: &HashMap<u32, Vec<Section>>) -> Result<Zkey> {
+    let header = read_plonk_header(fd, sections).await?;
+    let n8r = header.n8r as usize;
+
+    let record_len = 8 + 2 * n8r;
+    let additions = read_additions(fd, sections, n8r).await?;
+    if additions.len() != header.n_additions as usize {
+        return Err(SnarkError::SectionSizeMismatch {
+            section: ZKEY_PL_ADDITIONS_SECTION,
+            expected: header.n_additions as u64 * record_len as u64,
+            got: (additions.len() * record_len) as u64,
+        });
+    }
+
+    // Each selector section holds the coefficients followed by the 4x evaluations.
+    let domain_size = header.domain_size as usize;
+    let mut selectors: [Vec<Element<Bn128>>; 5] = Default::default();
+    for (coefficients, (section_num, _)) in selectors.iter_mut().zip(SELECTORS) {
+        let buf = file::read_section(fd, sections, section_num, None, None).await?;
+        let mut cursor = SectionCursor::new(section_num, &buf);
+        for _ in 0..domain_size {
+            coefficients.push(read_fr(&mut cursor, n8r)?);
+        }
+        cursor.take(4 * domain_size * n8r)?;
+        cursor.finish()?;
+    }
+
+    Ok(Zkey {
+        header,
+        additions,
+        selectors,
+    })
+}
+
+/// Decodes the additions section (3) laid out by [`write_additions`](crate::plonk::write_additions):
+/// two signal ids followed by the two coefficients in Montgomery form, `n8r` bytes each.
+pub async fn read_additions(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+    n8r: usize,
+) -> Result<Vec<PlonkAddition>> {
+    let order = fd.byte_order();
+    file::read_records(fd, sections, ZKEY_PL_ADDITIONS_SECTION, 8 + 2 * n8r, |r| {
+        Ok((
+            order.u32(&r[0..]),
+            order.u32(&r[4..]),
+            order.field(&r[8..8 + n8r])?,
+            order.field(&r[8 + n8r..])?,
+        ))
+    })
+    .await
+}
+
+/// Parses the PLONK header (section 2) written by [`write_zkey_header`](crate::plonk::write_zkey_header),
+/// after checking that section 1 declares the PLONK protocol.
+pub async fn read_plonk_header(
+    fd: &mut BinFile,
+    sections: &HashMap<u32, Vec<Section>>,
+) -> Result<PlonkHeader> {
+    let protocol = read_zkey_protocol(fd, sections).await?;
+    if protocol != ZkeyProtocol::Plonk {
+        return Err(SnarkError::UnsupportedProtocol(protocol.id()));
+    }
+
+    let buf = file::read_section(fd, sections, ZKEY_PL_HEADER_SECTION, None, None).await?;
+    let mut cursor = SectionCursor::new(ZKEY_PL_HEADER_SECTION, &buf);
+
+    let n8q = cursor.read_u32()?;
+    let q = cursor.read_biguint(n8q as usize)?;
+    let n8r = cursor.read_u32()?;
+    let r = cursor.read_biguint(n8r as usize)?;
+    let n_vars = cursor.read_u32()?;
+    let n_public = cursor.read_u32()?;
+    let domain_size = cursor.read_u32()?;
+    let n_additions = cursor.read_u32()?;
+    let n_constraints = cursor.read_u32()?;
+    let k1 = read_fr(&mut cursor, n8r as usize)?;
+    let k2 = read_fr(&mut cursor, n8r as usize)?;
+    let mut commitments: [Vec<u8>; 8] = Default::default();
+    for commitment in &mut commitments {
+        *commitment = cursor.take(2 * n8q as usize)?.to_vec();
+    }
+    let x2 = cursor.take(4 * n8q as usize)?.to_vec();
+    cursor.finish()?;
+
+    Ok(PlonkHeader {
+        n8q,
+        q,
+        n8r,
+        r,
+        n_vars,
+        n_public,
+        domain_size,
+        n_additions,
+        n_constraints,
+        k1,
+        k2,
+        commitments,
+        x2,
+    })
+}
+
+fn read_fr(cursor: &mut SectionCursor<'_>, n8r: usize) -> Result<Element<Bn128>> {
+    Element::from_montgomery_bytes(cursor.take(n8r)?)
+}
+
+/// Checks that the zkey at `zkey_path` was set up for the circuit at `r1cs_path`.
+///
+/// Only the header metadata is re-derived (nPublic, nVars, domain size, k1, k2 and the
+/// number of additions); commitments are not recomputed. The first differing field is
+/// reported as [`SnarkError::ZkeyMismatch`].
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn verify_zkey(zkey_path: &str, r1cs_path: &str) -> Result<()> {
+    let (mut fd_zkey, sections_zkey) = file::read_bin_file(zkey_path, "zkey", 1).await?;
+    let header = read_plonk_header(&mut fd_zkey, &sections_zkey).await?;
+
+    let (mut fd_r1cs, sections_r1cs) = file::read_bin_file(r1cs_path, "r1cs", 1).await?;
+    let r1cs = file::read_r1cs_fd(&mut fd_r1cs, &sections_r1cs).await?;
+    let (constraints, additions, stats) = r1cs::process_constraints::<Bn128>(&r1cs, None)?;
+
+    let curve = Curve::from_q(&header.q)?;
+    let domain = Domain::new(&curve, constraints.len())?;
+
+    let checks = [
+        ("r", r1cs.header.prime.to_string(), header.r.to_string()),
+        (
+            "nPublic",
+            stats.n_public.to_string(),
+            header.n_public.to_string(),
+        ),
+        (
+            "nVars",
+            stats.final_n_vars.to_string(),
+            header.n_vars.to_string(),
+        ),
+        (
+            "domainSize",
+            domain.size.to_string(),
+            header.domain_size.to_string(),
+        ),
+        (
+            "nAdditions",
+            additions.len().to_string(),
+            header.n_additions.to_string(),
+        ),
+        (
+            "nConstraints",
+            constraints.len().to_string(),
+            header.n_constraints.to_string(),
+        ),
+        ("k1", domain.k1.to_string(), header.k1.to_string()),
+        ("k2", domain.k2.to_string(), header.k2.to_string()),
+    ];
+    for (field, expected, got) in checks {
+        if expected != got {
+            return Err(SnarkError::ZkeyMismatch {
+                field,
+                expected,
+                got,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft::FftEngine;
+    use crate::plonk::{plonk_setup, write_additions, write_selectors, write_zkey_header};
+    use tempfile::{NamedTempFile, tempdir};
+
+    const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+    const R1CS_PATH: &str = "src/artifacts/small.r1cs";
+
+    #[tokio::test]
+    async fn test_verify_zkey() -> Result<()> {
+        let dir = tempdir()?;
+        let zkey_path = dir.path().join("circuit.zkey");
+        let zkey_path = zkey_path.to_str().unwrap();
+        plonk_setup(PTAU_PATH, R1CS_PATH, zkey_path, None).await?;
+
+        verify_zkey(zkey_path, R1CS_PATH).await?;
+
+        // Bump nPublic in place: n8q, q, n8r, r, nVars come first.
+        let (_, sections) = file::read_bin_file(zkey_path, "zkey", 1).await?;
+        let offset = sections[&ZKEY_PL_HEADER_SECTION][0].offset as usize + 4 + 32 + 4 + 32 + 4;
+        let mut bytes = std::fs::read(zkey_path)?;
+        bytes[offset] += 1;
+        std::fs::write(zkey_path, bytes)?;
+
+        let result = verify_zkey(zkey_path, R1CS_PATH).await;
+        assert!(matches!(
+            result,
+            Err(SnarkError::ZkeyMismatch { field: "nPublic", expected, got })
+                if expected == "3" && got == "4"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_zkey_round_trip() -> Result<()> {
+        let domain_size = 8;
+        let header = PlonkHeader {
+            n8q: 32,
+            q: Curve::q(),
+            n8r: 32,
+            r: Curve::r(),
+            n_vars: 5,
+            n_public: 1,
+            domain_size: domain_size as u32,
+            n_additions: 2,
+            n_constraints: 6,
+            k1: Element::from(2u64),
+            k2: Element::from(3u64),
+            commitments: std::array::from_fn(|i| vec![i as u8; 64]),
+            x2: vec![0xee; 128],
+        };
+        let additions: Vec<PlonkAddition> = vec![
+            (1, 2, Element::from(3u64), -Element::<Bn128>::one()),
+            (4, 0, Element::one(), Element::from(7u64)),
+        ];
+        let selectors: [Vec<Element<Bn128>>; 5] = std::array::from_fn(|s| {
+            (0..domain_size)
+                .map(|i| Element::from((s * domain_size + i) as u64))
+                .collect()
+        });
+        let fft = FftEngine::<Bn128>::new(5)?;
+
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut fd = BinFile::create(path, "zkey", 1, 8).await?;
+        write_additions(
+            &mut fd,
+            ZKEY_PL_ADDITIONS_SECTION,
+            "Additions",
+            32,
+            &additions,
+            None,
+        )
+        .await?;
+        write_selectors(&mut fd, &selectors, 32, &fft, None, None).await?;
+        write_zkey_header(&mut fd, &header).await?;
+        fd.flush().await?;
+
+        let zkey = read_zkey(path).await?;
+        assert_eq!(zkey.header, header);
+        assert_eq!(zkey.additions, additions);
+        assert_eq!(zkey.selectors, selectors);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_additions() -> Result<()> {
+        let additions: Vec<PlonkAddition> = vec![
+            (3, 1, Element::zero(), Element::from(5u64)),
+            (0, 7, -Element::<Bn128>::one(), Element::zero()),
+        ];
+
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut fd = BinFile::create(path, "zkey", 1, 1).await?;
+        write_additions(
+            &mut fd,
+            ZKEY_PL_ADDITIONS_SECTION,
+            "Additions",
+            32,
+            &additions,
+            None,
+        )
+        .await?;
+        fd.flush().await?;
+
+        let (mut fd, sections) = file::read_bin_file(path, "zkey", 1).await?;
+        assert_eq!(read_additions(&mut fd, &sections, 32).await?, additions);
+
+        // A record size that does not divide the section is rejected.
+        assert!(matches!(
+            read_additions(&mut fd, &sections, 24).await,
+            Err(SnarkError::SectionRecordMisaligned { record_len: 56, .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_zkey_rejects_other_protocols() -> Result<()> {
+        let tmp = NamedTempFile::new()?;
+        let path = tmp.path().to_str().unwrap();
+        let mut fd = BinFile::create(path, "zkey", 1, 1).await?;
+        fd.start_write_section(ZKEY_HEADER_SECTION).await?;
+        fd.write_u32(1).await?; // Groth16
+        fd.end_write_section().await?;
+        fd.flush().await?;
+
+        let result = read_zkey(path).await;
+        assert!(matches!(result, Err(SnarkError::UnsupportedProtocol(1))));
+        Ok(())
+    }
+}
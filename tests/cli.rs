@@ -0,0 +1,99 @@
+use snark_rs::SnarkError;
+use snark_rs::cli::{Command, parse_args, run};
+use snark_rs::config::SetupConfig;
+use tempfile::tempdir;
+
+const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+const R1CS_PATH: &str = "src/artifacts/small.r1cs";
+
+#[tokio::test]
+async fn test_plonk_setup_command() -> snark_rs::Result<()> {
+    let dir = tempdir()?;
+    let out_path = dir.path().join("output.zkey");
+    let out = out_path.to_str().unwrap();
+
+    let command = parse_args(["plonk", "setup", PTAU_PATH, R1CS_PATH, out, "--verbose"])?;
+    assert_eq!(
+        command,
+        Command::Setup(SetupConfig {
+            verbose: true,
+            ..SetupConfig::new(PTAU_PATH, R1CS_PATH, out)
+        })
+    );
+
+    run(command).await?;
+    assert!(out_path.is_file());
+
+    // Groth16 setup cannot write a complete zkey yet, so the CLI does not offer it.
+    let groth16 = parse_args(["groth16", "setup", PTAU_PATH, R1CS_PATH, out]);
+    assert!(matches!(groth16, Err(SnarkError::InvalidArguments(_))));
+    Ok(())
+}
+
+#[test]
+fn test_parse_args_errors() {
+    assert_eq!(parse_args(Vec::<String>::new()).unwrap(), Command::Help);
+    assert_eq!(parse_args(["--help"]).unwrap(), Command::Help);
+
+    let missing = parse_args(["plonk", "setup", "missing.ptau", R1CS_PATH, "out.zkey"]);
+    assert!(
+        matches!(missing, Err(SnarkError::InvalidArguments(msg)) if msg.contains("missing.ptau"))
+    );
+
+    let unknown = parse_args(["plonk", "prove"]);
+    assert!(matches!(unknown, Err(SnarkError::InvalidArguments(_))));
+
+    let flag = parse_args(["plonk", "setup", PTAU_PATH, R1CS_PATH, "out.zkey", "--fast"]);
+    assert!(matches!(flag, Err(SnarkError::InvalidArguments(msg)) if msg.contains("--fast")));
+}
+
+#[test]
+fn test_parse_args_config() -> snark_rs::Result<()> {
+    let dir = tempdir()?;
+    let config_path = dir.path().join("setup.json");
+    let config = config_path.to_str().unwrap();
+    std::fs::write(
+        config,
+        format!(
+            r#"{{"ptau": "{}", "r1cs": "{}", "out": "from_config.zkey", "curve": "bn254"}}"#,
+            PTAU_PATH, R1CS_PATH
+        ),
+    )?;
+
+    let command = parse_args(["plonk", "setup", "--config", config])?;
+    // The configured curve is kept, spelled as in the file.
+    assert_eq!(
+        command,
+        Command::Setup(SetupConfig {
+            curve: "bn254".to_string(),
+            ..SetupConfig::new(PTAU_PATH, R1CS_PATH, "from_config.zkey")
+        })
+    );
+
+    // Paths and flags on the command line win over the file.
+    let command = parse_args([
+        "plonk", "setup", "-c", config, PTAU_PATH, R1CS_PATH, "cli.zkey", "-v",
+    ])?;
+    assert_eq!(
+        command,
+        Command::Setup(SetupConfig {
+            curve: "bn254".to_string(),
+            verbose: true,
+            ..SetupConfig::new(PTAU_PATH, R1CS_PATH, "cli.zkey")
+        })
+    );
+
+    std::fs::write(
+        config,
+        format!(
+            r#"{{"ptau": "{}", "r1cs": "{}", "out": "a.zkey", "curve": "bls12-381"}}"#,
+            PTAU_PATH, R1CS_PATH
+        ),
+    )?;
+    let unsupported = parse_args(["plonk", "setup", "--config", config]);
+    assert!(matches!(unsupported, Err(SnarkError::CurveNotSupported(_))));
+
+    let no_file = parse_args(["plonk", "setup", "--config"]);
+    assert!(matches!(no_file, Err(SnarkError::InvalidArguments(msg)) if msg.contains("--config")));
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use snark_rs::plonk_setup;
+use std::cell::RefCell;
+use tempfile::tempdir;
+
+const PTAU_PATH: &str = "src/artifacts/pot8.ptau";
+const R1CS_PATH: &str = "src/artifacts/small.r1cs";
+
+#[tokio::test]
+async fn test_plonk_setup_writes_zkey() -> snark_rs::Result<()> {
+    let dir = tempdir()?;
+    let out_path = dir.path().join("output.zkey");
+
+    plonk_setup(PTAU_PATH, R1CS_PATH, out_path.to_str().unwrap(), None).await?;
+
+    let bytes = std::fs::read(&out_path)?;
+    assert_eq!(&bytes[0..4], b"zkey");
+    assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 1);
+    assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), 14);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plonk_setup_reports_progress() -> snark_rs::Result<()> {
+    let dir = tempdir()?;
+    let out_path = dir.path().join("output.zkey");
+
+    let calls = RefCell::new(Vec::new());
+    let progress = |stage: &str, done: usize, total: usize| {
+        calls.borrow_mut().push((stage.to_string(), done, total));
+    };
+    plonk_setup(PTAU_PATH, R1CS_PATH, out_path.to_str().unwrap(), Some(&progress)).await?;
+
+    let calls = calls.into_inner();
+    let stages: Vec<&str> = calls.iter().map(|(stage, _, _)| stage.as_str()).collect();
+    for stage in ["Constraints", "Additions", "Amap", "Bmap", "Cmap", "Qm", "Qc"] {
+        assert!(stages.contains(&stage), "missing progress for {}", stage);
+    }
+    assert!(calls.contains(&("Constraints".to_string(), 38, 38)));
+    assert!(calls.iter().all(|(_, done, total)| done <= total));
+
+    Ok(())
+}